@@ -0,0 +1,4 @@
+fn main() {
+    // install.rs compiles grammars at runtime, and cc needs to know what it's compiling for
+    println!("cargo:rustc-env=TARGET={}", std::env::var("TARGET").unwrap());
+}
@@ -0,0 +1,123 @@
+// Every step of +highlight and +render timed on its own, for a small, medium and huge bit of URCL:
+//
+//     cargo bench -p custom-highlight-core
+//
+// events is tree-sitter (or the regex rules) figuring out what's what, and ansi is that turned into
+// +highlight's output. layout highlights it too, so it's events plus shaping and wrapping every line.
+// rasterize is drawing the glyphs and encode is the PNG, those two only start from the step before.
+// Criterion keeps the last run around, so a slower build shows up as a regression
+use std::{collections::HashMap, sync::Arc};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use custom_highlight_core::{
+    cache, highlight_events, languages,
+    render::{self, Encoding, RenderOptions},
+    syntax_highlight, theme, LanguageConfig, DEFAULT_TAB_WIDTH,
+};
+
+// the example from languages.toml, since urcl is always compiled in
+const SNIPPET: &str = "BITS 8\nIMM R1 5\n.loop\nDEC R1 R1\nBNZ .loop R1\nHLT\n";
+
+// (name, how many times the snippet is repeated). huge still has to fit in the 10 images a render can be
+const SIZES: &[(&str, usize)] = &[("small", 1), ("medium", 20), ("huge", 250)];
+
+fn urcl() -> Arc<LanguageConfig> {
+    let languages: HashMap<String, Arc<LanguageConfig>> =
+        languages::load_from(concat!(env!("CARGO_MANIFEST_DIR"), "/../languages.toml"))
+            .unwrap_or_else(|e| panic!("{e}"));
+    languages["urcl"].clone()
+}
+
+fn options() -> RenderOptions {
+    RenderOptions {
+        theme: theme::DEFAULT,
+        line_numbers: true,
+        title: None,
+        wrap: Some(render::DEFAULT_WRAP_WIDTH),
+        tab_width: DEFAULT_TAB_WIDTH,
+        font: None,
+        size: render::DEFAULT_TEXT_SIZE,
+        encoding: Encoding::Png,
+        range: None,
+        emphasis: Vec::new(),
+        transparent: false,
+        animate: false,
+        spoiler: false,
+    }
+}
+
+fn stages(c: &mut Criterion) {
+    let config = urcl();
+    let options = options();
+    for &(size, repeat) in SIZES {
+        let code = SNIPPET.repeat(repeat);
+        let mut group = c.benchmark_group(size);
+        group.throughput(Throughput::Bytes(code.len() as u64));
+
+        // these would only be timing the cache after the first time, see cache.rs
+        group.bench_function("events", |b| {
+            b.iter_batched(
+                cache::clear,
+                |_| highlight_events(&config, &code).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function("ansi", |b| {
+            b.iter_batched(
+                cache::clear,
+                |_| syntax_highlight(&config, &code, theme::DEFAULT, DEFAULT_TAB_WIDTH).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function("layout", |b| {
+            b.iter_batched(
+                cache::clear,
+                |_| render::lay_out(&config, &code, &options).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+
+        let layout = render::lay_out(&config, &code, &options).unwrap();
+        group.bench_function("rasterize", |b| {
+            b.iter(|| render::rasterize(&layout, &options).unwrap())
+        });
+
+        let images = render::rasterize(&layout, &options).unwrap();
+        group.bench_function("encode", |b| {
+            b.iter(|| {
+                images
+                    .iter()
+                    .map(|image| render::encode(image, Encoding::Png).unwrap())
+                    .collect::<Vec<_>>()
+            })
+        });
+        group.finish();
+    }
+}
+
+// the other encodings are only tried for big renders, see Encoding::Auto, so they only get the huge one
+fn encodings(c: &mut Criterion) {
+    let config = urcl();
+    let options = options();
+    let (_, repeat) = SIZES[SIZES.len() - 1];
+    let images = render::render(&config, &SNIPPET.repeat(repeat), &options).unwrap();
+    let mut group = c.benchmark_group("encoding");
+    for (name, encoding) in [
+        ("png", Encoding::Png),
+        ("indexed", Encoding::Indexed),
+        ("webp", Encoding::WebP),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| render::encode(&images[0], encoding).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    // the huge ones take a while, and this is plenty to spot a regression
+    config = Criterion::default().sample_size(20);
+    targets = stages, encodings
+}
+criterion_main!(benches);
@@ -0,0 +1,203 @@
+// chl, for highlighting and rendering code without going through discord at all:
+//
+//     chl [--lang LANG] [--theme THEME] [--format ansi|png|svg|html] [--output FILE] [FILE]
+//
+// It reads FILE (or stdin if there isn't one) and writes to --output (or stdout).
+// The languages come from languages.toml just like they do for the bot, see --languages
+use std::{
+    error::Error,
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+    process,
+};
+
+use custom_highlight_core::{
+    html, languages,
+    render::{self, Encoding, RenderOptions},
+    svg, syntax_highlight, theme, DEFAULT_TAB_WIDTH,
+};
+
+const USAGE: &str = "usage: chl [--lang LANG] [--theme THEME] [--format ansi|png|svg|html] \
+    [--line-numbers] [--output FILE] [--languages PATH] [FILE]";
+
+enum Output {
+    Ansi,
+    Png,
+    Svg,
+    Html,
+}
+
+struct Args {
+    lang: Option<String>,
+    theme: Option<String>,
+    format: Output,
+    // only for png, the other formats don't have line numbers
+    line_numbers: bool,
+    output: Option<String>,
+    languages: String,
+    input: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        lang: None,
+        theme: None,
+        format: Output::Ansi,
+        line_numbers: false,
+        output: None,
+        languages: languages::LANGUAGES_PATH.to_owned(),
+        input: None,
+    };
+    let mut words = std::env::args().skip(1);
+    while let Some(word) = words.next() {
+        let mut value = || words.next().ok_or_else(|| format!("{word} needs a value"));
+        match word.as_str() {
+            "--lang" | "-l" => args.lang = Some(value()?),
+            "--theme" | "-t" => args.theme = Some(value()?),
+            "--format" | "-f" => {
+                args.format = match value()?.to_lowercase().as_str() {
+                    "ansi" => Output::Ansi,
+                    "png" => Output::Png,
+                    "svg" => Output::Svg,
+                    "html" => Output::Html,
+                    other => {
+                        return Err(format!(
+                            "--format can only be ansi, png, svg or html, not {other}"
+                        ))
+                    }
+                }
+            }
+            "--line-numbers" => args.line_numbers = true,
+            "--output" | "-o" => args.output = Some(value()?),
+            "--languages" => args.languages = value()?,
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                process::exit(0);
+            }
+            _ if word.starts_with('-') && word != "-" => {
+                return Err(format!("unknown option {word}"))
+            }
+            // - is stdin, same as leaving it out
+            _ if args.input.is_none() => args.input = Some(word).filter(|word| word != "-"),
+            _ => return Err("only one file at a time".to_owned()),
+        }
+    }
+    Ok(args)
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("chl: {e}");
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args = parse_args().map_err(|e| format!("{e}\n{USAGE}"))?;
+    let code = match args.input {
+        Some(ref path) => {
+            fs::read_to_string(path).map_err(|e| format!("couldn't read {path}: {e}"))?
+        }
+        None => {
+            let mut code = String::new();
+            io::stdin()
+                .read_to_string(&mut code)
+                .map_err(|e| format!("couldn't read stdin: {e}"))?;
+            code
+        }
+    };
+    // the bot normalizes these too, \r would just show up as garbage
+    let code = code.replace("\r\n", "\n");
+
+    let languages = languages::load_from(&args.languages)?;
+    let config = match args.lang {
+        Some(ref lang) => languages
+            .get(&lang.to_lowercase())
+            .ok_or_else(|| format!("there's no language called {lang}"))?,
+        // the file extension is usually one of the aliases, and otherwise it's just plain text
+        None => args
+            .input
+            .as_deref()
+            .and_then(|path| Path::new(path).extension()?.to_str())
+            .and_then(|extension| languages.get(&extension.to_lowercase()))
+            .unwrap_or(&languages[""]),
+    };
+    let theme = match args.theme {
+        Some(ref name) => theme::by_name(name).ok_or_else(|| {
+            format!(
+                "there's no theme called {name}, it can be one of {}",
+                theme::names()
+            )
+        })?,
+        None => theme::DEFAULT,
+    };
+
+    let output = match args.format {
+        Output::Ansi => {
+            let mut ansi = syntax_highlight(config, &code, theme, DEFAULT_TAB_WIDTH)?;
+            // so the terminal isn't left in whatever color the code ended with
+            ansi.push_str("\u{001b}[0m");
+            ansi.into_bytes()
+        }
+        Output::Html => html::highlight_html(config, &code, theme, DEFAULT_TAB_WIDTH)?.into_bytes(),
+        Output::Svg => svg::highlight_svg(config, &code, theme, DEFAULT_TAB_WIDTH)?.into_bytes(),
+        Output::Png => {
+            let options = RenderOptions {
+                theme,
+                line_numbers: args.line_numbers,
+                title: None,
+                wrap: Some(render::DEFAULT_WRAP_WIDTH),
+                tab_width: DEFAULT_TAB_WIDTH,
+                font: None,
+                size: render::DEFAULT_TEXT_SIZE,
+                encoding: Encoding::Png,
+                range: None,
+                emphasis: Vec::new(),
+                transparent: false,
+                animate: false,
+                spoiler: false,
+            };
+            // there's no upload limit here, so it's never made smaller
+            let draw = |options: &RenderOptions| render::render(config, &code, options);
+            let (mut files, _) = render::render_to_fit(draw, options, usize::MAX)?;
+            if files.len() > 1 {
+                return write_parts(&files, args.output.as_deref());
+            }
+            files.remove(0).0
+        }
+    };
+    match args.output {
+        Some(ref path) => {
+            fs::write(path, output).map_err(|e| format!("couldn't write {path}: {e}"))?
+        }
+        None => io::stdout()
+            .write_all(&output)
+            .map_err(|e| format!("couldn't write to stdout: {e}"))?,
+    }
+    Ok(())
+}
+
+// Really tall renders are split into a few images, and those can't all go to stdout,
+// so they're numbered like the bot numbers its attachments: code-1.png, code-2.png, ...
+fn write_parts(
+    files: &[(Vec<u8>, &'static str)],
+    output: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let output = output.ok_or_else(|| {
+        format!(
+            "that's too tall for one image, it's {} of them. --output can save them all",
+            files.len()
+        )
+    })?;
+    let path = Path::new(output);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("code");
+    for (i, (bytes, _)) in files.iter().enumerate() {
+        let part = path.with_file_name(format!("{stem}-{}.png", i + 1));
+        fs::write(&part, bytes).map_err(|e| format!("couldn't write {}: {e}", part.display()))?;
+    }
+    Ok(())
+}
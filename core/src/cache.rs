@@ -0,0 +1,113 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Mutex,
+};
+
+use super::*;
+use lru::LruCache;
+
+// What tree-sitter made of the last few codeblocks, so pressing Highlight, then Render, then Pretty Parse
+// on the same huge codeblock only has it highlighted once and parsed once. This is just in memory and
+// only for this process, unlike the bot's render cache, which is whole images (see src/cache.rs).
+//
+// The highlights are kept as which capture goes where, not as ANSI, since the colors can change
+// (with a theme, or /theme set-capture) and turning them into ANSI is the cheap part anyway.
+// Both are keyed by the language's fingerprint and a hash of the code, so a language with a new
+// query doesn't get the old highlights. HIGHLIGHT_CACHE_SIZE is how many of each are kept
+const DEFAULT_HIGHLIGHT_CACHE_SIZE: usize = 16;
+
+lazy_static! {
+    static ref SIZE: usize = config::parse("HIGHLIGHT_CACHE_SIZE")
+        .filter(|&size| size > 0)
+        .unwrap_or(DEFAULT_HIGHLIGHT_CACHE_SIZE);
+    static ref EVENTS: Mutex<LruCache<Key, Vec<Event>>> = Mutex::new(LruCache::new(*SIZE));
+    static ref TREES: Mutex<LruCache<Key, Tree>> = Mutex::new(LruCache::new(*SIZE));
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    fingerprint: u64,
+    // not that two codeblocks are ever going to have the same hash, but it's free
+    len: usize,
+    hash: u64,
+}
+
+impl Key {
+    fn new(config: &LanguageConfig, code: &str) -> Key {
+        let mut hasher = DefaultHasher::new();
+        code.hash(&mut hasher);
+        Key {
+            fingerprint: config.fingerprint,
+            len: code.len(),
+            hash: hasher.finish(),
+        }
+    }
+}
+
+// HighlightEvent can't be cloned, so this is what's kept instead
+#[derive(Clone, Copy)]
+enum Event {
+    Source(usize, usize),
+    Start(usize),
+    End,
+}
+
+impl From<&HighlightEvent> for Event {
+    fn from(event: &HighlightEvent) -> Event {
+        match *event {
+            HighlightEvent::Source { start, end } => Event::Source(start, end),
+            HighlightEvent::HighlightStart(Highlight(u)) => Event::Start(u),
+            HighlightEvent::HighlightEnd => Event::End,
+        }
+    }
+}
+
+impl From<Event> for HighlightEvent {
+    fn from(event: Event) -> HighlightEvent {
+        match event {
+            Event::Source(start, end) => HighlightEvent::Source { start, end },
+            Event::Start(u) => HighlightEvent::HighlightStart(Highlight(u)),
+            Event::End => HighlightEvent::HighlightEnd,
+        }
+    }
+}
+
+// The events from last time this exact code was highlighted, or highlight's if it wasn't.
+// Whatever went wrong isn't kept, so that's tried again every time
+pub fn events(
+    config: &LanguageConfig,
+    code: &str,
+    highlight: impl FnOnce() -> Result<Vec<HighlightEvent>, HighlightError>,
+) -> Result<Vec<HighlightEvent>, HighlightError> {
+    let key = Key::new(config, code);
+    // the lock isn't held while it's highlighting, so nothing else waits on this one
+    if let Some(cached) = EVENTS.lock().unwrap().get(&key) {
+        return Ok(cached.iter().copied().map(HighlightEvent::from).collect());
+    }
+    let events = highlight()?;
+    let cached = events.iter().map(Event::from).collect();
+    EVENTS.lock().unwrap().put(key, cached);
+    Ok(events)
+}
+
+// Same as events, but for the syntax tree. Trees are reference counted, so they're cheap to hand out
+pub fn tree<E>(
+    config: &LanguageConfig,
+    code: &str,
+    parse: impl FnOnce() -> Result<Tree, E>,
+) -> Result<Tree, E> {
+    let key = Key::new(config, code);
+    if let Some(tree) = TREES.lock().unwrap().get(&key) {
+        return Ok(tree.clone());
+    }
+    let tree = parse()?;
+    TREES.lock().unwrap().put(key, tree.clone());
+    Ok(tree)
+}
+
+// For the benches, which want to time tree-sitter and not this
+pub fn clear() {
+    EVENTS.lock().unwrap().clear();
+    TREES.lock().unwrap().clear();
+}
@@ -0,0 +1,48 @@
+use std::{fs, str::FromStr};
+
+use super::*;
+
+// Settings for whoever is hosting it, like RENDER_PIXEL_BUDGET or DISCORD_TOKEN.
+// Every one of them can be an environment variable, or go in config.toml (or wherever CONFIG_PATH
+// points) with the same name in lowercase, like render_pixel_budget = 50000000.
+// The environment wins, so one run can change something without editing the file
+pub const CONFIG_PATH: &str = "config.toml";
+
+lazy_static! {
+    static ref FILE: toml::value::Table = {
+        let path = std::env::var("CONFIG_PATH").unwrap_or_else(|_| CONFIG_PATH.to_owned());
+        match fs::read_to_string(&path) {
+            // it's optional, the environment (or the defaults) can be enough
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(e) => panic!("couldn't read {path}: {e}"),
+            Ok(source) => toml::from_str(&source)
+                .unwrap_or_else(|e| panic!("couldn't parse {path}: {e}")),
+        }
+    };
+}
+
+// called at startup, so a broken config.toml fails then and not whenever it's first needed
+pub fn load() {
+    lazy_static::initialize(&FILE);
+}
+
+pub fn var(name: &str) -> Option<String> {
+    if let Ok(value) = std::env::var(name) {
+        return Some(value);
+    }
+    match FILE.get(&name.to_lowercase())? {
+        toml::Value::String(value) => Some(value.clone()),
+        // numbers and booleans are written without quotes, but they're parsed the same way
+        value => Some(value.to_string()),
+    }
+}
+
+// A setting that isn't what it should be is ignored, just like one that isn't there
+pub fn parse<T: FromStr>(name: &str) -> Option<T> {
+    let value = var(name)?;
+    let parsed = value.parse().ok();
+    if parsed.is_none() {
+        warn!("{name} can't be {value:?}, so it's ignored");
+    }
+    parsed
+}
@@ -0,0 +1,74 @@
+use std::error::Error;
+
+use super::*;
+
+// Everything that can go wrong while highlighting, parsing or rendering.
+// What it says is meant for whoever asked for it, and the rest is for the logs,
+// so whatever shows it to someone should log it with {:?} too
+#[derive(Debug, thiserror::Error)]
+pub enum HighlightError {
+    // tree-sitter itself failing, which is never because of a syntax error in the code.
+    // the underlying errors don't all implement Error, so it's only kept as its Debug
+    #[error("internal error from tree-sitter (not a syntax error)")]
+    TreeSitter {
+        language: String,
+        // how far into the code it got before it failed, if it got started at all
+        offset: Option<usize>,
+        reason: String,
+    },
+    #[error("This language doesn't have parsing support")]
+    NoGrammar { language: String },
+    #[error("That part of the tree isn't there anymore, was the code edited?")]
+    NodeGone { path: Vec<usize> },
+    #[error("The {what} failed to serialize")]
+    Serialize {
+        what: &'static str,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("The {what} failed to encode")]
+    Encode {
+        what: &'static str,
+        #[source]
+        source: Box<dyn Error + Send + Sync>,
+    },
+    #[error("There aren't that many lines")]
+    OutOfRange { first: usize, lines: usize },
+    // too big to draw or to send, and the message already says what to do about it
+    #[error("{0}")]
+    TooBig(String),
+}
+
+impl HighlightError {
+    // Whether something actually broke, as opposed to the code being too big or not parseable.
+    // Only those are worth logging
+    pub fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            HighlightError::TreeSitter { .. }
+                | HighlightError::Serialize { .. }
+                | HighlightError::Encode { .. }
+        )
+    }
+
+    pub fn tree_sitter<E: Debug>(
+        config: &LanguageConfig,
+        offset: Option<usize>,
+    ) -> impl FnOnce(E) -> HighlightError {
+        let language = config.name.clone();
+        move |e| HighlightError::TreeSitter {
+            language,
+            offset,
+            reason: format!("{e:?}"),
+        }
+    }
+
+    pub fn encode<E: Error + Send + Sync + 'static>(
+        what: &'static str,
+    ) -> impl FnOnce(E) -> HighlightError {
+        move |e| HighlightError::Encode {
+            what,
+            source: Box::new(e),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt::Write as _;
+
+use super::*;
+
+// +highlight html (and chl --format html), a standalone page with the code highlighted like a render,
+// for putting it in docs or on a website. Everything is inline so it's just the one file
+
+pub fn highlight_html(
+    config: &LanguageConfig,
+    code: &str,
+    theme: &Theme,
+    tab_width: usize,
+) -> Result<String, HighlightError> {
+    let frame = theme.frame;
+    let reset = theme.get(RESET);
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    writeln!(out, "<title>{}</title>", escape(&config.name)).unwrap();
+    out.push_str("<style>\n");
+    writeln!(
+        out,
+        "pre {{ background: {}; border: {}px solid {}; border-radius: {}px; padding: {}px; color: {}; tab-size: {tab_width}; }}",
+        hex(frame.background),
+        frame.border_width,
+        hex(frame.border),
+        frame.radius,
+        frame.padding,
+        hex(reset.rgb)
+    )
+    .unwrap();
+    out.push_str("</style>\n</head>\n<body>\n<pre><code>");
+
+    let formats = config.formats.read().unwrap();
+    let mut styles = ne_vec![reset];
+    for event in highlight_events(config, code)? {
+        match event {
+            HighlightEvent::HighlightStart(Highlight(u)) => {
+                let style = theme.resolve(formats[u]).inside(*styles.last());
+                write!(out, "<span style=\"{}\">", css(style)).unwrap();
+                styles.push(style);
+            }
+            HighlightEvent::Source { start, end } => out.push_str(&escape(&code[start..end])),
+            HighlightEvent::HighlightEnd => {
+                styles.pop();
+                out.push_str("</span>");
+            }
+        }
+    }
+    out.push_str("</code></pre>\n</body>\n</html>\n");
+    Ok(out)
+}
+
+fn css(style: Style) -> String {
+    let mut css = format!("color: {}", hex(style.rgb));
+    if let Some(background) = style.background {
+        write!(css, "; background: {}", hex(background)).unwrap();
+    }
+    if style.bold {
+        css.push_str("; font-weight: bold");
+    }
+    if style.italic {
+        css.push_str("; font-style: italic");
+    }
+    if style.underline {
+        css.push_str("; text-decoration: underline");
+    }
+    css
+}
+
+// the svg uses these too
+pub fn hex(Rgb([r, g, b]): Rgb<u8>) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,329 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+};
+
+use super::*;
+use serde::Deserialize;
+use tree_sitter::QueryError;
+
+pub const LANGUAGES_PATH: &str = "languages.toml";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LanguageEntry {
+    #[serde(default)]
+    grammar: Option<String>,
+    #[serde(default)]
+    rules: Vec<RuleEntry>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default = "default_auto_respond")]
+    auto_respond: bool,
+    #[serde(default)]
+    example: Option<String>,
+    #[serde(default)]
+    standard_captures: bool,
+    #[serde(default)]
+    captures: HashMap<String, String>,
+}
+
+// The capture names most mainstream grammars use in their highlight queries.
+// Languages with ``standard_captures = true`` get all of these, and their own captures go on top.
+const STANDARD_CAPTURES: &[(&str, ColorName)] = &[
+    ("comment", GRAY),
+    ("keyword", PINK),
+    ("operator", GRAY),
+    ("punctuation.delimiter", GRAY),
+    ("punctuation.bracket", GRAY),
+    ("punctuation.special", GRAY),
+    ("function", YELLOW),
+    ("function.method", YELLOW),
+    ("function.builtin", YELLOW),
+    ("function.macro", PINK),
+    ("constructor", DARK_GREEN),
+    ("type", DARK_GREEN),
+    ("type.builtin", DARK_BLUE),
+    ("label", YELLOW),
+    ("attribute", PINK),
+    ("property", BLUE),
+    ("variable.builtin", DARK_BLUE),
+    ("variable.parameter", WHITE),
+    ("constant", DARK_BLUE),
+    ("constant.builtin", DARK_BLUE),
+    ("number", LIGHT_GREEN),
+    ("string", CYAN),
+    ("string.special", CYAN),
+    ("escape", YELLOW),
+    ("embedded", RESET),
+];
+
+// grammars that are only compiled in with their cargo feature, see Cargo.toml
+const OPTIONAL_GRAMMARS: &[&str] = &["rust", "c", "python", "json"];
+
+// for languages without a grammar
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    pattern: String,
+    color: String,
+    // what /theme set-capture calls this rule, defaults to the pattern itself
+    #[serde(default)]
+    name: Option<String>,
+}
+
+fn default_auto_respond() -> bool {
+    true
+}
+
+// the grammars themselves are still compiled into the bot, the config file can only pick between them
+fn grammar(name: &str) -> Option<(Language, &'static str)> {
+    Some(match name {
+        "ursl" => (tree_sitter_ursl::language(), tree_sitter_ursl::HIGHLIGHTS_QUERY),
+        "urcl" => (tree_sitter_urcl::language(), tree_sitter_urcl::HIGHLIGHTS_QUERY),
+        "phinix" => (
+            tree_sitter_phinix::language(),
+            tree_sitter_phinix::HIGHLIGHTS_QUERY,
+        ),
+        "hexagn" => (
+            tree_sitter_hexagn::language(),
+            tree_sitter_hexagn::HIGHLIGHTS_QUERY,
+        ),
+        #[cfg(feature = "lang-rust")]
+        "rust" => (tree_sitter_rust::language(), tree_sitter_rust::HIGHLIGHT_QUERY),
+        #[cfg(feature = "lang-c")]
+        "c" => (tree_sitter_c::language(), tree_sitter_c::HIGHLIGHT_QUERY),
+        #[cfg(feature = "lang-python")]
+        "python" => (
+            tree_sitter_python::language(),
+            tree_sitter_python::HIGHLIGHT_QUERY,
+        ),
+        #[cfg(feature = "lang-json")]
+        "json" => (tree_sitter_json::language(), tree_sitter_json::HIGHLIGHT_QUERY),
+        _ => return None,
+    })
+}
+
+fn fingerprint(source: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn highlight_configuration(
+    language: Language,
+    query: &str,
+    captures: &[String],
+) -> Result<HighlightConfiguration, QueryError> {
+    let mut highlight =
+        HighlightConfiguration::new(language, &format!("(ERROR) @error\n{query}"), "", "")?;
+    highlight.configure(captures);
+    Ok(highlight)
+}
+
+impl LanguageEntry {
+    fn build(&self, name: &str) -> Result<LanguageConfig, String> {
+        match (&self.grammar, self.rules.is_empty()) {
+            (Some(grammar), true) => self.build_tree_sitter(name, grammar),
+            (None, false) => self.build_regex(name),
+            _ => Err(format!("{name}: needs either a grammar or regex rules, but not both")),
+        }
+    }
+
+    fn build_regex(&self, name: &str) -> Result<LanguageConfig, String> {
+        if !self.captures.is_empty() || self.standard_captures {
+            return Err(format!("{name}: regex rules have their own colors, captures don't do anything"));
+        }
+        let mut rules = Vec::new();
+        let mut patterns = Vec::new();
+        let mut captures = Vec::new();
+        let mut formats = Vec::new();
+        for rule in &self.rules {
+            rules.push(
+                Regex::new(&rule.pattern)
+                    .map_err(|e| format!("{name}: {:?} isn't a valid regex: {e}", rule.pattern))?,
+            );
+            patterns.push(&rule.pattern);
+            captures.push(rule.name.clone().unwrap_or_else(|| rule.pattern.clone()));
+            formats.push(Format::from(ColorName::by_name(&rule.color).ok_or_else(|| {
+                format!("{name}: {:?} has unknown color {:?}", rule.pattern, rule.color)
+            })?));
+        }
+        Ok(LanguageConfig {
+            name: name.to_owned(),
+            grammar: String::new(),
+            source: GrammarSource::BuiltIn,
+            aliases: self.aliases.clone(),
+            example: self.example.clone(),
+            highlight: HighlightType::Regex(rules),
+            captures,
+            formats: Arc::new(std::sync::RwLock::new(formats)),
+            language: None,
+            auto_respond: self.auto_respond,
+            fingerprint: fingerprint((name, patterns)),
+        })
+    }
+
+    fn build_tree_sitter(&self, name: &str, grammar_name: &str) -> Result<LanguageConfig, String> {
+        let (language, query) = grammar(grammar_name)
+            .ok_or_else(|| format!("{name}: there is no grammar called {grammar_name:?}"))?;
+        self.with_grammar(name, grammar_name, GrammarSource::BuiltIn, language, query)
+    }
+
+    fn with_grammar(
+        &self,
+        name: &str,
+        grammar_name: &str,
+        source: GrammarSource,
+        language: Language,
+        query: &str,
+    ) -> Result<LanguageConfig, String> {
+        let mut captures = HashMap::new();
+        captures.insert("error", ERROR);
+        if self.standard_captures {
+            captures.extend(STANDARD_CAPTURES.iter().copied());
+        }
+        for (capture, color) in &self.captures {
+            captures.insert(
+                capture.as_str(),
+                ColorName::by_name(color)
+                    .ok_or_else(|| format!("{name}: {capture} has unknown color {color:?}"))?,
+            );
+        }
+        let (captures, formats): (Vec<String>, Vec<Format>) = captures
+            .into_iter()
+            .map(|(capture, color)| (capture.to_owned(), Format::from(color)))
+            .unzip();
+        let highlight = highlight_configuration(language, query, &captures)
+            .map_err(|e| format!("{name}: the highlight query failed to compile: {e:?}"))?;
+        // an installed grammar can have the same name as another one, the pool and caches can't mix them up
+        let fingerprint = fingerprint((name, grammar_name, &source, query));
+        Ok(LanguageConfig {
+            name: name.to_owned(),
+            grammar: grammar_name.to_owned(),
+            source,
+            aliases: self.aliases.clone(),
+            example: self.example.clone(),
+            highlight: HighlightType::TreeSitter(highlight),
+            captures,
+            formats: Arc::new(std::sync::RwLock::new(formats)),
+            language: Some(language),
+            auto_respond: self.auto_respond,
+            fingerprint,
+        })
+    }
+}
+
+// For grammars that aren't in languages.toml (see install.rs), so all they get is the standard captures
+pub fn standard_config(
+    name: &str,
+    grammar_name: &str,
+    source: GrammarSource,
+    language: Language,
+    query: &str,
+) -> Result<LanguageConfig, String> {
+    LanguageEntry {
+        grammar: Some(grammar_name.to_owned()),
+        rules: Vec::new(),
+        aliases: Vec::new(),
+        auto_respond: false,
+        example: None,
+        standard_captures: true,
+        captures: HashMap::new(),
+    }
+    .with_grammar(name, grammar_name, source, language, query)
+}
+
+impl LanguageConfig {
+    // The same language with the same colors, but highlighted by a different query
+    // The error is meant to be shown to whoever wrote the query
+    pub fn with_query(&self, query: &str) -> Result<LanguageConfig, String> {
+        let language = self
+            .language
+            .ok_or("This language doesn't have a grammar, so it can't have a query")?;
+        let highlight = highlight_configuration(language, query, &self.captures).map_err(|e| {
+            // the (ERROR) line is prepended, so the 0-based row is the 1-based line in their query
+            format!("Line {}, column {}: {:?} {}", e.row, e.column + 1, e.kind, e.message)
+        })?;
+        Ok(LanguageConfig {
+            name: self.name.clone(),
+            grammar: self.grammar.clone(),
+            source: self.source.clone(),
+            aliases: self.aliases.clone(),
+            example: self.example.clone(),
+            highlight: HighlightType::TreeSitter(highlight),
+            captures: self.captures.clone(),
+            // shared, so changing a color also changes it for every guild's override
+            formats: self.formats.clone(),
+            language: self.language,
+            auto_respond: self.auto_respond,
+            fingerprint: fingerprint((self.fingerprint, query)),
+        })
+    }
+}
+
+pub fn load() -> Result<HashMap<String, Arc<LanguageConfig>>, String> {
+    load_from(LANGUAGES_PATH)
+}
+
+// chl can be run from anywhere, so it can point at a languages.toml somewhere else
+pub fn load_from(path: &str) -> Result<HashMap<String, Arc<LanguageConfig>>, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("couldn't read {path}: {e}"))?;
+    let entries: HashMap<String, LanguageEntry> =
+        toml::from_str(&source).map_err(|e| format!("couldn't parse {path}: {e}"))?;
+
+    let mut languages = HashMap::new();
+    // "" is the plaintext highlighting, so you can test rendering without a lang
+    // do not respond to plain codeblocks lmao
+    languages.insert(
+        String::new(),
+        Arc::new(LanguageConfig {
+            name: String::new(),
+            grammar: String::new(),
+            source: GrammarSource::BuiltIn,
+            aliases: Vec::new(),
+            example: None,
+            highlight: HighlightType::Plaintext,
+            captures: Vec::new(),
+            formats: Default::default(),
+            language: None,
+            auto_respond: false,
+            fingerprint: 0,
+        }),
+    );
+    for (name, entry) in entries {
+        if let Some(ref name_of_grammar) = entry.grammar {
+            if grammar(name_of_grammar).is_none() && OPTIONAL_GRAMMARS.contains(&&**name_of_grammar) {
+                warn!("skipping {name}, the {name_of_grammar} grammar wasn't compiled in");
+                continue;
+            }
+        }
+        let config = Arc::new(entry.build(&name)?);
+        for key in iter::once(&name).chain(&entry.aliases) {
+            // lookups are lowercased, so the keys have to be too
+            if languages
+                .insert(key.to_lowercase(), config.clone())
+                .is_some()
+            {
+                return Err(format!("{name}: {key:?} is already used by another language"));
+            }
+        }
+    }
+    Ok(languages)
+}
+
+// Only existing captures can be changed, adding a new one would mean recompiling the query
+pub fn set_capture_color(
+    config: &LanguageConfig,
+    capture: &str,
+    format: Format,
+) -> Result<(), String> {
+    let index = config
+        .captures
+        .iter()
+        .position(|name| name == capture)
+        .ok_or_else(|| format!("{} doesn't have a capture called {capture}", config.name))?;
+    config.formats.write().unwrap()[index] = format;
+    Ok(())
+}
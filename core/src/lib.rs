@@ -0,0 +1,725 @@
+// Everything the bot does to code that doesn't need serenity: the languages, the themes,
+// highlighting with ANSI, parsing, and rendering images. The bot is a binary on top of this
+// and so is chl (see src/bin/chl.rs). Anything logged goes through tracing, and chl doesn't
+// set up a subscriber, so its stdout is only ever the output itself
+pub mod cache;
+pub mod config;
+pub mod error;
+pub mod html;
+pub mod languages;
+pub mod markdown;
+pub mod pool;
+pub mod render;
+pub mod svg;
+pub mod theme;
+
+pub use error::HighlightError;
+pub use pool::Pooled;
+
+use std::{borrow::Cow, collections::HashMap, fmt::Debug, iter, sync::Arc};
+
+use image::Rgb;
+use lazy_static::lazy_static;
+use non_empty_vec::ne_vec;
+use regex::Regex;
+use theme::Theme;
+use tracing::{debug, error, info, info_span, warn};
+use tree_sitter::{Language, Parser, Tree, TreeCursor};
+use tree_sitter_highlight::{Highlight, HighlightConfiguration, HighlightEvent};
+
+// There's no Wasm variant for sandboxed community grammars (yet). Loading grammars compiled to
+// WebAssembly needs tree-sitter's wasmtime backend, which only exists from tree-sitter 0.22 onwards,
+// and this bot (and every grammar crate it depends on) is still on 0.20. That has to be upgraded
+// all at once before a Wasm variant can exist here.
+enum HighlightType {
+    TreeSitter(HighlightConfiguration),
+    // for languages without a grammar, see regex_events
+    Regex(Vec<Regex>),
+    Plaintext,
+}
+
+// Where a grammar came from, for /about grammars
+#[derive(Clone, Debug, Hash)]
+pub enum GrammarSource {
+    // compiled into the bot, or no grammar at all
+    BuiltIn,
+    // cloned and compiled with /grammar install. library is a hash of the compiled grammar,
+    // so it's clear whether a reinstall actually changed anything
+    Dylib { url: String, library: u64 },
+}
+
+impl std::fmt::Display for GrammarSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GrammarSource::BuiltIn => write!(f, "built-in"),
+            GrammarSource::Dylib { url, library } => write!(f, "dylib {library:016x} {url}"),
+        }
+    }
+}
+
+pub struct LanguageConfig {
+    pub name: String,
+    // which of the compiled in grammars this is, empty for plaintext
+    pub grammar: String,
+    pub source: GrammarSource,
+    pub aliases: Vec<String>,
+    pub example: Option<String>,
+    highlight: HighlightType,
+    // the capture names the highlighter recognizes, and the color for each one
+    // the colors can be changed at runtime, see /theme set-capture
+    pub captures: Vec<String>,
+    pub formats: Arc<std::sync::RwLock<Vec<Format>>>,
+    pub language: Option<Language>,
+    pub auto_respond: bool,
+    // Where the highlighting comes from (the grammar and its query, or the regex rules) hashed,
+    // so it's the same in every process running the same build. The bot's caches key on it
+    pub fingerprint: u64,
+}
+
+// How a color looks as a theme defines it, see theme.rs
+// ansi already includes the bold/underline codes, the other fields are only for rendering
+#[derive(Clone, Copy, Debug)]
+pub struct Style {
+    pub ansi: &'static str,
+    pub rgb: Rgb<u8>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub background: Option<Rgb<u8>>,
+}
+
+// ANSI for going back to no background, since the other codes leave the background alone
+pub const ANSI_DEFAULT_BACKGROUND: &str = "\u{001b}[49m";
+
+impl Style {
+    pub const PLAIN: Style = Style {
+        ansi: "",
+        rgb: Rgb([0, 0, 0]),
+        bold: false,
+        italic: false,
+        underline: false,
+        background: None,
+    };
+
+    // A background covers everything inside it, the same way it does with ANSI
+    pub fn inside(self, parent: Style) -> Style {
+        Style {
+            background: self.background.or(parent.background),
+            ..self
+        }
+    }
+}
+
+macro_rules! colors {
+    ($($name:ident)*) => {
+        // The colors every theme has to have. Languages only ever refer to these names,
+        // and the theme decides what they actually look like.
+        #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ColorName {
+            $($name,)*
+        }
+
+        $(pub const $name: ColorName = ColorName::$name;)*
+
+        #[allow(non_snake_case)]
+        struct Palette {
+            $($name: Style,)*
+        }
+
+        impl Palette {
+            fn get(&self, name: ColorName) -> Style {
+                match name {
+                    $(ColorName::$name => self.$name,)*
+                }
+            }
+        }
+
+        impl ColorName {
+            pub const ALL: &'static [ColorName] = &[$(ColorName::$name),*];
+
+            pub fn by_name(name: &str) -> Option<ColorName> {
+                match name {
+                    $(stringify!($name) => Some(ColorName::$name),)*
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+// Note that there are not ANSI names, they are names that fit the specific colors
+// discord uses for the relevant ansi code, see theme.rs for what they actually are
+colors! {
+    ERROR
+    RESET
+    GRAY
+    RED
+    LIGHT_GREEN
+    DARK_GREEN
+    YELLOW
+    BLUE
+    DARK_BLUE
+    PINK
+    CYAN
+    WHITE
+}
+
+// What a capture is colored as. Named colors change with the theme,
+// but a custom color (from /theme set-capture #rrggbb) always looks the same
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    Named(ColorName),
+    Custom(Style),
+}
+
+impl From<ColorName> for Format {
+    fn from(name: ColorName) -> Format {
+        Format::Named(name)
+    }
+}
+
+impl Format {
+    // Either one of the names above, or #rrggbb optionally followed by bold, italic and/or underline
+    // ANSI only has a few colors, so a hex color uses whichever named color is closest to it for ANSI,
+    // and the styles only show up in renders
+    pub fn parse(color: &str) -> Option<Format> {
+        let mut words = color.split_whitespace();
+        let color = words.next()?;
+        if let Some(hex) = color.strip_prefix('#') {
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            let rgb = u32::from_str_radix(hex, 16).ok()?;
+            let rgb = Rgb([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]);
+            let distance = |other: Style| -> i32 {
+                iter::zip(rgb.0, other.rgb.0)
+                    .map(|(a, b)| (a as i32 - b as i32).pow(2))
+                    .sum()
+            };
+            let nearest = ColorName::ALL
+                .iter()
+                .map(|&name| theme::DEFAULT.get(name))
+                // a background would be way more than asked for
+                .filter(|color| color.background.is_none())
+                .min_by_key(|&color| distance(color))?;
+            let mut style = Style {
+                ansi: nearest.ansi,
+                rgb,
+                ..Style::PLAIN
+            };
+            for word in words {
+                match word.to_lowercase().as_str() {
+                    "bold" => style.bold = true,
+                    "italic" => style.italic = true,
+                    "underline" => style.underline = true,
+                    _ => return None,
+                }
+            }
+            Some(Format::Custom(style))
+        } else if words.next().is_none() {
+            ColorName::by_name(&color.to_uppercase()).map(Format::Named)
+        } else {
+            // the theme decides the style of a named color
+            None
+        }
+    }
+}
+
+// Discord shows tabs as however wide it feels like, and fonts don't know what a tab is at all,
+// so they're turned into spaces everywhere. This keeps track of the column across calls,
+// so a line can be expanded one piece at a time. ANSI escapes don't take up any columns.
+pub struct Tabs {
+    width: usize,
+    column: usize,
+    in_escape: bool,
+}
+
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+pub const MAX_TAB_WIDTH: usize = 16;
+
+impl Tabs {
+    pub fn new(width: usize) -> Self {
+        Tabs {
+            width,
+            column: 0,
+            in_escape: false,
+        }
+    }
+
+    pub fn expand<'a>(&mut self, text: &'a str) -> Cow<'a, str> {
+        let mut expanded = String::new();
+        for (i, c) in text.char_indices() {
+            match c {
+                '\t' => {
+                    if expanded.is_empty() {
+                        expanded.push_str(&text[..i]);
+                    }
+                    let spaces = self.width - self.column % self.width;
+                    expanded.extend(iter::repeat(' ').take(spaces));
+                    self.column += spaces;
+                    continue;
+                }
+                '\n' => self.column = 0,
+                '\u{001b}' => self.in_escape = true,
+                c if self.in_escape => self.in_escape = !c.is_ascii_alphabetic(),
+                _ => self.column += 1,
+            }
+            if !expanded.is_empty() {
+                expanded.push(c);
+            }
+        }
+        if expanded.is_empty() {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(expanded)
+        }
+    }
+}
+
+// Every kind of highlighting boils down to the same events,
+// so the ANSI and image output don't have to care which kind it was
+pub fn highlight_events(
+    config: &LanguageConfig,
+    code: &str,
+) -> Result<Vec<HighlightEvent>, HighlightError> {
+    match config.highlight {
+        HighlightType::TreeSitter(ref highlight) => {
+            cache::events(config, code, || tree_sitter_events(config, highlight, code))
+        }
+        HighlightType::Regex(ref rules) => Ok(regex_events(rules, code)),
+        HighlightType::Plaintext => Ok(vec![HighlightEvent::Source {
+            start: 0,
+            end: code.len(),
+        }]),
+    }
+}
+
+fn tree_sitter_events(
+    config: &LanguageConfig,
+    highlight: &HighlightConfiguration,
+    code: &str,
+) -> Result<Vec<HighlightEvent>, HighlightError> {
+    let mut highlighter = Pooled::highlighter(config);
+    let highlights = highlighter
+        .highlight(highlight, code.as_bytes(), None, |_| None)
+        .map_err(HighlightError::tree_sitter(config, None))?;
+    let mut events = Vec::new();
+    // where the last bit of code ended, for saying where it went wrong
+    let mut offset = 0;
+    for event in highlights {
+        let event = event.map_err(HighlightError::tree_sitter(config, Some(offset)))?;
+        if let HighlightEvent::Source { end, .. } = event {
+            offset = end;
+        }
+        events.push(event);
+    }
+    Ok(events)
+}
+
+// The earliest match of any rule gets highlighted, and if several start at the same place, the first rule wins.
+// Matches never overlap, so this is a lot dumber than tree-sitter, but it's better than nothing.
+//
+// Every rule remembers where its next match is, and only searches again once the cursor has gone past it.
+// Searching every rule from the cursor at every step would be quadratic in how long the code is
+fn regex_events(rules: &[Regex], code: &str) -> Vec<HighlightEvent> {
+    let mut events = Vec::new();
+    let mut next = rules
+        .iter()
+        .map(|rule| next_match(rule, code, 0))
+        .collect::<Vec<_>>();
+    let mut pos = 0;
+    while pos < code.len() {
+        for (rule, next) in iter::zip(rules, &mut next) {
+            if next.map_or(false, |m| m.start() < pos) {
+                *next = next_match(rule, code, pos);
+            }
+        }
+        let found = next
+            .iter()
+            .enumerate()
+            .filter_map(|(i, m)| m.map(|m| (i, m)))
+            .min_by_key(|(i, m)| (m.start(), *i));
+        match found {
+            Some((i, m)) => {
+                if m.start() > pos {
+                    events.push(HighlightEvent::Source {
+                        start: pos,
+                        end: m.start(),
+                    });
+                }
+                events.push(HighlightEvent::HighlightStart(Highlight(i)));
+                events.push(HighlightEvent::Source {
+                    start: m.start(),
+                    end: m.end(),
+                });
+                events.push(HighlightEvent::HighlightEnd);
+                pos = m.end();
+            }
+            None => {
+                events.push(HighlightEvent::Source {
+                    start: pos,
+                    end: code.len(),
+                });
+                break;
+            }
+        }
+    }
+    events
+}
+
+// The first match at or after start that isn't empty, because an empty match would never advance
+fn next_match<'a>(rule: &Regex, code: &'a str, mut start: usize) -> Option<regex::Match<'a>> {
+    loop {
+        let m = rule.find_at(code, start)?;
+        if !m.as_str().is_empty() {
+            return Some(m);
+        }
+        start = m.end() + code[m.end()..].chars().next()?.len_utf8();
+    }
+}
+
+pub fn syntax_highlight(
+    config: &LanguageConfig,
+    code: &str,
+    theme: &Theme,
+    tab_width: usize,
+) -> Result<String, HighlightError> {
+    let mut output = String::new();
+    let formats = config.formats.read().unwrap();
+    let mut colors = ne_vec![theme.get(RESET)];
+    let mut tabs = Tabs::new(tab_width);
+    for event in highlight_events(config, code)? {
+        let ansi = match event {
+            HighlightEvent::HighlightStart(Highlight(u)) => {
+                colors.push(theme.resolve(formats[u]).inside(*colors.last()));
+                colors.last().ansi
+            }
+            HighlightEvent::Source { start, end } => {
+                output += &tabs.expand(&code[start..end]);
+                continue;
+            }
+            HighlightEvent::HighlightEnd => {
+                let ended = colors.pop();
+                if ended.map_or(false, |ended| ended.background.is_some())
+                    && colors.last().background.is_none()
+                {
+                    output += ANSI_DEFAULT_BACKGROUND;
+                }
+                colors.last().ansi
+            }
+        };
+        output += ansi;
+    }
+    Ok(output)
+}
+
+// Every bit of code +highlight colors, with the byte range and the capture it's colored as.
+// Nested captures are split up like semantic tokens, so spans never overlap and each one has the innermost capture
+pub fn highlight_json(config: &LanguageConfig, code: &str) -> Result<String, HighlightError> {
+    let mut captures = Vec::new();
+    let mut spans = Vec::new();
+    for event in highlight_events(config, code)? {
+        match event {
+            HighlightEvent::HighlightStart(Highlight(u)) => {
+                captures.push(config.captures.get(u).map_or("", String::as_str))
+            }
+            HighlightEvent::Source { start, end } => {
+                if let Some(capture) = captures.last() {
+                    spans.push(serde_json::json!({
+                        "start": start,
+                        "end": end,
+                        "capture": capture,
+                    }));
+                }
+            }
+            HighlightEvent::HighlightEnd => {
+                captures.pop();
+            }
+        }
+    }
+    serde_json::to_string_pretty(&spans).map_err(|source| HighlightError::Serialize {
+        what: "highlights",
+        source,
+    })
+}
+
+pub fn parse(config: &LanguageConfig, code: &str) -> Result<Tree, HighlightError> {
+    cache::tree(config, code, || {
+        // it only gives up without saying why, like when it's cancelled
+        parser(config)?
+            .parse(code, None)
+            .ok_or_else(|| HighlightError::TreeSitter {
+                language: config.name.clone(),
+                offset: None,
+                reason: "the parser gave up".to_owned(),
+            })
+    })
+}
+
+// One from the pool, see pool.rs. It goes back once it's dropped, so hold on to the tree and not the parser
+pub fn parser(config: &LanguageConfig) -> Result<Pooled<Parser>, HighlightError> {
+    Pooled::parser(config)
+}
+
+// A node that pretty_parse didn't go into because of the depth limit.
+// path is the index of every child on the way to it from the root
+pub struct Collapsed {
+    pub path: Vec<usize>,
+    pub kind: &'static str,
+    pub row: usize,
+}
+
+// The node at path, and its children up to depth levels below it.
+// theme is None for the plain version without any colors
+pub fn pretty_parse(
+    tree: &Tree,
+    code: &str,
+    theme: Option<&Theme>,
+    verbose: bool,
+    path: &[usize],
+    depth: Option<usize>,
+) -> Result<(String, Vec<Collapsed>), HighlightError> {
+    let mut cursor = tree.walk();
+    for &i in path {
+        // there's no way to go straight to the nth child
+        if !cursor.goto_first_child() || (0..i).any(|_| !cursor.goto_next_sibling()) {
+            return Err(HighlightError::NodeGone {
+                path: path.to_vec(),
+            });
+        }
+    }
+    let mut collapsed = Vec::new();
+    let formatted = pretty_parse_node(
+        &mut cursor,
+        0,
+        String::new(),
+        code,
+        theme,
+        verbose,
+        depth,
+        &mut path.to_vec(),
+        &mut collapsed,
+    );
+    Ok((formatted, collapsed))
+}
+
+// how much of the code is quoted for each error
+const MAX_ERROR_TEXT: usize = 40;
+
+// One line for every ERROR and MISSING node, which is a lot easier to read than looking for red in a whole parse
+pub fn explain_errors(
+    config: &LanguageConfig,
+    code: &str,
+    theme: &Theme,
+) -> Result<String, HighlightError> {
+    let tree = parse(config, code)?;
+    let ansi = |name| theme.get(name).ansi;
+    let mut explained = String::new();
+    let mut cursor = tree.walk();
+    'walk: loop {
+        let node = cursor.node();
+        let problem = if node.is_missing() {
+            Some((YELLOW, format!("missing \"{}\"", node.kind())))
+        } else if node.is_error() {
+            let text = code[node.byte_range()].lines().next().unwrap_or_default().trim();
+            let mut quoted = text.chars().take(MAX_ERROR_TEXT).collect::<String>();
+            if quoted.len() < text.len() {
+                quoted.push('…');
+            }
+            Some((RED, format!("unexpected \"{quoted}\"")))
+        } else {
+            None
+        };
+        let found = problem.is_some();
+        if let Some((color, problem)) = problem {
+            let tree_sitter::Point { row, column } = node.start_position();
+            explained.push_str(&format!(
+                "{}{}:{}{} — {}{problem}{}\n",
+                ansi(PINK),
+                row + 1,
+                column + 1,
+                ansi(RESET),
+                ansi(color),
+                ansi(RESET)
+            ));
+        }
+        // everything inside an error is part of the same problem, so it doesn't go any deeper
+        if !found && node.has_error() && cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+    if explained.is_empty() {
+        explained.push_str("No syntax errors in there!");
+    }
+    Ok(explained)
+}
+
+// Everything pretty_parse shows and then some, for +parse json.
+// rows and columns start at 0 here, like they do in tree-sitter itself
+pub fn parse_json(config: &LanguageConfig, code: &str) -> Result<String, HighlightError> {
+    let tree = parse(config, code)?;
+    let json = json_node(&mut tree.walk(), code);
+    serde_json::to_string_pretty(&json).map_err(|source| HighlightError::Serialize {
+        what: "syntax tree",
+        source,
+    })
+}
+
+fn json_node(cursor: &mut TreeCursor, code: &str) -> serde_json::Value {
+    let node = cursor.node();
+    let point = |point: tree_sitter::Point| {
+        serde_json::json!({ "row": point.row, "column": point.column })
+    };
+    let mut json = serde_json::json!({
+        "kind": node.kind(),
+        "field": cursor.field_name(),
+        "named": node.is_named(),
+        "error": node.is_error(),
+        "missing": node.is_missing(),
+        "extra": node.is_extra(),
+        "start_byte": node.start_byte(),
+        "end_byte": node.end_byte(),
+        "start": point(node.start_position()),
+        "end": point(node.end_position()),
+    });
+    let mut children = Vec::new();
+    if cursor.goto_first_child() {
+        loop {
+            children.push(json_node(cursor, code));
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+        cursor.goto_parent();
+    }
+    if children.is_empty() {
+        json["text"] = code[node.byte_range()].into();
+    } else {
+        json["children"] = children.into();
+    }
+    json
+}
+
+// depth is how many more levels down it goes, and path is where this node is, see Collapsed.
+// verbose shows where every node starts and ends, not just where the leaves start
+fn pretty_parse_node(
+    cursor: &mut TreeCursor,
+    indent: usize,
+    mut string: String,
+    code: &str,
+    theme: Option<&Theme>,
+    verbose: bool,
+    depth: Option<usize>,
+    path: &mut Vec<usize>,
+    collapsed: &mut Vec<Collapsed>,
+) -> String {
+    const INDENT: &str = "    ";
+    let ansi = |name| theme.map_or("", |theme| theme.get(name).ansi);
+    string.extend(iter::repeat(INDENT).take(indent));
+    if let Some(field_name) = cursor.field_name() {
+        string.push_str(ansi(YELLOW));
+        string.push_str(field_name);
+        string.push_str(": ");
+        string.push_str(ansi(RESET));
+    }
+    if cursor.node().is_error() {
+        string.push_str(ansi(RED));
+    } else if cursor.node().is_extra() {
+        string.push_str(ansi(GRAY));
+    } else {
+        string.push_str(ansi(LIGHT_GREEN));
+    }
+    string.push_str(cursor.node().kind());
+    string.push_str(ansi(RESET));
+    if verbose {
+        // rows and columns start at 1 like everywhere else in here, but bytes start at 0 like in queries
+        let node = cursor.node();
+        let (start, end) = (node.start_position(), node.end_position());
+        string.push_str(ansi(PINK));
+        string.push_str(&format!(
+            " [{}, {} - {}, {}] {}..{}",
+            start.row + 1,
+            start.column + 1,
+            end.row + 1,
+            end.column + 1,
+            node.start_byte(),
+            node.end_byte()
+        ));
+        string.push_str(ansi(RESET));
+    }
+
+    if depth == Some(0) && cursor.node().named_child_count() > 0 {
+        // how much is hiding in there
+        string.push_str(ansi(GRAY));
+        string.push_str(&format!(" [+{}]", cursor.node().named_child_count()));
+        string.push_str(ansi(RESET));
+        collapsed.push(Collapsed {
+            path: path.clone(),
+            kind: cursor.node().kind(),
+            row: cursor.node().start_position().row,
+        });
+        return string;
+    }
+
+    let printed = cursor.goto_first_child() && {
+        let mut printed = false;
+        let mut i = 0;
+        loop {
+            if cursor.field_name().is_some()
+                || cursor.node().is_named()
+                || cursor.node().child_count() > 0
+            {
+                printed = true;
+                string.push('\n');
+                path.push(i);
+                string = pretty_parse_node(
+                    cursor,
+                    indent + 1,
+                    string,
+                    code,
+                    theme,
+                    verbose,
+                    depth.map(|depth| depth.saturating_sub(1)),
+                    path,
+                    collapsed,
+                );
+                path.pop();
+            }
+            i += 1;
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+        cursor.goto_parent();
+        printed
+    };
+    if !printed {
+        // verbose already said where it is
+        if !verbose {
+            string.push_str(ansi(PINK));
+            string.push_str(" [");
+            let tree_sitter::Point { row, column } = cursor.node().start_position();
+            string.push_str(&(row + 1).to_string());
+            string.push_str(", ");
+            string.push_str(&(column + 1).to_string());
+            string.push_str("]");
+        }
+        string.push(' ');
+        if cursor.node().is_named() {
+            if cursor.node().is_extra() {
+                string.push_str(ansi(GRAY));
+            } else {
+                string.push_str(ansi(BLUE));
+            }
+            string.push_str(&code[cursor.node().byte_range()]);
+            string.push_str(ansi(RESET));
+        }
+    }
+    string
+}
@@ -0,0 +1,49 @@
+// Finding codeblocks in a message. This is all hand-rolled slicing of whatever anyone typed,
+// so it's fuzzed, see fuzz/fuzz_targets/codeblock.rs
+
+// discord itself accepts things like ```c++ and ```objective-c, so those have to count as a lang too
+pub fn is_fence_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '+' | '#' | '_' | '.')
+}
+
+// (before, lang, code, after), for a message with exactly one codeblock in it
+pub fn codeblock(content: &str) -> Option<(&str, &str, &str, &str)> {
+    let (before, content) = content.split_once("```")?;
+    let (content, after) = content.split_once("```")?;
+    // multiple codeblocks, nontrivial, so abort. the context menu asks which one, see blocks.rs in the bot
+    if after.contains("```") {
+        return None;
+    }
+    let (lang, code) = fence(content)?;
+    Some((before.trim(), lang, code, after))
+}
+
+// What's between the ```s, split into (lang, code). None if there's no code in there
+pub fn fence(content: &str) -> Option<(&str, &str)> {
+    let (lang, code) = content.split_once("\n").unwrap_or((content, ""));
+    let code = code.trim_matches('\n');
+    let (lang, code) = if code.is_empty() {
+        ("", lang)
+    } else if !lang.chars().all(is_fence_char) {
+        ("", content)
+    } else {
+        (lang, code)
+    };
+    if code.is_empty() {
+        None
+    } else {
+        Some((lang, code))
+    }
+}
+
+// Every codeblock in the message, as (lang, code). A ``` that never gets closed doesn't count
+pub fn codeblocks(content: &str) -> Vec<(&str, &str)> {
+    let parts = content.split("```").collect::<Vec<_>>();
+    parts
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .take((parts.len() - 1) / 2)
+        .filter_map(|part| fence(part))
+        .collect()
+}
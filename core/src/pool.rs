@@ -0,0 +1,124 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Mutex,
+};
+
+use super::*;
+use tree_sitter_highlight::Highlighter;
+
+// Parsers and highlighters that are done get put back here instead of being dropped, so the next
+// command in the same language picks up one that already has its language set and its buffers
+// allocated. Mashing buttons on a big codeblock would otherwise make a fresh one every single time.
+//
+// A parser is reset before it goes back, so whatever it was doing (like a parse that timed out)
+// doesn't carry over into the next one. Only POOLED_PER_LANGUAGE are kept for each language, the rest
+// are dropped like before, so a burst of commands doesn't keep that many around forever
+const DEFAULT_POOLED_PER_LANGUAGE: usize = 4;
+
+lazy_static! {
+    static ref POOLED_PER_LANGUAGE: usize =
+        config::parse("POOLED_PER_LANGUAGE").unwrap_or(DEFAULT_POOLED_PER_LANGUAGE);
+    static ref PARSERS: Pool<Parser> = Pool::new(|parser| {
+        parser.reset();
+        parser.set_timeout_micros(0);
+    });
+    // it doesn't keep anything from one highlight to the next that the next one could see
+    static ref HIGHLIGHTERS: Pool<Highlighter> = Pool::new(|_| {});
+}
+
+// keyed by the language's fingerprint, the idle ones for each language. Not the name, because the same
+// name can be a different grammar (an installed one in another guild, or the one from before a reload),
+// and a parser that still has the other grammar set would parse with that
+struct Pool<T> {
+    idle: Mutex<HashMap<u64, Vec<T>>>,
+    // what has to be undone before the next one gets it
+    clean: fn(&mut T),
+}
+
+impl<T> Pool<T> {
+    fn new(clean: fn(&mut T)) -> Self {
+        Pool {
+            idle: Mutex::new(HashMap::new()),
+            clean,
+        }
+    }
+
+    fn take(&self, fingerprint: u64) -> Option<T> {
+        self.idle.lock().unwrap().get_mut(&fingerprint)?.pop()
+    }
+
+    fn put(&self, fingerprint: u64, mut item: T) {
+        (self.clean)(&mut item);
+        let mut idle = self.idle.lock().unwrap();
+        let idle = idle.entry(fingerprint).or_default();
+        if idle.len() < *POOLED_PER_LANGUAGE {
+            idle.push(item);
+        }
+    }
+}
+
+// A parser or highlighter that goes back to the pool once it's dropped
+pub struct Pooled<T: 'static> {
+    // only None while it's being dropped
+    item: Option<T>,
+    fingerprint: u64,
+    pool: &'static Pool<T>,
+}
+
+impl<T: 'static> Deref for Pooled<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.item.as_ref().unwrap()
+    }
+}
+
+impl<T: 'static> DerefMut for Pooled<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.item.as_mut().unwrap()
+    }
+}
+
+impl Pooled<Parser> {
+    pub fn parser(config: &LanguageConfig) -> Result<Self, HighlightError> {
+        let parser = match PARSERS.take(config.fingerprint) {
+            Some(parser) => parser,
+            None => {
+                let language = config.language.ok_or_else(|| HighlightError::NoGrammar {
+                    language: config.name.clone(),
+                })?;
+                let mut parser = Parser::new();
+                parser
+                    .set_language(language)
+                    .map_err(HighlightError::tree_sitter(config, None))?;
+                parser
+            }
+        };
+        Ok(Pooled {
+            item: Some(parser),
+            fingerprint: config.fingerprint,
+            pool: &PARSERS,
+        })
+    }
+}
+
+impl Pooled<Highlighter> {
+    // the highlighter sets the language itself every time it highlights, so any of them would do,
+    // but keeping them apart means its buffers are already about the right size for that language
+    pub fn highlighter(config: &LanguageConfig) -> Self {
+        let highlighter = HIGHLIGHTERS.take(config.fingerprint);
+        Pooled {
+            item: Some(highlighter.unwrap_or_else(Highlighter::new)),
+            fingerprint: config.fingerprint,
+            pool: &HIGHLIGHTERS,
+        }
+    }
+}
+
+impl<T: 'static> Drop for Pooled<T> {
+    fn drop(&mut self) {
+        if let Some(item) = self.item.take() {
+            self.pool.put(self.fingerprint, item);
+        }
+    }
+}
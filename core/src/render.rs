@@ -0,0 +1,1463 @@
+// Drawing highlighted code as images, and encoding them. Nothing in here talks to discord,
+// the bot decides what goes in RenderOptions and where the images end up
+use std::{borrow::Cow, cmp, collections::HashMap, fs, iter, mem, path::Path, ptr, sync::Arc};
+
+use super::*;
+use ab_glyph::{point, Font, FontRef, Glyph, GlyphId, PxScale, PxScaleFont, ScaleFont};
+use image::{
+    codecs::{png, webp},
+    imageops, ColorType, GenericImage, GenericImageView, ImageEncoder, Rgba, RgbaImage, SubImage,
+};
+use lru::LruCache;
+use rayon::prelude::*;
+use rustybuzz::UnicodeBuffer;
+use similar::ChangeTag;
+use unicode_segmentation::UnicodeSegmentation;
+
+// rustybuzz figures out which glyphs go where (so ligatures and combining marks work),
+// and ab_glyph draws them. They both need to look at the same font file.
+struct Face<'a> {
+    shaper: rustybuzz::Face<'a>,
+    font: FontRef<'a>,
+}
+
+impl<'a> Face<'a> {
+    fn new(data: &'a [u8]) -> Option<Face<'a>> {
+        Some(Face {
+            shaper: rustybuzz::Face::from_slice(data, 0)?,
+            font: FontRef::try_from_slice(data).ok()?,
+        })
+    }
+
+    fn scaled(&self, size: f32) -> PxScaleFont<&FontRef<'a>> {
+        // as_scaled takes the font by value, so it has to be a reference to not move it out of here
+        (&self.font).as_scaled(size)
+    }
+}
+
+lazy_static! {
+    // These are optional, not every font even has an italic. Without them, bold is faked by drawing
+    // everything twice one pixel apart, and italic is just drawn upright.
+    static ref BOLD: Option<Vec<u8>> = load_face("font-bold.ttf");
+    static ref ITALIC: Option<Vec<u8>> = load_face("font-italic.ttf");
+}
+
+const FONT: &[u8] = include_bytes!("../../font.ttf");
+
+fn load_face(path: &str) -> Option<Vec<u8>> {
+    let bytes = fs::read(path).ok()?;
+    if Face::new(&bytes).is_none() {
+        error!("{path} isn't a font");
+        return None;
+    }
+    Some(bytes)
+}
+
+// Whether this is a font the renderer can use at all, for checking fonts before they're saved
+pub fn is_font(bytes: &[u8]) -> bool {
+    Face::new(bytes).is_some()
+}
+
+// The fonts one render uses. The faces borrow the font files, so they're made fresh for every render
+struct Fonts<'a> {
+    regular: Face<'a>,
+    bold: Option<Face<'a>>,
+    italic: Option<Face<'a>>,
+    // in pixels, which is also the height of every line
+    size: f32,
+    // keyed by (which face, glyph, subpixel x, subpixel y), see Fonts::rasterize
+    glyphs: std::sync::Mutex<LruCache<(usize, GlyphId, u8, u8), Option<Arc<Mask>>>>,
+}
+
+// A glyph's coverage, rasterized once and then copied everywhere that same glyph is drawn
+struct Mask {
+    // where the coverage starts, relative to the whole pixel the glyph is positioned in
+    left: i32,
+    top: i32,
+    width: u32,
+    coverage: Vec<f32>,
+}
+
+// plenty for all of ASCII in every style at every subpixel offset
+const GLYPH_CACHE_SIZE: usize = 8192;
+// glyphs are positioned to a quarter of a pixel, any finer than that and nobody can tell the difference
+const SUBPIXEL_STEPS: f32 = 4.0;
+
+impl<'a> Fonts<'a> {
+    // A custom font (see /server font) doesn't go with the bundled bold and italic, so those are faked with it
+    fn new(custom: Option<&'a [u8]>, size: u32) -> Fonts<'a> {
+        let size = size as f32;
+        if let Some(face) = custom.and_then(Face::new) {
+            return Fonts {
+                regular: face,
+                bold: None,
+                italic: None,
+                size,
+                glyphs: std::sync::Mutex::new(LruCache::new(GLYPH_CACHE_SIZE)),
+            };
+        }
+        Fonts {
+            regular: Face::new(FONT).unwrap(),
+            bold: BOLD.as_deref().and_then(Face::new),
+            italic: ITALIC.as_deref().and_then(Face::new),
+            size,
+            glyphs: std::sync::Mutex::new(LruCache::new(GLYPH_CACHE_SIZE)),
+        }
+    }
+
+    // The glyph's coverage, and the whole pixel it goes at. None for glyphs with no outline, like spaces
+    fn rasterize(&self, style: &Style, glyph: &Glyph) -> Option<(i32, i32, Arc<Mask>)> {
+        let face = self.face(style);
+        let x = glyph.position.x.floor();
+        let y = glyph.position.y.floor();
+        let subpixel_x = ((glyph.position.x - x) * SUBPIXEL_STEPS) as u8;
+        let subpixel_y = ((glyph.position.y - y) * SUBPIXEL_STEPS) as u8;
+        // the faces live exactly as long as the cache does, so where they are is good enough to tell them apart
+        let key = (face as *const Face as usize, glyph.id, subpixel_x, subpixel_y);
+        let cached = self.glyphs.lock().unwrap().get(&key).cloned();
+        let mask = match cached {
+            Some(mask) => mask,
+            None => {
+                // not holding the lock here, so the other threads can draw while this one rasterizes
+                let outline = face.font.outline_glyph(glyph.id.with_scale_and_position(
+                    glyph.scale,
+                    point(
+                        subpixel_x as f32 / SUBPIXEL_STEPS,
+                        subpixel_y as f32 / SUBPIXEL_STEPS,
+                    ),
+                ));
+                let mask = outline.map(|outline| {
+                    let bounds = outline.px_bounds();
+                    let width = bounds.width() as u32;
+                    let mut coverage = vec![0.0; (width * bounds.height() as u32) as usize];
+                    outline.draw(|dx, dy, v| coverage[(dy * width + dx) as usize] = v);
+                    Arc::new(Mask {
+                        left: bounds.min.x as i32,
+                        top: bounds.min.y as i32,
+                        width,
+                        coverage,
+                    })
+                });
+                self.glyphs.lock().unwrap().put(key, mask.clone());
+                mask
+            }
+        };
+        Some((x as i32, y as i32, mask?))
+    }
+
+    fn scale(&self) -> PxScale {
+        PxScale::from(self.size)
+    }
+
+    fn face(&self, style: &Style) -> &Face<'a> {
+        match (&self.bold, &self.italic) {
+            (_, Some(italic)) if style.italic => italic,
+            (Some(bold), _) if style.bold => bold,
+            _ => &self.regular,
+        }
+    }
+
+    fn fake_bold(&self, style: &Style) -> bool {
+        style.bold
+            && self
+                .bold
+                .as_ref()
+                .map_or(true, |bold| !ptr::eq(self.face(style), bold))
+    }
+}
+
+// Text size in pixels, can be changed with size= or /preferences size
+pub const DEFAULT_TEXT_SIZE: u32 = 36;
+// any smaller is unreadable, any bigger and a normal snippet already hits the upload limit
+pub const MIN_TEXT_SIZE: u32 = 8;
+pub const MAX_TEXT_SIZE: u32 = 128;
+// Wide enough for about 110 columns with Fira Code at the default size, anything longer is probably not meant to be read on a phone anyways
+pub const DEFAULT_WRAP_WIDTH: u32 = 2400;
+// a few characters have to fit, at least
+pub const MIN_WRAP_WIDTH: u32 = DEFAULT_TEXT_SIZE * 4;
+// the lines separating the code from the line numbers and the title
+const RULE_WIDTH: i32 = 2;
+lazy_static! {
+    // the biggest render (in pixels) that will even be attempted, RENDER_PIXEL_BUDGET changes it.
+    // the default is about 400MB of memory before it's even encoded
+    static ref PIXEL_BUDGET: u64 = config::parse("RENDER_PIXEL_BUDGET").unwrap_or(100_000_000);
+}
+// how many rows are drawn in parallel before they're put on the image, see render
+const ROWS_PER_BATCH: usize = 256;
+// Discord scales images down to fit on screen, so anything much taller than this is unreadable without opening it.
+// taller renders are split into a few images instead, see draw
+const MAX_IMAGE_HEIGHT: u32 = 6000;
+// the most attachments a message can have
+const MAX_IMAGES: usize = 10;
+// +render animate reveals this many glyphs per frame, and refuses anything longer than MAX_ANIMATED_GLYPHS
+const GLYPHS_PER_FRAME: usize = 3;
+const MAX_ANIMATED_GLYPHS: usize = 600;
+// in milliseconds. The last frame stays up for a bit, so the finished code can actually be read before it starts over
+const FRAME_DELAY: u16 = 50;
+const LAST_FRAME_DELAY: u16 = 3000;
+
+#[derive(Debug)]
+enum LineHighlightEvent<'a> {
+    Style(Style),
+    Segment(&'a str),
+    Newline,
+}
+
+// Everything about how a render looks, other than the code itself
+#[derive(Clone)]
+pub struct RenderOptions {
+    pub theme: &'static Theme,
+    pub line_numbers: bool,
+    pub title: Option<Title>,
+    // in pixels, not counting the line numbers. None means never wrap
+    pub wrap: Option<u32>,
+    pub tab_width: usize,
+    // the guild's own font, see /server font
+    pub font: Option<Arc<[u8]>>,
+    // in pixels, between MIN_TEXT_SIZE and MAX_TEXT_SIZE
+    pub size: u32,
+    pub encoding: Encoding,
+    // the first and last line to draw, starting at 1. None means all of them
+    pub range: Option<(usize, usize)>,
+    // (first, last) lines that get a background behind them, starting at 1
+    pub emphasis: Vec<(usize, usize)>,
+    // no background or border, just the code on top of nothing
+    pub transparent: bool,
+    // an APNG that types out the code, see draw
+    pub animate: bool,
+    // uploaded as SPOILER_code.png, so it's blurred until someone clicks it.
+    // it doesn't change what the image looks like, so it's not part of the cache key
+    pub spoiler: bool,
+}
+
+// So a render still says where it came from when it's shared somewhere else
+#[derive(Clone)]
+pub struct Title {
+    pub author: String,
+    pub language: String,
+    pub avatar: Option<RgbaImage>,
+}
+
+// just the language, for telling codeblocks apart, see render_blocks
+fn label(language: &str) -> Title {
+    Title {
+        author: language.to_owned(),
+        language: String::new(),
+        avatar: None,
+    }
+}
+
+// Renders it again with smaller text until every image is at most limit bytes.
+// The size it ended up with is returned along with the encoded images
+pub fn render_to_fit(
+    draw: impl Fn(&RenderOptions) -> Result<Vec<RgbaImage>, HighlightError>,
+    mut options: RenderOptions,
+    limit: usize,
+) -> Result<(Vec<(Vec<u8>, &'static str)>, u32), HighlightError> {
+    loop {
+        let mut files = Vec::new();
+        let images = draw(&options)?;
+        let stage = info_span!("encode", images = images.len()).entered();
+        if options.animate {
+            // they're frames, not separate images
+            files.push((encode_apng(&images)?, "code.png"));
+        } else {
+            for image in images {
+                let (buffer, file_name) = encode(&image, options.encoding)?;
+                debug!("encoded {file_name} ({} bytes)", buffer.len());
+                files.push((buffer, file_name));
+            }
+        }
+        drop(stage);
+        // the limit is per file, like discord's
+        if files.iter().all(|(buffer, _)| buffer.len() <= limit) {
+            return Ok((files, options.size));
+        }
+        if options.size <= MIN_TEXT_SIZE {
+            return Err(HighlightError::TooBig(
+                "The resulting image is WAYY TOO BIG, get lost".to_owned(),
+            ));
+        }
+        // the file size goes with the area, so this roughly halves it every time
+        let smaller = cmp::max(options.size * 7 / 10, MIN_TEXT_SIZE);
+        // and the wrap width shrinks with it, so the lines still wrap in the same places
+        options.wrap = options.wrap.map(|wrap| wrap * smaller / options.size);
+        options.size = smaller;
+        info!("too big, trying again at size {smaller}");
+    }
+}
+
+// What renders get uploaded as, picked with format=
+#[derive(Clone, Copy, Default, Hash)]
+pub enum Encoding {
+    Png,
+    // PNG with a 256 color palette, see encode_indexed
+    Indexed,
+    WebP,
+    // PNG, but big images also get encoded as indexed PNG and WebP, and whichever is smallest wins
+    #[default]
+    Auto,
+}
+
+impl Encoding {
+    pub fn by_name(name: &str) -> Option<Encoding> {
+        match name.to_lowercase().as_str() {
+            "png" => Some(Encoding::Png),
+            "indexed" => Some(Encoding::Indexed),
+            "webp" => Some(Encoding::WebP),
+            "auto" => Some(Encoding::Auto),
+            _ => None,
+        }
+    }
+}
+
+// Below this, PNG is small enough already and encoding more than once is just wasted time
+const AUTO_THRESHOLD: usize = 1_000_000;
+
+// The image as a file, and what the file is called
+pub fn encode(
+    image: &RgbaImage,
+    encoding: Encoding,
+) -> Result<(Vec<u8>, &'static str), HighlightError> {
+    match encoding {
+        Encoding::Png => Ok((encode_png(image)?, "code.png")),
+        Encoding::Indexed => Ok((encode_indexed(image)?, "code.png")),
+        Encoding::WebP => Ok((encode_webp(image)?, "code.webp")),
+        Encoding::Auto => {
+            let png = encode_png(image)?;
+            if png.len() < AUTO_THRESHOLD {
+                return Ok((png, "code.png"));
+            }
+            let indexed = encode_indexed(image)?;
+            let webp = encode_webp(image)?;
+            debug!(
+                "png is {} bytes, indexed is {} bytes, webp is {} bytes",
+                png.len(),
+                indexed.len(),
+                webp.len()
+            );
+            Ok([(png, "code.png"), (indexed, "code.png"), (webp, "code.webp")]
+                .into_iter()
+                .min_by_key(|(bytes, _)| bytes.len())
+                .unwrap())
+        }
+    }
+}
+
+// Renders only have a handful of colors plus the antialiasing between them,
+// so a 256 color palette is plenty and takes a quarter of the space per pixel before compression even starts
+fn encode_indexed(image: &RgbaImage) -> Result<Vec<u8>, HighlightError> {
+    let (palette, indices) = quantize(image);
+    let mut buffer = Vec::new();
+    // ::png is the crate, plain png is image's png codec
+    let mut encoder = ::png::Encoder::new(&mut buffer, image.width(), image.height());
+    encoder.set_color(::png::ColorType::Indexed);
+    encoder.set_depth(::png::BitDepth::Eight);
+    let rgb: Vec<u8> = palette.iter().flat_map(|rgba| &rgba[..3]).copied().collect();
+    let alpha: Vec<u8> = palette.iter().map(|rgba| rgba[3]).collect();
+    encoder.set_palette(rgb);
+    encoder.set_trns(alpha);
+    encoder.set_compression(::png::Compression::Best);
+    // the filters do arithmetic on the bytes, which means nothing when they're palette indices
+    encoder.set_filter(::png::FilterType::NoFilter);
+    let mut writer = encoder.write_header().map_err(HighlightError::encode("image"))?;
+    writer
+        .write_image_data(&indices)
+        .map_err(HighlightError::encode("image"))?;
+    writer.finish().map_err(HighlightError::encode("image"))?;
+    Ok(buffer)
+}
+
+// Every frame after the first is only the part that changed since the one before it,
+// which for typing is usually a handful of characters
+fn encode_apng(frames: &[RgbaImage]) -> Result<Vec<u8>, HighlightError> {
+    let (width, height) = frames[0].dimensions();
+    let mut buffer = Vec::new();
+    let mut encoder = ::png::Encoder::new(&mut buffer, width, height);
+    encoder.set_color(::png::ColorType::Rgba);
+    encoder.set_depth(::png::BitDepth::Eight);
+    encoder.set_compression(::png::Compression::Best);
+    // 0 plays means it loops forever
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .map_err(HighlightError::encode("animation"))?;
+    let mut writer = encoder.write_header().map_err(HighlightError::encode("animation"))?;
+    let mut previous: Option<&RgbaImage> = None;
+    for (i, frame) in frames.iter().enumerate() {
+        let (x, y, w, h) = match previous {
+            Some(previous) => changed_area(previous, frame),
+            None => (0, 0, width, height),
+        };
+        let delay = if i + 1 == frames.len() { LAST_FRAME_DELAY } else { FRAME_DELAY };
+        // the position has to go back to the corner first, or the new size might not fit where the last one was
+        writer
+            .set_frame_position(0, 0)
+            .and_then(|_| writer.set_frame_dimension(w, h))
+            .and_then(|_| writer.set_frame_position(x, y))
+            .and_then(|_| writer.set_frame_delay(delay, 1000))
+            .and_then(|_| writer.set_blend_op(::png::BlendOp::Source))
+            .and_then(|_| writer.write_image_data(&frame.view(x, y, w, h).to_image()))
+            .map_err(HighlightError::encode("animation"))?;
+        previous = Some(frame);
+    }
+    writer.finish().map_err(HighlightError::encode("animation"))?;
+    Ok(buffer)
+}
+
+// (x, y, width, height) of the smallest rectangle around every pixel that's different.
+// draw already skips frames that are exactly the same, but it's still at least one pixel so the frame isn't empty
+fn changed_area(previous: &RgbaImage, frame: &RgbaImage) -> (u32, u32, u32, u32) {
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in frame.enumerate_pixels() {
+        if previous.get_pixel(x, y) != pixel {
+            left = cmp::min(left, x);
+            top = cmp::min(top, y);
+            right = cmp::max(right, x);
+            bottom = cmp::max(bottom, y);
+        }
+    }
+    if left > right {
+        return (0, 0, 1, 1);
+    }
+    (left, top, right - left + 1, bottom - top + 1)
+}
+
+// The exact colors if there's few enough of them, otherwise NeuQuant picks the closest 256
+fn quantize(image: &RgbaImage) -> (Vec<[u8; 4]>, Vec<u8>) {
+    let mut palette = Vec::new();
+    let mut lookup = HashMap::new();
+    let mut indices = Vec::with_capacity(image.len() / 4);
+    for &Rgba(rgba) in image.pixels() {
+        let index = match lookup.get(&rgba) {
+            Some(&index) => index,
+            None if palette.len() < 256 => {
+                let index = palette.len() as u8;
+                palette.push(rgba);
+                lookup.insert(rgba, index);
+                index
+            }
+            None => return neuquant(image),
+        };
+        indices.push(index);
+    }
+    (palette, indices)
+}
+
+fn neuquant(image: &RgbaImage) -> (Vec<[u8; 4]>, Vec<u8>) {
+    // 10 is the speed/quality tradeoff it suggests, 1 is best and 30 is fastest
+    let quantizer = color_quant::NeuQuant::new(10, 256, image.as_raw());
+    let palette = quantizer
+        .color_map_rgba()
+        .chunks_exact(4)
+        .map(|rgba| [rgba[0], rgba[1], rgba[2], rgba[3]])
+        .collect();
+    let indices = image
+        .pixels()
+        .map(|pixel| quantizer.index_of(&pixel.0) as u8)
+        .collect();
+    (palette, indices)
+}
+
+fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, HighlightError> {
+    // I've tested all other encodings that ``image`` comes with
+    // and the only other one that even worked was JPEG
+    // which is too moldy for text, and therefore unacceptable.
+    // (lossless WebP didn't exist in ``image`` back then, see encode_webp)
+    //
+    // I've hand-picked these settings through trial and error:
+    //
+    // CompressionType = Run length encoding
+    //
+    // Because most of the image is gonna be the same gray BG color
+    // especially when the image is big enough that
+    // the choice of these settings actually matter
+    //
+    // FilterType = Up (scanline above)
+    //
+    // Because text generally contains a lot of vertical lines
+    // and this measurably decreased size by about 20% with no noticeable delay
+    // for the example.ursl in URSL repository
+    let mut buffer = Vec::new();
+    let png = png::PngEncoder::new_with_quality(
+        &mut buffer,
+        png::CompressionType::Rle,
+        png::FilterType::Up,
+    );
+    png.write_image(image, image.width(), image.height(), ColorType::Rgba8)
+        .map_err(HighlightError::encode("image"))?;
+    Ok(buffer)
+}
+
+// Lossless, so it's just as crisp as PNG. It's slower, but for big renders it's usually a lot smaller
+fn encode_webp(image: &RgbaImage) -> Result<Vec<u8>, HighlightError> {
+    let mut buffer = Vec::new();
+    webp::WebPEncoder::new_lossless(&mut buffer)
+        .encode(image, image.width(), image.height(), ColorType::Rgba8)
+        .map_err(HighlightError::encode("image"))?;
+    Ok(buffer)
+}
+
+// Right-to-left text is completely unsupported because none of my spoken languages are right-to-left so it does not affect me personally, and is therefore seen as an inconvenience rather than a requirement.
+pub fn render(
+    config: &LanguageConfig,
+    code: &str,
+    options: &RenderOptions,
+) -> Result<Vec<RgbaImage>, HighlightError> {
+    rasterize(&lay_out(config, code, options)?, options)
+}
+
+// The first half of render, everything up to knowing where every glyph goes
+pub fn lay_out(
+    config: &LanguageConfig,
+    code: &str,
+    options: &RenderOptions,
+) -> Result<Layout, HighlightError> {
+    let theme = options.theme;
+    let lines = highlight_lines(config, code, options)?;
+
+    // The whole file still gets highlighted, so whatever's above the range is still highlighted right,
+    // but only the range is actually drawn
+    let (first, last) = options.range.unwrap_or((1, usize::MAX));
+    if first > lines.len() {
+        return Err(HighlightError::OutOfRange {
+            first,
+            lines: lines.len(),
+        });
+    }
+    let lines = lines
+        .into_iter()
+        .enumerate()
+        .skip(first - 1)
+        .take(last - (first - 1))
+        .map(|(i, segments)| {
+            let number = i + 1;
+            let emphasized = options
+                .emphasis
+                .iter()
+                .any(|&(first, last)| (first..=last).contains(&number));
+            SourceLine {
+                label: number.to_string(),
+                segments,
+                // a faint yellow, like github permalinks do it
+                tint: emphasized.then(|| (theme.get(YELLOW).rgb, 0.15)),
+            }
+        })
+        .collect();
+    arrange(lines, options, options.line_numbers)
+}
+
+// Every codeblock drawn on its own, with its language on top instead of a title,
+// and then stacked into as few images as they fit in, still in the order they're in
+pub fn render_blocks(
+    blocks: &[(Arc<LanguageConfig>, String)],
+    options: &RenderOptions,
+) -> Result<Vec<RgbaImage>, HighlightError> {
+    let mut stacks: Vec<Vec<RgbaImage>> = Vec::new();
+    for (config, code) in blocks {
+        // frames of different codeblocks can't be stacked
+        let options = RenderOptions {
+            title: Some(label(&config.name)),
+            animate: false,
+            ..options.clone()
+        };
+        for image in render(config, code, &options)? {
+            let height = image.height();
+            match stacks.last_mut() {
+                Some(stack) if stack_height(stack) + BLOCK_GAP + height <= MAX_IMAGE_HEIGHT => {
+                    stack.push(image)
+                }
+                _ => stacks.push(vec![image]),
+            }
+        }
+    }
+    if stacks.len() > MAX_IMAGES {
+        return Err(HighlightError::TooBig(format!(
+            "That's too much code to fit in {MAX_IMAGES} images, try rendering the codeblocks one at a time"
+        )));
+    }
+    Ok(stacks.iter().map(|stack| stack_images(stack)).collect())
+}
+
+// the space between two codeblocks in the same image, it's transparent
+const BLOCK_GAP: u32 = 16;
+
+fn stack_height(stack: &[RgbaImage]) -> u32 {
+    let gaps = stack.len().saturating_sub(1) as u32 * BLOCK_GAP;
+    stack.iter().map(|image| image.height()).sum::<u32>() + gaps
+}
+
+fn stack_images(stack: &[RgbaImage]) -> RgbaImage {
+    let width = stack.iter().map(|image| image.width()).max().unwrap_or_default();
+    let mut stacked = RgbaImage::new(width, stack_height(stack));
+    let mut y = 0;
+    for image in stack {
+        imageops::overlay(&mut stacked, image, 0, y);
+        y += (image.height() + BLOCK_GAP) as i64;
+    }
+    stacked
+}
+
+// A unified diff between two versions of the same code. Both are highlighted on their own,
+// so a line that was removed is still highlighted the way it was in the old code
+pub fn render_diff(
+    config: &LanguageConfig,
+    old: &str,
+    new: &str,
+    options: &RenderOptions,
+) -> Result<Vec<RgbaImage>, HighlightError> {
+    let theme = options.theme;
+    let old_lines = highlight_lines(config, old, options)?;
+    let new_lines = highlight_lines(config, new, options)?;
+    let lines = similar::TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| match change.tag() {
+            ChangeTag::Delete => SourceLine {
+                label: "-".to_owned(),
+                segments: old_lines[change.old_index().unwrap()].clone(),
+                tint: Some((theme.get(RED).rgb, 0.2)),
+            },
+            ChangeTag::Insert => SourceLine {
+                label: "+".to_owned(),
+                segments: new_lines[change.new_index().unwrap()].clone(),
+                tint: Some((theme.get(DARK_GREEN).rgb, 0.2)),
+            },
+            ChangeTag::Equal => SourceLine {
+                label: String::new(),
+                segments: new_lines[change.new_index().unwrap()].clone(),
+                tint: None,
+            },
+        })
+        .collect();
+    draw(lines, options, true)
+}
+
+// One line of code, split up into differently styled segments with the tabs already expanded
+type Segments<'a> = Vec<(Style, Cow<'a, str>)>;
+
+fn highlight_lines<'a>(
+    config: &LanguageConfig,
+    code: &'a str,
+    options: &RenderOptions,
+) -> Result<Vec<Segments<'a>>, HighlightError> {
+    let theme = options.theme;
+    let events = {
+        let formats = config.formats.read().unwrap();
+        let mut events = Vec::new();
+        let mut colors = ne_vec![theme.get(RESET)];
+        for event in highlight_events(config, code)? {
+            match event {
+                HighlightEvent::HighlightStart(Highlight(i)) => {
+                    colors.push(theme.resolve(formats[i]).inside(*colors.last()));
+                    events.push(LineHighlightEvent::Style(*colors.last()))
+                }
+                HighlightEvent::Source { start, end } => {
+                    let text = &code[start..end];
+                    let (first, lines) = text
+                        .split_once("\n")
+                        .map_or((text, None), |(first, lines)| (first, Some(lines)));
+                    events.push(LineHighlightEvent::Segment(first));
+                    if let Some(lines) = lines {
+                        events.extend(lines.split("\n").flat_map(|line| {
+                            [
+                                LineHighlightEvent::Newline,
+                                LineHighlightEvent::Segment(line),
+                            ]
+                        }));
+                    }
+                }
+                HighlightEvent::HighlightEnd => {
+                    colors.pop();
+                    events.push(LineHighlightEvent::Style(*colors.last()))
+                }
+            }
+        }
+        events
+    };
+
+    let mut next_color = theme.get(RESET);
+    let mut lines = Vec::new();
+    let mut current_line = Vec::new();
+    let mut tabs = Tabs::new(options.tab_width);
+
+    for event in events {
+        match event {
+            LineHighlightEvent::Style(style) => next_color = style,
+            LineHighlightEvent::Segment(seg) => {
+                current_line.push((next_color, tabs.expand(seg)));
+            }
+            LineHighlightEvent::Newline => {
+                lines.push(current_line);
+                current_line = Vec::new();
+                tabs = Tabs::new(options.tab_width);
+            }
+        }
+    }
+    lines.push(current_line);
+    Ok(lines)
+}
+
+// Says what's making it so big, instead of trying to allocate gigabytes and failing later
+fn too_big(
+    rows: &[(Option<&str>, &SourceLine, Vec<(Style, &str)>)],
+    laid_out: &[Line],
+    width: u32,
+    size: f32,
+) -> String {
+    let max_rows = *PIXEL_BUDGET / width as u64 / size as u64;
+    let mut why =
+        format!("That would be way too big to render, only about {max_rows} lines this wide fit");
+    let widest = (0..rows.len()).max_by(|&a, &b| laid_out[a].width.total_cmp(&laid_out[b].width));
+    if let Some(row) = widest {
+        let label = &rows[row].1.label;
+        // diffs have + and - instead of line numbers, which don't say much
+        if label.parse::<usize>().is_ok() {
+            why += &format!(", and line {label} is the widest one");
+        }
+    }
+    if rows.len() as u64 > max_rows {
+        why += &format!(
+            ". There's {} of them, so try a few at a time with something like +render 1..{max_rows}",
+            rows.len()
+        );
+    } else {
+        why += ". Try wrap= to wrap the long lines";
+    }
+    why + ", or a smaller size="
+}
+
+// A line as it gets drawn
+struct SourceLine<'a> {
+    // what goes in the gutter, usually the line number
+    label: String,
+    segments: Segments<'a>,
+    // a background behind the whole row, and how strong it is
+    tint: Option<(Rgb<u8>, f32)>,
+}
+
+// labels says whether the gutter is shown even when nothing wraps
+fn draw(
+    lines: Vec<SourceLine>,
+    options: &RenderOptions,
+    labels: bool,
+) -> Result<Vec<RgbaImage>, HighlightError> {
+    rasterize(&arrange(lines, options, labels)?, options)
+}
+
+// A render that's laid out but not drawn yet. It owns everything, so the layout and the rasterizing
+// can happen (and be timed, see benches/render.rs) on their own
+pub struct Layout {
+    // every row that gets drawn, so a wrapped line is a few of them
+    lines: Vec<Line>,
+    // the background behind each row, see SourceLine
+    tints: Vec<Option<(Rgb<u8>, f32)>>,
+    // the title, and where the rule under it is
+    header: Option<(Line, i32)>,
+    // the line numbers, and where the rule next to them is
+    gutter: Option<(Vec<Line>, i32)>,
+    // where the code starts, below the title
+    code_y: i32,
+    width: u32,
+    height: u32,
+}
+
+// Really tall renders are split up into a few images, since discord shrinks tall images down to nothing
+fn rows_per_image(size: f32) -> usize {
+    cmp::max(1, MAX_IMAGE_HEIGHT / size as u32) as usize
+}
+
+// Everything up to knowing how big it is. Anything too big is refused here, before it's drawn
+fn arrange(
+    lines: Vec<SourceLine>,
+    options: &RenderOptions,
+    labels: bool,
+) -> Result<Layout, HighlightError> {
+    // the spans are timed, see the logging in the bot's main.rs
+    let stage = info_span!("layout", lines = lines.len()).entered();
+    let theme = options.theme;
+    let fonts = Fonts::new(options.font.as_deref(), options.size);
+    let size = fonts.size;
+    // space on both sides of the lines separating the code from the line numbers and the title
+    let rule_padding = size / 2.0;
+
+    // Each row is what actually gets drawn on one line of the image, and has the label if it's the start of a line.
+    // wrapped rows still know which line they're part of, for the tint
+    let rows = lines
+        .iter()
+        .flat_map(|line| {
+            let segments = line
+                .segments
+                .iter()
+                .map(|(style, text)| (*style, text.as_ref()))
+                .collect::<Vec<_>>();
+            let rows = match options.wrap {
+                Some(max_width) => wrap(&fonts, &segments, max_width as f32),
+                None => vec![segments],
+            };
+            rows.into_iter()
+                .enumerate()
+                .map(move |(j, row)| ((j == 0).then(|| line.label.as_str()), line, row))
+        })
+        .collect::<Vec<_>>();
+    let wrapped = rows.len() > lines.len();
+
+    let ascent = fonts.regular.scaled(size).ascent();
+    let dim = theme.get(GRAY);
+
+    // The title goes above everything, and the code starts below it
+    let (header, code_y) = match options.title {
+        Some(ref title) => {
+            let text_x = match title.avatar {
+                Some(_) => size + rule_padding,
+                None => 0.0,
+            };
+            let mut segments = vec![(theme.get(RESET), title.author.as_str())];
+            if !title.language.is_empty() {
+                segments.push((dim, " · "));
+                segments.push((dim, title.language.as_str()));
+            }
+            let line = layout(&fonts, &segments, text_x, ascent);
+            let rule = (size + rule_padding).round() as i32;
+            (Some((line, rule)), rule + RULE_WIDTH + rule_padding as i32)
+        }
+        None => (None, 0),
+    };
+    let baseline = |i: usize| code_y as f32 + i as f32 * size + ascent;
+
+    // The gutter has to be figured out before the code too, the code starts where it ends
+    // Wrapped lines always get one, so it's clear where the wrapping happened
+    let numbers = rows
+        .iter()
+        .map(|(label, _, _)| match label {
+            Some(_) if !labels => "",
+            Some(label) => label,
+            None => wrap_indicator(&fonts),
+        })
+        .collect::<Vec<_>>();
+    let (gutter, code_x) = if labels || wrapped {
+        let widest = numbers.iter().fold(0f32, |widest, number| {
+            widest.max(layout(&fonts, &[(dim, *number)], 0.0, 0.0).width)
+        });
+        let gutter = numbers
+            .iter()
+            .enumerate()
+            .map(|(i, &number)| {
+                // right aligned, like every editor does it
+                let width = layout(&fonts, &[(dim, number)], 0.0, 0.0).width;
+                layout(&fonts, &[(dim, number)], widest - width, baseline(i))
+            })
+            .collect::<Vec<_>>();
+        let rule = (widest + rule_padding).round();
+        (Some((gutter, rule as i32)), rule + RULE_WIDTH as f32 + rule_padding)
+    } else {
+        (None, 0.0)
+    };
+
+    let laid_out = rows
+        .iter()
+        .enumerate()
+        .map(|(i, (_, _, segments))| layout(&fonts, segments, code_x, baseline(i)))
+        .collect::<Vec<_>>();
+
+    let width = laid_out
+        .iter()
+        .chain(header.as_ref().map(|(line, _)| line))
+        .fold(0, |width, line| {
+            // fake bold sticks out one pixel to the right
+            let extra = match line.glyphs.last() {
+                Some((style, _)) if fonts.fake_bold(style) => 1,
+                _ => 0,
+            };
+            cmp::max(width, line.width.ceil() as u32 + extra)
+        });
+    let height = code_y as u32 + size as u32 * rows.len() as u32;
+    drop(stage);
+    debug!("dimensions are {width}x{height}");
+    if width as u64 * height as u64 > *PIXEL_BUDGET {
+        return Err(HighlightError::TooBig(too_big(&rows, &laid_out, width, size)));
+    }
+    let rows_per_image = rows_per_image(size);
+    if rows.len() > rows_per_image * MAX_IMAGES {
+        return Err(HighlightError::TooBig(format!(
+            "That's {} lines, which is too many even split up into {MAX_IMAGES} images. Try a range like +render 1..{}",
+            rows.len(),
+            rows_per_image * MAX_IMAGES
+        )));
+    }
+    Ok(Layout {
+        lines: laid_out,
+        tints: rows.iter().map(|(_, line, _)| line.tint).collect(),
+        header,
+        gutter,
+        code_y,
+        width,
+        height,
+    })
+}
+
+// The second half of render, as one image or a few if it's really tall, or as the frames of +render animate
+pub fn rasterize(
+    layout: &Layout,
+    options: &RenderOptions,
+) -> Result<Vec<RgbaImage>, HighlightError> {
+    let (code_y, width, height) = (layout.code_y, layout.width, layout.height);
+    let _stage = info_span!("rasterize", width, height).entered();
+    let theme = options.theme;
+    // the faces borrow the font files, so the layout can't keep them. it didn't rasterize any glyphs anyways
+    let fonts = Fonts::new(options.font.as_deref(), options.size);
+    let size = fonts.size;
+    let ascent = fonts.regular.scaled(size).ascent();
+    let descent = fonts.regular.scaled(size).descent();
+    let dim = theme.get(GRAY);
+    let baseline = |i: usize| code_y as f32 + i as f32 * size + ascent;
+    let (header, gutter) = (&layout.header, &layout.gutter);
+
+    // every image gets its own border, and only the first one has the title
+    let rows_per_image = rows_per_image(size);
+    let slices = (0..layout.lines.len())
+        .step_by(rows_per_image)
+        .map(|start| start..cmp::min(start + rows_per_image, layout.lines.len()))
+        .collect::<Vec<_>>();
+
+    // a closure, because +render animate draws it over and over with less of the code
+    let draw_images = |laid_out: &[Line]| {
+        let mut images = Vec::new();
+        for slice in slices.iter().cloned() {
+            // where this image starts, as if it was all one big image
+            let (offset, slice_height) = match slice.start {
+                0 => (0, code_y as u32 + size as u32 * slice.len() as u32),
+                start => (
+                    code_y + start as i32 * size as i32,
+                    size as u32 * slice.len() as u32,
+                ),
+            };
+            let bottom = offset + slice_height as i32;
+            let mut image = RgbaImage::default();
+            let transparent = options.transparent;
+            let safe_area =
+                &mut border::make_image(&mut image, width, slice_height, theme, transparent);
+
+            let mut paint = |x: i32, y: i32, rgb: Rgb<u8>, v: f32| {
+                paint_pixel(&mut **safe_area, x, y - offset, rgb, v)
+            };
+
+            // Tinted lines (hl= and diffs) get it across the whole width, including the gutter.
+            // this is before anything else so the numbers and code are drawn on top of it
+            for i in slice.clone() {
+                if let Some((rgb, v)) = layout.tints[i] {
+                    let top = code_y + i as i32 * size as i32;
+                    for y in top..top + size as i32 {
+                        for x in 0..width as i32 {
+                            paint(x, y, rgb, v);
+                        }
+                    }
+                }
+            }
+
+            if let (Some(title), Some((line, rule)), 0) = (&options.title, header, slice.start) {
+                if let Some(ref avatar) = title.avatar {
+                    let avatar = imageops::resize(
+                        avatar,
+                        size as u32,
+                        size as u32,
+                        imageops::FilterType::Triangle,
+                    );
+                    // cropped to a circle, like discord shows it
+                    let radius = size / 2.0;
+                    for (x, y, &Rgba([r, g, b, a])) in avatar.enumerate_pixels() {
+                        let dx = x as f32 + 0.5 - radius;
+                        let dy = y as f32 + 0.5 - radius;
+                        let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+                        let v = a as f32 / u8::MAX as f32 * coverage;
+                        paint(x as i32, y as i32, Rgb([r, g, b]), v);
+                    }
+                }
+                draw_glyphs(&fonts, line, &mut paint);
+                for y in *rule..rule + RULE_WIDTH {
+                    for x in 0..width as i32 {
+                        paint(x, y, dim.rgb, 0.5);
+                    }
+                }
+            }
+
+            if let Some((gutter, rule)) = gutter {
+                for line in &gutter[slice.clone()] {
+                    draw_glyphs(&fonts, line, &mut paint);
+                }
+                for x in *rule..rule + RULE_WIDTH {
+                    for y in cmp::max(code_y, offset)..bottom {
+                        // even dimmer than the numbers
+                        paint(x, y, dim.rgb, 0.5);
+                    }
+                }
+            }
+
+            // The code is most of the work for big renders, so the rows are drawn on a few threads at once,
+            // each into its own buffer that then gets put on top of the image.
+            // it's done in batches so there's never a second copy of the entire image in memory
+            for (batch, rows) in laid_out[slice.clone()].chunks(ROWS_PER_BATCH).enumerate() {
+                let drawn = rows
+                    .par_iter()
+                    .enumerate()
+                    .map(|(i, line)| {
+                        let i = slice.start + batch * ROWS_PER_BATCH + i;
+                        // with a whole row of room above and below, for anything that sticks out of it
+                        let origin = code_y + (i as i32 - 1) * size as i32;
+                        let mut buffer = RgbaImage::new(width, size as u32 * 3);
+                        let mut paint = |x: i32, y: i32, rgb: Rgb<u8>, v: f32| {
+                            paint_pixel(&mut buffer, x, y - origin, rgb, v)
+                        };
+                        // backgrounds go first, so the text is drawn on top of them
+                        let top = code_y + i as i32 * size as i32;
+                        for &(background, start, end) in &line.backgrounds {
+                            for x in start.floor() as i32..end.ceil() as i32 {
+                                for y in top..top + size as i32 {
+                                    paint(x, y, background, 1.0);
+                                }
+                            }
+                        }
+                        draw_glyphs(&fonts, line, &mut paint);
+                        for (x, top, image) in &line.emoji {
+                            for (dx, dy, &Rgba([r, g, b, a])) in image.enumerate_pixels() {
+                                let x = x.round() as i32 + dx as i32;
+                                let y = top.round() as i32 + dy as i32;
+                                paint(x, y, Rgb([r, g, b]), a as f32 / u8::MAX as f32);
+                            }
+                        }
+                        // a bit below the baseline, and as thick as the strokes of the font roughly are
+                        let underline_y = (baseline(i) - descent / 3.0).round() as i32;
+                        let thickness = cmp::max(1, size as i32 / 18);
+                        for (style, start, end) in &line.underlines {
+                            for x in start.round() as i32..end.round() as i32 {
+                                for y in underline_y..underline_y + thickness {
+                                    paint(x, y, style.rgb, 1.0);
+                                }
+                            }
+                        }
+                        (origin, buffer)
+                    })
+                    .collect::<Vec<_>>();
+                // in order, so where rows overlap it looks the same as drawing them one after another
+                for (origin, buffer) in drawn {
+                    for (x, y, color) in buffer.enumerate_pixels() {
+                        let y = origin + y as i32 - offset;
+                        if color[3] == 0 || y < 0 || y as u32 >= slice_height {
+                            continue;
+                        }
+                        let pixel = safe_area.get_pixel(x, y as u32);
+                        safe_area.put_pixel(x, y as u32, blend(pixel, *color));
+                    }
+                }
+            }
+            images.push(image);
+        }
+        images
+    };
+    if !options.animate {
+        return Ok(draw_images(&layout.lines));
+    }
+
+    // +render animate draws it again for every frame, with a few more glyphs every time.
+    // all the frames are in memory at once, so it has to be pretty small
+    if slices.len() > 1 {
+        return Err(HighlightError::TooBig(format!(
+            "That's too tall to animate, try a range like +render 1..{rows_per_image}"
+        )));
+    }
+    let total = layout
+        .lines
+        .iter()
+        .map(|line| line.glyphs.len())
+        .sum::<usize>();
+    if total > MAX_ANIMATED_GLYPHS {
+        return Err(HighlightError::TooBig(format!(
+            "That's too much code to animate, it can be {MAX_ANIMATED_GLYPHS} characters at most"
+        )));
+    }
+    let frame_count = (total / GLYPHS_PER_FRAME + 2) as u64;
+    if frame_count * width as u64 * height as u64 > *PIXEL_BUDGET {
+        return Err(HighlightError::TooBig(
+            "That's too big to animate, try a smaller size= or a range".to_owned(),
+        ));
+    }
+    let mut frames: Vec<RgbaImage> = Vec::new();
+    for revealed in (0..total).step_by(GLYPHS_PER_FRAME).chain(iter::once(total)) {
+        let mut before = 0;
+        let cut = layout
+            .lines
+            .iter()
+            .map(|line| {
+                let shown = revealed.saturating_sub(before).min(line.glyphs.len());
+                let cutoff = match line.glyphs.get(shown) {
+                    // it didn't get to this line yet
+                    _ if revealed <= before => f32::NEG_INFINITY,
+                    Some((_, glyph)) => glyph.position.x,
+                    None => f32::INFINITY,
+                };
+                before += line.glyphs.len();
+                cut_line(line, shown, cutoff)
+            })
+            .collect::<Vec<_>>();
+        let frame = draw_images(&cut).pop().unwrap();
+        // whitespace doesn't change anything, and neither does a frame that's already there
+        if frames.last() != Some(&frame) {
+            frames.push(frame);
+        }
+    }
+    Ok(frames)
+}
+
+// The first few glyphs of a line, for +render animate. Everything else on it is cut off at the same place
+fn cut_line(line: &Line, glyphs: usize, cutoff: f32) -> Line {
+    Line {
+        glyphs: line.glyphs[..glyphs].to_vec(),
+        underlines: line
+            .underlines
+            .iter()
+            .filter(|(_, start, _)| *start < cutoff)
+            .map(|&(style, start, end)| (style, start, end.min(cutoff)))
+            .collect(),
+        backgrounds: line
+            .backgrounds
+            .iter()
+            .filter(|(_, start, _)| *start < cutoff)
+            .map(|&(rgb, start, end)| (rgb, start, end.min(cutoff)))
+            .collect(),
+        emoji: line
+            .emoji
+            .iter()
+            .filter(|(x, _, _)| *x < cutoff)
+            .cloned()
+            .collect(),
+        width: line.width,
+    }
+}
+
+fn paint_pixel(
+    target: &mut impl GenericImage<Pixel = Rgba<u8>>,
+    x: i32,
+    y: i32,
+    rgb: Rgb<u8>,
+    v: f32,
+) {
+    let (width, height) = target.dimensions();
+    if x < 0 || y < 0 || x as u32 >= width || y as u32 >= height {
+        return;
+    }
+    let a = (v * u8::MAX as f32).trunc() as u8;
+    let Rgb([r, g, b]) = rgb;
+    let color = Rgba([r, g, b, a]);
+    let pixel = target.get_pixel(x as u32, y as u32);
+    target.put_pixel(x as u32, y as u32, blend(pixel, color));
+}
+
+lazy_static! {
+    // Coverage is blended in linear light, otherwise light text on a dark background looks thin and fringed.
+    // RENDER_GAMMA=1 blends straight in sRGB, like image's own blend does
+    static ref GAMMA: f32 = config::parse("RENDER_GAMMA")
+        .filter(|&gamma: &f32| gamma > 0.0)
+        .unwrap_or(2.2);
+    static ref TO_LINEAR: [f32; 256] = {
+        let mut table = [0.0; 256];
+        for (i, linear) in table.iter_mut().enumerate() {
+            *linear = (i as f32 / u8::MAX as f32).powf(*GAMMA);
+        }
+        table
+    };
+}
+
+// source over destination, with straight (not premultiplied) alpha
+fn blend(destination: Rgba<u8>, source: Rgba<u8>) -> Rgba<u8> {
+    match source[3] {
+        0 => return destination,
+        u8::MAX => return source,
+        _ => (),
+    }
+    let source_alpha = source[3] as f32 / u8::MAX as f32;
+    let destination_alpha = destination[3] as f32 / u8::MAX as f32 * (1.0 - source_alpha);
+    let alpha = source_alpha + destination_alpha;
+    let channel = |i: usize| {
+        let linear = (TO_LINEAR[source[i] as usize] * source_alpha
+            + TO_LINEAR[destination[i] as usize] * destination_alpha)
+            / alpha;
+        (linear.powf(1.0 / *GAMMA) * u8::MAX as f32).round() as u8
+    };
+    Rgba([
+        channel(0),
+        channel(1),
+        channel(2),
+        (alpha * u8::MAX as f32).round() as u8,
+    ])
+}
+
+fn draw_glyphs(fonts: &Fonts, line: &Line, paint: &mut impl FnMut(i32, i32, Rgb<u8>, f32)) {
+    for (style, glyph) in &line.glyphs {
+        if let Some((x, y, mask)) = fonts.rasterize(style, glyph) {
+            let offsets: &[i32] = if fonts.fake_bold(style) { &[0, 1] } else { &[0] };
+            for offset in offsets {
+                for (i, &v) in mask.coverage.iter().enumerate() {
+                    if v == 0.0 {
+                        continue;
+                    }
+                    let dx = (i as u32 % mask.width) as i32;
+                    let dy = (i as u32 / mask.width) as i32;
+                    paint(x + mask.left + dx + offset, y + mask.top + dy, style.rgb, v);
+                }
+            }
+        }
+    }
+}
+
+struct Line {
+    glyphs: Vec<(Style, Glyph)>,
+    // (style, start x, end x)
+    underlines: Vec<(Style, f32, f32)>,
+    backgrounds: Vec<(Rgb<u8>, f32, f32)>,
+    // (x, top, image)
+    emoji: Vec<(f32, f32, Arc<RgbaImage>)>,
+    width: f32,
+}
+
+// Splits a line into rows that are at most max_width wide, between graphemes so nothing gets torn apart.
+// This doesn't shape anything, so it can be off by a pixel or two, which doesn't really matter
+fn wrap<'a>(
+    fonts: &Fonts,
+    segments: &[(Style, &'a str)],
+    max_width: f32,
+) -> Vec<Vec<(Style, &'a str)>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut caret = 0f32;
+    for &(style, text) in segments {
+        let font = fonts.face(&style);
+        let mut start = 0;
+        for (i, grapheme) in text.grapheme_indices(true) {
+            let advance = match emoji::get(grapheme, fonts.size as u32) {
+                Some(_) => fonts.size,
+                None => grapheme
+                    .chars()
+                    .map(|ch| font.scaled(fonts.size).h_advance(font.font.glyph_id(ch)))
+                    .sum::<f32>(),
+            };
+            // a single grapheme wider than the whole row still has to go somewhere
+            if caret + advance > max_width && caret > 0.0 {
+                if i > start {
+                    row.push((style, &text[start..i]));
+                }
+                rows.push(mem::take(&mut row));
+                caret = 0.0;
+                start = i;
+            }
+            caret += advance;
+        }
+        if start < text.len() {
+            row.push((style, &text[start..]));
+        }
+    }
+    rows.push(row);
+    rows
+}
+
+// In the gutter, where a line number would be if it wasn't the same line as above
+fn wrap_indicator(fonts: &Fonts) -> &'static str {
+    // glyph 0 is the "this font doesn't have that" box
+    if fonts.regular.font.glyph_id('↪') == GlyphId(0) {
+        "»"
+    } else {
+        "↪"
+    }
+}
+
+// Every style can use a different font, so every segment is shaped on its own
+fn layout(fonts: &Fonts, segments: &[(Style, &str)], x: f32, baseline: f32) -> Line {
+    let mut caret = x;
+    let mut glyphs = Vec::new();
+    let mut underlines = Vec::new();
+    let mut backgrounds = Vec::new();
+    let mut emoji = Vec::new();
+    let ascent = fonts.regular.scaled(fonts.size).ascent();
+
+    for &(style, text) in segments {
+        let start = caret;
+        // emoji aren't in the font, so the text around them is shaped separately
+        let mut run = 0;
+        for (i, grapheme) in text.grapheme_indices(true) {
+            // emoji are square, as big as the text, and sit on top of the line like discord does it
+            if let Some(image) = emoji::get(grapheme, fonts.size as u32) {
+                caret = shape(fonts, &text[run..i], style, caret, baseline, &mut glyphs);
+                emoji.push((caret, baseline - ascent, image));
+                caret += fonts.size;
+                run = i + grapheme.len();
+            }
+        }
+        caret = shape(fonts, &text[run..], style, caret, baseline, &mut glyphs);
+        if style.underline && caret > start {
+            underlines.push((style, start, caret));
+        }
+        if let Some(background) = style.background {
+            if caret > start {
+                backgrounds.push((background, start, caret));
+            }
+        }
+    }
+    Line {
+        glyphs,
+        underlines,
+        backgrounds,
+        emoji,
+        width: caret,
+    }
+}
+
+// Returns where the caret ends up after the text
+fn shape(
+    fonts: &Fonts,
+    text: &str,
+    style: Style,
+    mut caret: f32,
+    baseline: f32,
+    glyphs: &mut Vec<(Style, Glyph)>,
+) -> f32 {
+    if text.is_empty() {
+        return caret;
+    }
+    let face = fonts.face(&style);
+    let mut buffer = UnicodeBuffer::new();
+    buffer.push_str(text);
+    let shaped = rustybuzz::shape(&face.shaper, &[], buffer);
+    // rustybuzz works in font units
+    let factor = face.scaled(fonts.size).scale_factor();
+    for (info, position) in iter::zip(shaped.glyph_infos(), shaped.glyph_positions()) {
+        let glyph = GlyphId(info.glyph_id as u16).with_scale_and_position(
+            fonts.scale(),
+            point(
+                caret + position.x_offset as f32 * factor.horizontal,
+                baseline - position.y_offset as f32 * factor.vertical,
+            ),
+        );
+        glyphs.push((style, glyph));
+        caret += position.x_advance as f32 * factor.horizontal;
+    }
+    caret
+}
+
+// Color emoji, as PNGs in the emoji directory named after their codepoints like twemoji does it (1f600.png, 1f1f3-1f1f4.png)
+// fonts only have outlines, so without these emoji are just the font's missing glyph box
+mod emoji {
+    use super::*;
+
+    const EMOJI_DIR: &str = "emoji";
+
+    lazy_static! {
+        // None is cached too, so missing emoji don't hit the disk every time.
+        // keyed by the size too, since they're resized to match the text
+        static ref CACHE: std::sync::Mutex<HashMap<(String, u32), Option<Arc<RgbaImage>>>> =
+            Default::default();
+    }
+
+    pub fn get(grapheme: &str, size: u32) -> Option<Arc<RgbaImage>> {
+        // every emoji has something outside of ASCII, and this saves looking up every single letter
+        if grapheme.is_ascii() {
+            return None;
+        }
+        let mut cache = CACHE.lock().unwrap();
+        cache
+            .entry((grapheme.to_owned(), size))
+            .or_insert_with(|| load(grapheme, size))
+            .clone()
+    }
+
+    fn file_name(codepoints: impl Iterator<Item = char>) -> String {
+        let names = codepoints.map(|c| format!("{:x}", c as u32));
+        names.collect::<Vec<_>>().join("-")
+    }
+
+    fn load(grapheme: &str, size: u32) -> Option<Arc<RgbaImage>> {
+        // twemoji leaves out the variation selector in most file names, but not all of them
+        let names = [
+            file_name(grapheme.chars()),
+            file_name(grapheme.chars().filter(|&c| c != '\u{fe0f}')),
+        ];
+        let image = names.iter().find_map(|name| {
+            let path = Path::new(EMOJI_DIR).join(format!("{name}.png"));
+            let bytes = fs::read(path).ok()?;
+            match image::load_from_memory(&bytes) {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    error!("emoji {name} is broken: {e}");
+                    None
+                }
+            }
+        })?;
+        Some(Arc::new(imageops::resize(
+            &image.to_rgba8(),
+            size,
+            size,
+            imageops::FilterType::Triangle,
+        )))
+    }
+}
+
+mod border {
+    use super::*;
+
+    // The code gets drawn inside the returned area, with the theme's padding around it
+    pub fn make_image<'a>(
+        image: &'a mut RgbaImage,
+        width: u32,
+        height: u32,
+        theme: &Theme,
+        transparent: bool,
+    ) -> SubImage<&'a mut RgbaImage> {
+        let frame = theme.frame;
+        let padding = frame.padding;
+        let real_width = width + padding * 2;
+        let real_height = height + padding * 2;
+        if transparent {
+            // still with the same space around it, so nothing is right at the edge of the image
+            *image = RgbaImage::new(real_width, real_height);
+            return image.sub_image(padding, padding, width, height);
+        }
+        let Rgb([r, g, b]) = frame.background;
+        *image = RgbaImage::from_pixel(real_width, real_height, Rgba([r, g, b, u8::MAX]));
+        // only the pixels this close to the edge can be anything other than the background
+        let band = frame.radius.max(frame.border_width).ceil() as u32 + 1;
+        for y in 0..real_height {
+            for x in 0..real_width {
+                let near_edge =
+                    x < band || y < band || x + band >= real_width || y + band >= real_height;
+                if near_edge {
+                    let pixel = edge_pixel(frame, x, y, real_width, real_height);
+                    image.put_pixel(x, y, pixel);
+                }
+            }
+        }
+        image.sub_image(padding, padding, width, height)
+    }
+
+    // How far the middle of that pixel is from the edge of the rounded rectangle, negative inside of it
+    fn distance(frame: theme::Frame, x: u32, y: u32, width: u32, height: u32) -> f32 {
+        let (half_width, half_height) = (width as f32 / 2.0, height as f32 / 2.0);
+        let radius = frame.radius.min(half_width).min(half_height);
+        let dx = (x as f32 + 0.5 - half_width).abs() - (half_width - radius);
+        let dy = (y as f32 + 0.5 - half_height).abs() - (half_height - radius);
+        let outside = (dx.max(0.0).powi(2) + dy.max(0.0).powi(2)).sqrt();
+        let inside = dx.max(dy).min(0.0);
+        outside + inside - radius
+    }
+
+    fn edge_pixel(frame: theme::Frame, x: u32, y: u32, width: u32, height: u32) -> Rgba<u8> {
+        let distance = distance(frame, x, y, width, height);
+        // antialiased by how much of the pixel is on each side of the edges
+        let alpha = (0.5 - distance).clamp(0.0, 1.0);
+        let t = (distance + frame.border_width + 0.5).clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        let Rgb([r, g, b]) = frame.background;
+        let Rgb([br, bg, bb]) = frame.border;
+        Rgba([
+            mix(r, br),
+            mix(g, bg),
+            mix(b, bb),
+            (alpha * u8::MAX as f32).round() as u8,
+        ])
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt::Write as _;
+
+use super::*;
+use html::{escape, hex};
+
+// chl --format svg, the code as text in a vector image, so it stays sharp at any size.
+// The text is drawn by whatever is looking at it, so this only guesses how wide a column is.
+// Any monospace font is close enough to 0.6em that the backgrounds still line up
+
+const FONT_SIZE: f32 = 16.0;
+const LINE_HEIGHT: f32 = FONT_SIZE * 1.4;
+const COLUMN_WIDTH: f32 = FONT_SIZE * 0.6;
+
+pub fn highlight_svg(
+    config: &LanguageConfig,
+    code: &str,
+    theme: &Theme,
+    tab_width: usize,
+) -> Result<String, HighlightError> {
+    let frame = theme.frame;
+    let formats = config.formats.read().unwrap();
+    let mut styles = ne_vec![theme.get(RESET)];
+    let mut tabs = Tabs::new(tab_width);
+    // every line is the text with the style it's in, split up wherever the style changes
+    let mut lines = vec![Vec::<(Style, String)>::new()];
+    for event in highlight_events(config, code)? {
+        match event {
+            HighlightEvent::HighlightStart(Highlight(u)) => {
+                styles.push(theme.resolve(formats[u]).inside(*styles.last()))
+            }
+            HighlightEvent::Source { start, end } => {
+                let text = tabs.expand(&code[start..end]);
+                for (i, part) in text.split('\n').enumerate() {
+                    if i > 0 {
+                        lines.push(Vec::new());
+                    }
+                    if !part.is_empty() {
+                        lines
+                            .last_mut()
+                            .unwrap()
+                            .push((*styles.last(), part.to_owned()));
+                    }
+                }
+            }
+            HighlightEvent::HighlightEnd => {
+                styles.pop();
+            }
+        }
+    }
+    // the newline at the end of a file isn't an empty line at the bottom
+    if lines.len() > 1 && lines.last().map_or(false, Vec::is_empty) {
+        lines.pop();
+    }
+
+    let columns = lines
+        .iter()
+        .map(|line| {
+            line.iter()
+                .map(|(_, text)| text.chars().count())
+                .sum::<usize>()
+        })
+        .max()
+        .unwrap_or(0);
+    let padding = frame.padding as f32 + frame.border_width;
+    let width = columns as f32 * COLUMN_WIDTH + padding * 2.0;
+    let height = lines.len() as f32 * LINE_HEIGHT + padding * 2.0;
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">"
+    )
+    .unwrap();
+    // the stroke is centered on the edge, so it's moved in by half of it to stay inside the image
+    let inset = frame.border_width / 2.0;
+    writeln!(
+        out,
+        "<rect x=\"{inset}\" y=\"{inset}\" width=\"{}\" height=\"{}\" rx=\"{}\" fill=\"{}\" stroke=\"{}\" stroke-width=\"{}\"/>",
+        width - frame.border_width,
+        height - frame.border_width,
+        frame.radius,
+        hex(frame.background),
+        hex(frame.border),
+        frame.border_width
+    )
+    .unwrap();
+
+    // backgrounds go first, so they're behind all of the text
+    for (row, line) in lines.iter().enumerate() {
+        let mut column = 0;
+        for (style, text) in line {
+            let length = text.chars().count();
+            if let Some(background) = style.background {
+                writeln!(
+                    out,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{LINE_HEIGHT}\" fill=\"{}\"/>",
+                    padding + column as f32 * COLUMN_WIDTH,
+                    padding + row as f32 * LINE_HEIGHT,
+                    length as f32 * COLUMN_WIDTH,
+                    hex(background)
+                )
+                .unwrap();
+            }
+            column += length;
+        }
+    }
+
+    writeln!(
+        out,
+        "<text font-family=\"'Fira Code', monospace\" font-size=\"{FONT_SIZE}\" xml:space=\"preserve\">"
+    )
+    .unwrap();
+    for (row, line) in lines.iter().enumerate() {
+        // the baseline is a bit below the middle of the line
+        let y = padding + row as f32 * LINE_HEIGHT + LINE_HEIGHT * 0.75;
+        write!(out, "<tspan x=\"{padding}\" y=\"{y}\">").unwrap();
+        for (style, text) in line {
+            write!(out, "<tspan {}>{}</tspan>", attributes(style), escape(text)).unwrap();
+        }
+        out.push_str("</tspan>\n");
+    }
+    out.push_str("</text>\n</svg>\n");
+    Ok(out)
+}
+
+fn attributes(style: &Style) -> String {
+    let mut attributes = format!("fill=\"{}\"", hex(style.rgb));
+    if style.bold {
+        attributes.push_str(" font-weight=\"bold\"");
+    }
+    if style.italic {
+        attributes.push_str(" font-style=\"italic\"");
+    }
+    if style.underline {
+        attributes.push_str(" text-decoration=\"underline\"");
+    }
+    attributes
+}
@@ -0,0 +1,170 @@
+use super::*;
+use hex_literal::hex;
+
+macro_rules! color {
+    ($ansi:literal, $hex:literal $(on $background:literal)? $(, $style:ident)*) => {
+        Style {
+            ansi: concat!("\u{001b}[", $ansi, "m"),
+            rgb: Rgb(hex!($hex)),
+            $(background: Some(Rgb(hex!($background))),)?
+            $($style: true,)*
+            ..Style::PLAIN
+        }
+    };
+}
+
+pub struct Theme {
+    pub name: &'static str,
+    pub frame: Frame,
+    palette: Palette,
+}
+
+// The rounded rectangle around the code in renders
+#[derive(Clone, Copy)]
+pub struct Frame {
+    pub background: Rgb<u8>,
+    pub border: Rgb<u8>,
+    // in pixels, and the border is drawn inside of the rounded corners
+    pub radius: f32,
+    pub border_width: f32,
+    // between the edge of the image and the code
+    pub padding: u32,
+}
+
+// what discord's own codeblocks look like on the dark theme
+const DISCORD_FRAME: Frame = Frame {
+    background: Rgb(hex!("2f3136")),
+    border: Rgb(hex!("202225")),
+    radius: 10.5,
+    border_width: 2.0,
+    padding: 10,
+};
+
+impl Theme {
+    pub fn get(&self, name: ColorName) -> Style {
+        self.palette.get(name)
+    }
+
+    pub fn resolve(&self, format: Format) -> Style {
+        match format {
+            Format::Named(name) => self.get(name),
+            Format::Custom(color) => color,
+        }
+    }
+}
+
+// The ANSI codes are the same in every theme, because discord decides what those look like, not us.
+// Only the renders actually look different.
+//
+// ERROR has a red background, so a syntax error stands out no matter what's inside it.
+// the text keeps the normal text color, red text on red would be unreadable
+//
+// Some of these are using bold and other styles to achieve a finer granularity of styles
+// The renderer draws those styles too now, but bold alone is hard to spot at a glance,
+// so they also keep colors from dark_vs to make them look distinct when rendering
+const DISCORD_DARK: Theme = Theme {
+    name: "dark",
+    frame: DISCORD_FRAME,
+    palette: Palette {
+        ERROR: color!("0;41", "b9bbbe" on "5c2326"),
+        RESET: color!(0, "b9bbbe"),
+        GRAY: color!(30, "4f545c"),
+        RED: color!(31, "dc322f"),
+        LIGHT_GREEN: color!(32, "b5cea8"), // dark_vs constant.numeric
+        DARK_GREEN: color!("32;1", "6a9955", bold), // dark_vs comment
+        YELLOW: color!(33, "b58900"),
+        BLUE: color!(34, "268bd2"),
+        DARK_BLUE: color!("34;1", "569cd6", bold), // dark_vs constant.language
+        PINK: color!(35, "d33682"),
+        CYAN: color!(36, "2aa198"),
+        WHITE: color!(37, "ffffff"),
+    },
+};
+
+// mostly light_vs, since discord's own colors are way too bright on a light background
+const DISCORD_LIGHT: Theme = Theme {
+    name: "light",
+    frame: Frame {
+        background: Rgb(hex!("f2f3f5")),
+        border: Rgb(hex!("e3e5e8")),
+        ..DISCORD_FRAME
+    },
+    palette: Palette {
+        ERROR: color!("0;41", "2e3338" on "f7c6c6"),
+        RESET: color!(0, "2e3338"),
+        GRAY: color!(30, "747f8d"),
+        RED: color!(31, "a31515"),
+        LIGHT_GREEN: color!(32, "098658"),
+        DARK_GREEN: color!("32;1", "008000", bold),
+        YELLOW: color!(33, "795e26"),
+        BLUE: color!(34, "0000ff"),
+        DARK_BLUE: color!("34;1", "0070c1", bold),
+        PINK: color!(35, "af00db"),
+        CYAN: color!(36, "007a7a"),
+        WHITE: color!(37, "000000"),
+    },
+};
+
+const SOLARIZED: Theme = Theme {
+    name: "solarized",
+    frame: Frame {
+        background: Rgb(hex!("002b36")),
+        border: Rgb(hex!("073642")),
+        ..DISCORD_FRAME
+    },
+    palette: Palette {
+        ERROR: color!("0;41", "839496" on "4a2a2a"),
+        RESET: color!(0, "839496"),
+        GRAY: color!(30, "586e75"),
+        RED: color!(31, "dc322f"),
+        LIGHT_GREEN: color!(32, "859900"),
+        DARK_GREEN: color!("32;1", "6c71c4", bold),
+        YELLOW: color!(33, "b58900"),
+        BLUE: color!(34, "268bd2"),
+        DARK_BLUE: color!("34;1", "cb4b16", bold),
+        PINK: color!(35, "d33682"),
+        CYAN: color!(36, "2aa198"),
+        WHITE: color!(37, "eee8d5"),
+    },
+};
+
+const DRACULA: Theme = Theme {
+    name: "dracula",
+    frame: Frame {
+        background: Rgb(hex!("282a36")),
+        border: Rgb(hex!("21222c")),
+        // dracula is a bit rounder than everything else
+        radius: 16.0,
+        padding: 14,
+        ..DISCORD_FRAME
+    },
+    palette: Palette {
+        ERROR: color!("0;41", "f8f8f2" on "5c2a3a"),
+        RESET: color!(0, "f8f8f2"),
+        GRAY: color!(30, "6272a4"),
+        RED: color!(31, "ff5555"),
+        LIGHT_GREEN: color!(32, "bd93f9"),
+        DARK_GREEN: color!("32;1", "ffb86c", bold),
+        YELLOW: color!(33, "50fa7b"),
+        BLUE: color!(34, "8be9fd"),
+        DARK_BLUE: color!("34;1", "bd93f9", bold),
+        PINK: color!(35, "ff79c6"),
+        CYAN: color!(36, "f1fa8c"),
+        WHITE: color!(37, "ffffff"),
+    },
+};
+
+pub const THEMES: &[Theme] = &[DISCORD_DARK, DISCORD_LIGHT, SOLARIZED, DRACULA];
+pub const DEFAULT: &Theme = &DISCORD_DARK;
+
+pub fn by_name(name: &str) -> Option<&'static Theme> {
+    THEMES
+        .iter()
+        .find(|theme| theme.name.eq_ignore_ascii_case(name))
+}
+
+// for error messages and such
+pub fn names() -> String {
+    let names = THEMES.iter().map(|theme| theme.name);
+    names.collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,9 @@
+#include <stdio.h>
+
+/* tabs are expanded to 4 spaces */
+int main(void) {
+	for (int i = 0; i < 3; i++) {
+		printf("%d\n", i);
+	}
+	return 0;
+}
@@ -0,0 +1,12 @@
+class Stack:
+    """a list, but only the end of it"""
+
+    def __init__(self):
+        self.items = []
+
+    def push(self, item):
+        self.items.append(item)
+
+    @property
+    def top(self):
+        return self.items[-1] if self.items else None
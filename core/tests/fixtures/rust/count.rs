@@ -0,0 +1,14 @@
+use std::collections::HashMap;
+
+// counts how often every word shows up
+fn count(text: &str) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    for word in text.split_whitespace() {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    counts
+}
+
+fn main() {
+    println!("{:?}", count("a b a"));
+}
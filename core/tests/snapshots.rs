@@ -0,0 +1,73 @@
+// What syntax_highlight and pretty_parse make of every file in tests/fixtures, compared against
+// the snapshots in tests/snapshots. The directory a fixture is in is the language it's highlighted as,
+// and languages that weren't compiled in (see the lang-* features) are skipped.
+//
+// Changing a grammar, a query or a color changes these, so the change shows up as a diff of the output.
+// cargo insta review goes through them, and the new snapshots get committed along with the change.
+// The ANSI escape is written as \e in the snapshots, so the diffs are readable
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use custom_highlight_core::{
+    languages, parse, pretty_parse, syntax_highlight, theme, LanguageConfig, DEFAULT_TAB_WIDTH,
+};
+use lazy_static::lazy_static;
+
+lazy_static! {
+    // the same languages.toml the bot uses, wherever the tests are run from
+    static ref LANGUAGES: HashMap<String, Arc<LanguageConfig>> =
+        languages::load_from(concat!(env!("CARGO_MANIFEST_DIR"), "/../languages.toml"))
+            .unwrap_or_else(|e| panic!("{e}"));
+}
+
+// None if the language isn't compiled in
+fn fixture(path: &Path) -> Option<(Arc<LanguageConfig>, String)> {
+    let lang = path.parent()?.file_name()?.to_str()?;
+    let config = LANGUAGES.get(lang)?.clone();
+    let code = fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+    Some((config, code))
+}
+
+fn visible(ansi: &str) -> String {
+    ansi.replace('\u{001b}', "\\e")
+}
+
+#[test]
+fn highlight() {
+    insta::glob!("fixtures/*/*", |path| {
+        let (config, code) = match fixture(path) {
+            Some(fixture) => fixture,
+            None => return,
+        };
+        let highlighted =
+            syntax_highlight(&config, &code, theme::DEFAULT, DEFAULT_TAB_WIDTH).unwrap();
+        insta::assert_snapshot!(visible(&highlighted));
+    });
+}
+
+#[test]
+fn pretty() {
+    insta::glob!("fixtures/*/*", |path| {
+        let (config, code) = match fixture(path) {
+            Some(fixture) => fixture,
+            None => return,
+        };
+        let tree = parse(&config, &code).unwrap();
+        let (parsed, _) =
+            pretty_parse(&tree, &code, Some(theme::DEFAULT), false, &[], None).unwrap();
+        insta::assert_snapshot!(visible(&parsed));
+    });
+}
+
+// +pparse, which has no colors but is otherwise the same, so this is mostly about the layout
+#[test]
+fn plain() {
+    insta::glob!("fixtures/*/*", |path| {
+        let (config, code) = match fixture(path) {
+            Some(fixture) => fixture,
+            None => return,
+        };
+        let tree = parse(&config, &code).unwrap();
+        let (parsed, _) = pretty_parse(&tree, &code, None, false, &[], None).unwrap();
+        insta::assert_snapshot!(parsed);
+    });
+}
@@ -0,0 +1,21 @@
+// Whatever anyone sends goes through these, and whatever they find has to be sliced out of the message
+#![no_main]
+
+use custom_highlight_core::markdown::{codeblock, codeblocks, fence, is_fence_char};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|content: &str| {
+    if let Some((before, lang, code, after)) = codeblock(content) {
+        assert!(content.contains(before) && content.contains(after));
+        assert!(content.contains(lang) && content.contains(code));
+        assert!(!code.is_empty());
+    }
+    for (lang, code) in codeblocks(content) {
+        assert!(content.contains(lang) && content.contains(code));
+        assert!(!code.is_empty());
+    }
+    if let Some((lang, code)) = fence(content) {
+        assert!(lang.chars().all(is_fence_char));
+        assert!(!code.is_empty());
+    }
+});
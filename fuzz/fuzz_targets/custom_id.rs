@@ -0,0 +1,33 @@
+// A custom_id can be anything at all with a modified client, so none of these can panic on any of them
+#![no_main]
+
+use custom_highlight::custom_ids;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|custom_id: &str| {
+    let button = custom_ids::button(custom_id);
+    assert!(custom_id.starts_with(button.kind));
+    if button.lang.is_some() {
+        assert!(button.message.is_none());
+    }
+    if let Some((command, _)) = custom_ids::block(custom_id) {
+        assert!(custom_id.contains(command));
+    }
+    if let Some((command, _)) = custom_ids::lang(custom_id) {
+        assert!(custom_id.contains(command));
+    }
+    if let Some((mode, _, _)) = custom_ids::expand(custom_id) {
+        assert!(custom_id.contains(mode));
+    }
+    let _ = custom_ids::page(custom_id);
+    if let Some(command) = custom_ids::relang(button.kind) {
+        assert!(custom_id.contains(command));
+    }
+    if let Some(command) = custom_ids::help(button.kind) {
+        assert!(custom_id.contains(command));
+    }
+    if let Some((_, command, lang)) = custom_ids::modal(custom_id) {
+        assert!(custom_id.contains(command));
+        assert!(custom_id.ends_with(lang));
+    }
+});
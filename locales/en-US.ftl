@@ -0,0 +1,423 @@
+# What the bot says, in US English. This one is built into the bot, and anything a translation doesn't have
+# falls back to it. To translate the bot, copy this to locales/<locale>.ftl (like locales/de.ftl or
+# locales/pt-BR.ftl, named like the locales discord uses) and translate the text after the = signs.
+# Anything inside { } is filled in by the bot, so leave those as they are.
+
+## /help
+
+help =
+    Write a command on the line right above a codeblock, and the bot replies with what it asked for:
+    - ``+highlight`` colors it with ANSI, so it can be copied around
+    - ``+render`` draws it as an image
+    - ``+parse`` (or ``+pparse`` without colors) shows the syntax tree
+    - ``+errors`` lists the syntax errors, and ``+stats`` says how big the tree is
+    Options go after the command, like ``+render theme=light lines=on``.
+
+    You don't have to write anything though: right click a message, and under Apps there's { $highlight }, { $render }, { $pretty_parse }, { $plain_parse } and { $errors }. ``/highlight`` does it for code you haven't posted yet.
+
+    Under the replies, 📋 highlights a render, 🙈 hides it behind a spoiler, 🔤 picks a different language and ◀ ▶ go through long output. ``/languages`` lists every language the bot understands.
+help-example = Here's some { $language }, try it:
+help-example-gone = That example is gone, try /help again
+help-try = Try { $command }
+
+## /optout
+
+optout-on = I won't reply to your codeblocks anymore unless you ask, use /optout again to undo that
+optout-off = I'll reply to your codeblocks without being asked again
+
+## /server
+
+server-line-numbers-on = Renders in this server now have line numbers
+server-line-numbers-off = Renders in this server no longer have line numbers
+server-threads-on = Highlights in this server that take more than one page now go in a thread
+server-threads-off = Highlights in this server no longer go in a thread
+server-webhooks-on = Highlights in this server are now posted as whoever wrote the code (the bot needs permission to manage webhooks for that)
+server-webhooks-off = Highlights in this server are posted by the bot again
+server-reactions-on = Reacting to a codeblock with 🖍, 🖼 or 🔣 now highlights, renders or parses it
+server-reactions-off = Reactions are just reactions again
+server-prompt-timeout = My prompts in this server now delete themselves after { $minutes } minutes if nobody uses them
+server-prompt-timeout-never = My prompts in this server now stay until someone deletes them
+server-only = This only works in a server
+no-such-mode = There's no { $mode } mode
+server-theme = Renders in this server now use the { $theme } theme, unless someone picked their own with /preferences theme
+server-theme-reset = Renders in this server are back to the { $theme } theme
+server-font = Renders in this server now use { $file }
+server-font-reset = Renders in this server are back to the normal font
+server-font-too-big = That font is way too big, it can be at most { $max } bytes
+tone-no-message = A custom tone needs a template with {"{"}message{"}"} in it
+tone-owo = Error messages here are owo again
+tone-plain = Error messages here are plain now
+tone-custom = Error messages here use your template now
+autorespond-on = I'll reply to codeblocks here without being asked again
+autorespond-off = I won't reply to codeblocks here unless someone asks
+autorespond-allowlist-empty = I'll only reply to codeblocks without being asked in the channels on the allowlist, but there aren't any yet
+autorespond-allowlist = I'll only reply to codeblocks without being asked in { $channels }
+channels-everywhere = I can post in every channel here again
+channels-allowlist-empty = I'll only post in the channels on the list, but there aren't any yet, so only whoever asks will see anything
+channels-allowlist = I'll only post in { $channels }
+channels-blocklist-empty = I won't post in the channels on the list, but there aren't any yet
+channels-blocklist = I won't post in { $channels }, there i'll only answer whoever asks
+no-such-command = There's no { $command } command
+permissions-everyone = Everyone can use { $command } here
+permissions-roles = Only { $roles } can use { $command } here now
+
+## Errors that come up all over
+
+unknown-command = Unknown command `{ $name }`
+unknown-subcommand = Unknown subcommand `{ $name }`
+unknown-menu = Unknown menu `{ $id }`
+pick-a-subcommand = You need to pick a subcommand
+message-gone = That message isn't there anymore
+codeblock-gone = That codeblock isn't there anymore
+not-a-codeblock = That's not a codeblock. Am i a joke to you?
+unknown-language = I don't understand { $language }, sorry!
+unknown-language-anymore = I don't understand { $language } anymore, sorry!
+too-long = That took way too long, so i gave up on it
+interrupted = The bot is restarting, so this didn't finish. Try again in a minute
+panicked = Something went wrong on my end, sorry! Try again in a bit
+error-tree-sitter = internal error from tree-sitter (not a syntax error)
+error-no-grammar = This language doesn't have parsing support
+error-node-gone = That part of the tree isn't there anymore, was the code edited?
+error-serialize = The { $what } failed to serialize
+error-encode = The { $what } failed to encode
+error-out-of-range = There aren't that many lines
+error-join = The worker running it failed to join
+discord-channel = The channel couldn't be found
+discord-answer-menu = The menu couldn't be answered
+discord-send-output = The output couldn't be sent
+discord-send-files = The files couldn't be sent
+discord-send-tree = The syntax tree couldn't be sent
+discord-send-render = I couldn't send the render
+discord-edit-render = I couldn't edit the render
+discord-download-render = I couldn't download the render
+
+## Buttons
+
+button-not-yours-change = You didn't send the original message, so you can't change this.
+button-not-yours-delete = You didn't send the original message, so you can't delete this.
+
+## Picking a codeblock, see blocks.rs
+
+blocks-question = There's more than one codeblock in there, which one do you mean?
+blocks-placeholder = Pick a codeblock
+blocks-all = All of them
+blocks-label = Block { $number }: { $language }, { $lines ->
+        [one] { $lines } line
+       *[other] { $lines } lines
+    }
+blocks-no-language = no language
+blocks-none-understood = I don't understand any of those, sorry!
+
+## Picking a language, see picker.rs
+
+picker-unknown = I know that's a codeblock and all, but like, i don't understand { $language }. Which language is it?
+picker-question = Which language is it actually?
+picker-placeholder = { $first } to { $last }
+
+## Expanding a syntax tree, see expand.rs
+
+expand-unknown-node = I don't know what node that is
+expand-unknown-language = I don't understand { $language } anymore, did someone edit it?
+
+## Diff Codeblocks, see diff.rs
+
+diff-unknown-language = I can diff that, but like, i don't understand { $language }, so i can't highlight it
+diff-rendering = Rendering a diff...
+diff-description = A diff of { $language } code
+diff-reply-gone = The message it replies to isn't there anymore
+diff-how = Reply to the old code with the new code, and then use this on the reply
+diff-reply-not-a-codeblock = The message it replies to isn't a codeblock, so what am i supposed to diff it with?
+diff-too-big = { $file } is way too big to diff
+diff-download = I couldn't download { $file }
+diff-not-text = { $file } isn't text, i can't diff that
+
+## /highlight and /scratchpad, see modal.rs
+
+modal-title = Some { $language } code
+modal-code = Code
+modal-scratchpad = Scratchpad
+modal-language = Language (leave it empty for plain text)
+modal-no-code = There's no code in there
+
+## Pages, see pages.rs
+
+pages-forgotten = I don't remember the other pages anymore, you'll have to ask again
+
+## +query, see query.rs
+
+query-unknown-language = I don't understand { $language }, so i can't query it
+query-broken = That query doesn't work, at { $row }:{ $column } ({ $kind }): { $message }
+query-no-matches = That query didn't match anything
+query-more-matches = ...and { $count } more matches
+
+## Render queue, see queue.rs
+
+queue-already = You've already queued up a rendering task
+queue-position = You're #{ $position } in the queue
+
+## Cooldowns, see cooldown.rs
+
+cooldown = You're doing that too fast, try again in { $seconds }s
+
+## Who can use what, see /server permissions
+
+not-allowed = Sorry, only { $roles } can use { $command } here
+
+## Sending ANSI, see send_ansi
+
+thread-name = { $author }'s code
+ansi-too-big = That's way too big to send, even as a file
+ansi-no-files = That's too long to flip through, and I can't attach files here to send it as one
+ansi-as-file = That's too long to flip through, so here it is as a file
+line-too-long = Line is too long
+
+## Bare codeblocks, see detect.rs
+
+detected = That codeblock doesn't say what language it is, but it looks like { $language }
+detected-highlight = Highlight as ANSI
+detected-render = Render
+detected-delete = Delete
+
+## Renders from the context menu
+
+rendering-huge = Rendering... (this could take a while, especially if you're trying to break it intentionally)
+rendering-big = Rendering... (this could take a while, especially if the code is really big)
+rendering = Rendering...
+
+## Options, like +render theme=light
+
+option-on-off = { $option } can only be on or off
+option-needs-value = { $word } needs to look like option=value
+option-theme = There's no { $theme } theme, try one of { $themes }
+option-depth = depth has to be a number, not { $value }
+option-tabs = tabs has to be a number from 1 to { $max }
+option-size = size has to be a number from { $min } to { $max }
+option-format = format can only be png, indexed, webp or auto, not { $value }
+option-hl = hl needs to look like hl=3,7-9, not hl={ $value }
+option-wrap = wrap has to be off or a width in pixels, at least { $min }
+option-unknown = What's { $option }? I don't have an option called that
+option-range = { $range } needs to look like 10..40
+option-range-empty = { $range } doesn't have any lines in it
+option-not-for = { $option } doesn't do anything for { $command }
+
+## Buttons and menus under replies
+
+expand-placeholder = Expand a node
+expand-option = { $kind } (line { $line })
+export-json = Export as JSON
+wrong-language = Wrong language?
+
+## Exports, like +parse json
+
+upload-tree-too-big = That syntax tree is way too big to upload
+upload-highlights-too-big = That's way too many highlights to upload
+upload-html-too-big = That code is way too big to upload as HTML
+graph-failed = I couldn't draw the graph, but here's the DOT
+
+## Renders, see render.rs
+
+font-not-a-font = { $file } isn't a font i can use, it has to be a TTF or OTF
+font-not-saved = The font couldn't be saved: { $why }
+render-description = { $language } code
+render-blocks-description = { $count } codeblocks, one after the other
+render-alt-text = { $what }, { $lines ->
+        [one] { $lines } line
+       *[other] { $lines } lines
+    }, starting with:{" "}
+render-no-files = I can't attach files here, so there's nowhere to put the render. Someone can give me the Attach Files permission, or you can use the context menu to see it just for you
+render-part = { $description } (part { $part } of { $parts })
+render-shrunk = This was too big for discord, so the text is { $size } pixels tall instead of { $requested }
+render-highlight = Highlight as ANSI
+render-spoiler = Toggle spoiler
+render-nothing-to-spoil = There's no image here to spoil
+
+## /install, see install.rs
+
+install-no-bot-dir = The bot's own directory couldn't be found: { $why }
+install-no-sandbox = { $step } needs prlimit and bwrap (bubblewrap) to build grammars in a sandbox, and they aren't installed
+install-step-start = { $step } couldn't start: { $why }
+install-step-timeout = { $step } took longer than { $seconds }s
+install-step-failed = { $step } failed:
+    { $output }
+install-step-cloning = Cloning
+install-step-compiling = Compiling
+install-no-grammar-json = There's no src/grammar.json, is that a tree-sitter grammar?
+install-broken-grammar-json = src/grammar.json is broken: { $why }
+install-weird-name = { $name } is a weird name for a grammar
+install-cpp-scanner = Grammars with a C++ scanner aren't supported, only C
+install-no-compiler = There's no C compiler: { $why }
+install-unreadable = The compiled grammar couldn't be read: { $why }
+install-unrenamable = The compiled grammar couldn't be renamed: { $why }
+install-unloadable = The compiled grammar couldn't be loaded: { $why }
+install-no-symbol = The compiled grammar doesn't have a { $symbol } function
+install-abi = The grammar has ABI version { $version }, but i only support { $min } to { $max }
+install-no-highlights = There's no queries/highlights.scm, so there's nothing to highlight with
+install-bad-name = Language names can only have letters, numbers, - and _
+install-built-in = { $name } is already a built-in language
+install-https-only = Only https:// git URLs are allowed
+install-not-removed = The old grammar couldn't be removed: { $why }
+install-not-created = The grammar directory couldn't be created: { $why }
+install-not-found = The grammar directory couldn't be found: { $why }
+install-join = The loading task failed to join: { $why }
+install-not-installed = There's no { $name } grammar installed in this server
+
+## +stats, see stats.rs
+
+stats-nodes = nodes
+stats-nodes-value = { $nodes } ({ $named } named)
+stats-depth = max depth
+stats-errors = errors
+stats-errors-value = ({ $errors } ERROR, { $missing } MISSING)
+stats-parse-time = parse time
+stats-kinds = most common kinds:
+stats-more-kinds = ...and { $count } other kinds
+
+## /about, see usage.rs
+
+about-nothing-yet = nothing yet
+about =
+    ```
+    version     { $version }
+    uptime      { $days }d { $hours }h { $minutes }m
+    languages   { $languages }
+    renders     { $renders } ({ $ever } ever)
+    cache hits  { $hit_rate }
+    queue       { $running } rendering, { $waiting } waiting
+    shards      { $connected } of { $shards } connected
+    ```
+
+## /languages
+
+languages-header = **Languages i know:**
+languages-aka = (aka `{ $aliases }`)
+languages-more-examples = `/languages <language>` has the rest of the examples
+languages-aliases = Aliases: `{ $aliases }`
+languages-supports = Supports: { $commands }
+languages-auto-respond = Codeblocks in this language get a response automatically
+languages-when-asked = Codeblocks in this language only get a response when asked
+no-such-language = I don't know any language called { $language }, sorry!
+
+## /query
+
+query-server-only = Queries can only be changed in a server
+query-no-such-language = I don't know any language called { $language } that has a highlight query
+query-attach = You need to attach the query as a file
+query-too-big = That query is way too big, it can be at most { $max } bytes
+query-not-utf8 = That query isn't valid UTF-8
+query-set = { $language } is now highlighted with `{ $file }` in this server
+query-not-compiling = That query doesn't compile, so nothing was changed:
+query-reset = { $language } is back to its built-in highlight query
+download-failed = I couldn't download that attachment
+
+## /theme and /reload-languages
+
+theme-owner-only = Only the owner of the bot can change its colors
+theme-bad-color = { $color } isn't a color i know, try a name like PINK or a hex code like #ff00ff or #ff00ff bold
+theme-set = { $capture } in { $language } is now { $color }. This lasts until the bot restarts, put it in languages.toml to keep it.
+reload-owner-only = Only the owner of the bot can reload the languages
+reload-done = Reloaded { $count } languages. Colors changed with /theme set-capture are reset to what languages.toml says.
+reload-broken = languages.toml is broken, so i kept the old languages:
+reload-join = The reloading task failed to join
+theme-no-capture = { $language } doesn't have a capture called { $capture }
+
+## /about grammars and /grammar
+
+about-abi-versions = tree-sitter supports ABI versions { $min } to { $max }
+grammar-owner-only = Only the owner of the bot can install grammars
+grammar-server-only = Grammars can only be installed in a server
+grammar-installed = Installed { $language } in this server
+grammar-failed = Installing { $language } failed:
+grammar-uninstalled = Uninstalled { $language } from this server
+
+## /node-at
+
+node-at-start = Lines and columns start at 1
+node-at-not-a-link = That's not a message link
+node-at-not-allowed = I can't show you that message
+node-at-not-found = I can't find that message
+node-at-lines = There's only { $count } lines in there
+node-at-columns = Line { $line } is only { $count } characters long
+node-at-header = **The node at { $line }:{ $col } in { $language }:**
+node-at-text = That's `{ $text }`
+
+## /preferences
+
+preferences-size = The text in your renders is now { $size } pixels tall
+preferences-size-reset = The text in your renders is back to { $size } pixels tall
+preferences-public = The context menu commands now reply publicly
+preferences-private = The context menu commands now reply just to you
+preferences-theme = Your renders now use the { $theme } theme
+preferences-theme-server = Your renders are back to the theme each server picked, which is { $theme } in this one
+preferences-theme-default = Your renders are back to the theme each server picked, or the { $theme } theme if it didn't pick one
+
+## Slash command descriptions, the ones discord shows while typing them
+
+slash-highlight = Highlight some code without posting it first, only you will see it
+slash-highlight-language = The language the code is in
+slash-highlight-render = Render it as an image instead of ANSI
+slash-scratchpad = Paste some code and post it highlighted, without posting the code first
+slash-scratchpad-render = Render it as an image instead of ANSI
+slash-optout = Stop the bot from replying to your codeblocks unless you ask, or start again
+slash-languages = List the languages i know, or show details about one of them
+slash-languages-language = The language to show details about
+slash-query = Change how a language is highlighted in this server
+slash-query-set = Replace the highlight query of a language with your own
+slash-query-set-language = The language to change
+slash-query-set-query = The new highlights.scm
+slash-query-reset = Go back to the built-in highlight query of a language
+slash-query-reset-language = The language to reset
+slash-help = How to use the bot, with an example to try it on
+slash-help-language = The language of the example, see /languages
+slash-about = Information about the bot
+slash-about-grammars = Show the version and size of every grammar
+slash-about-bot = Show the version, how long it's been up, and how many renders it's done
+slash-reload-languages = Re-read languages.toml without restarting (bot owner only)
+slash-grammar = Install a tree-sitter grammar in this server (bot owner only)
+slash-grammar-install = Clone, build and register a grammar from a git repository
+slash-grammar-install-language = The name codeblocks will use for it
+slash-grammar-install-url = The https:// URL of the git repository
+slash-grammar-uninstall = Remove a grammar installed in this server
+slash-grammar-uninstall-language = The language to remove
+slash-theme = Change the colors the bot uses (bot owner only)
+slash-theme-set-capture = Change the color of one capture in a language, until the bot restarts
+slash-theme-set-capture-language = The language to change
+slash-theme-set-capture-capture = The capture name from the highlight query, like keyword
+slash-theme-set-capture-color = A color name like PINK, or #rrggbb with bold, italic or underline after it
+slash-node-at = Show which syntax node is at a position in a codeblock, and everything it's inside of
+slash-node-at-message = A link to the message with the codeblock (or its ID, if it's in this channel)
+slash-node-at-line = The line in the codeblock, starting at 1
+slash-node-at-col = The column in that line, starting at 1
+slash-preferences = Change how the bot responds to you
+slash-preferences-theme = Pick the theme your renders use, unless you write theme= yourself
+slash-preferences-theme-theme = The theme to use
+slash-preferences-size = Pick how big the text in your renders is, unless you write size= yourself
+slash-preferences-size-size = The text size in pixels, leave it out to go back to the default
+slash-preferences-visibility = Pick who sees what the context menu commands reply with
+slash-preferences-visibility-visibility = Public replies to the codeblock, or just for you
+slash-server = Change how the bot responds in this server
+slash-server-theme = Pick the theme renders use here, for anyone who didn't pick their own
+slash-server-theme-theme = The theme to use
+slash-server-line-numbers = Whether renders here have line numbers, unless someone writes lines= themselves
+slash-server-line-numbers-enabled = Whether to show line numbers
+slash-server-threads = Whether long highlights here go in a thread, unless someone writes thread= themselves
+slash-server-threads-enabled = Whether to make threads
+slash-server-webhooks = Whether highlights here are posted with the name and avatar of whoever wrote the code
+slash-server-webhooks-enabled = Whether to post through a webhook
+slash-server-reactions = Whether reacting to a codeblock with 🖍, 🖼 or 🔣 highlights, renders or parses it
+slash-server-reactions-enabled = Whether reactions run commands
+slash-server-prompt-timeout = How long the bot's prompts stay before they delete themselves
+slash-server-prompt-timeout-minutes = 0 keeps them forever
+slash-server-tone = How the bot's error messages sound here
+slash-server-tone-mode = owo, plain, or your own template
+slash-server-tone-template = For custom, {"{"}message{"}"} is replaced with the message
+slash-server-font = Use your own font for renders here, or go back to the normal one
+slash-server-font-font = A TTF or OTF file, leave it out to go back to the normal font
+slash-server-autorespond = Whether the bot replies to codeblocks here without being asked
+slash-server-autorespond-mode = Everywhere, nowhere, or only in the channels on the allowlist
+slash-server-autorespond-channel = A channel to add to the allowlist, or remove if it's already on it
+slash-server-channels = Where the bot can post publicly at all, anywhere else it only answers whoever asked
+slash-server-channels-mode = Everywhere, only the listed channels, or anywhere but the listed channels
+slash-server-channels-channel = A channel to add to the list, or remove if it's already on it
+slash-server-permissions = Which roles can use a command here, leave out the role to let everyone use it again
+slash-server-permissions-command = The command to limit
+slash-server-permissions-role = A role to let use it, or stop letting use it if it already can
@@ -0,0 +1,95 @@
+use super::*;
+
+// Code that was uploaded as a file instead of pasted in a codeblock. The file extension is looked up
+// like a codeblock's language, so a language just needs its extension in its aliases for this to work.
+// Big files are skipped, since nobody wants to read a render of those anyways
+const MAX_ATTACHMENT_SIZE: u64 = 64 * 1024;
+
+// (lang, code) of the first attachment in a language the bot knows
+pub async fn code(message: &Message, guild_id: Option<GuildId>) -> Option<(&str, String)> {
+    for attachment in &message.attachments {
+        if attachment.size > MAX_ATTACHMENT_SIZE {
+            continue;
+        }
+        let lang = match attachment.filename.rsplit_once('.') {
+            Some((_, extension)) if get_guild_language(guild_id, extension).is_some() => extension,
+            _ => continue,
+        };
+        let bytes = match attachment.download().await {
+            Ok(bytes) => bytes,
+            Err(why) => {
+                error!("{why}");
+                continue;
+            }
+        };
+        // not text, so it's not code either, whatever the extension says
+        let code = match String::from_utf8(bytes) {
+            Ok(code) => code,
+            Err(_) => continue,
+        };
+        let code = code.replace("\r\n", "\n");
+        let code = code.trim_matches('\n');
+        if !code.is_empty() {
+            return Some((lang, code.to_owned()));
+        }
+    }
+    None
+}
+
+// The same as a codeblock in message(): the message can be a command for the file,
+// and languages that get automatically rendered get that for files too
+pub async fn respond(ctx: &Context, message: &Message, content: &str) {
+    let (lang, code) = match code(message, message.guild_id).await {
+        Some(found) => found,
+        None => return,
+    };
+    // it was only just looked up, but /reload-languages could've happened in between
+    let config = match get_guild_language(message.guild_id, lang) {
+        Some(config) => config,
+        None => return,
+    };
+    let channel = message.channel(&ctx).await;
+    let channel = match channel.or_log("get the channel") {
+        Some(channel) => channel,
+        None => return,
+    };
+    let roles = message.member.as_ref().map_or(&[][..], |member| &member.roles);
+    let command = parse_command(content.trim()).map(|command| {
+        command.and_then(|(command, options)| {
+            may_run(message.guild_id, message.author.id, roles, command).map(|()| (command, options))
+        })
+    });
+    let (command, options, add_components) = match command {
+        Some(Ok((command, options))) => (command, options, false),
+        Some(Err(why)) => {
+            message
+                .reply(&ctx, why)
+                .await
+                .or_log("reply with the error");
+            return;
+        }
+        None if config.auto_respond && unprompted(ctx, message) => {
+            (Command::Render, Options::default(), true)
+        }
+        None => return,
+    };
+    let result = run_command(
+        ctx,
+        &channel,
+        command,
+        options,
+        config,
+        &code,
+        ReplyMethod::PublicReference(message),
+        message.author.id,
+        &message.author,
+        add_components,
+    )
+    .await;
+    if let Err(error) = result {
+        message
+            .reply(&ctx, error.report())
+            .await
+            .or_log("reply with the error");
+    }
+}
@@ -0,0 +1,233 @@
+use super::*;
+
+// codeblock() gives up on messages with more than one codeblock, because it can't know which one is meant.
+// The context menu commands ask instead: they reply with a menu of every codeblock (and all of them at once),
+// and picking from it runs the command on those. The custom_id is block-<command>-<message id>,
+// and the value is which codeblock it is, counting from 0, or "all".
+// All of them highlighted or rendered is one reply with every codeblock in it, see run_all
+
+// Sends the menu if there's more than one codeblock. false means there's nothing to pick, so go ahead
+pub async fn offer(
+    ctx: &Context,
+    interaction: &Interaction,
+    command: Command,
+    message: &Message,
+) -> bool {
+    let blocks = codeblocks(&message.content);
+    if blocks.len() < 2 {
+        return false;
+    }
+    let name = command.id();
+    // the last spot is for all of them
+    let labels = blocks
+        .iter()
+        .take(MAX_MENU_OPTIONS - 1)
+        .enumerate()
+        .map(|(i, (lang, code))| {
+            let lang = if lang.is_empty() {
+                tr!("blocks-no-language")
+            } else {
+                lang.to_string()
+            };
+            let lines = code.lines().count();
+            tr!(
+                "blocks-label",
+                number = i + 1,
+                language = lang,
+                lines = lines
+            )
+        })
+        .collect::<Vec<_>>();
+    create_interaction_response(ctx, interaction, |response| {
+        response.interaction_response_data(|msg| {
+            msg.ephemeral(true)
+                .content(tr!("blocks-question"))
+                .components(|c| {
+                    c.create_action_row(|row| {
+                        row.create_select_menu(|menu| {
+                            menu.custom_id(custom_ids::block_id(name, message.id.0))
+                                .placeholder(tr!("blocks-placeholder"))
+                                .options(|options| {
+                                    for (i, label) in labels.into_iter().enumerate() {
+                                        options.create_option(|option| {
+                                            option.label(label).value(i)
+                                        });
+                                    }
+                                    options.create_option(|option| {
+                                        option.label(tr!("blocks-all")).value("all")
+                                    })
+                                })
+                        })
+                    })
+                })
+        })
+    })
+    .await
+    .or_log("offer the codeblocks");
+    true
+}
+
+pub async fn handle(
+    ctx: &Context,
+    original: &Interaction,
+    interaction: &MessageComponentInteraction,
+) {
+    let (command, source) = match parse_custom_id(&interaction.data.custom_id) {
+        Some(parsed) => parsed,
+        None => {
+            interaction
+                .create_interaction_response(&ctx, |response| {
+                    response.interaction_response_data(|msg| {
+                        msg.ephemeral(true).content(owo!(
+                            "unknown-menu",
+                            id = interaction.data.custom_id.as_str()
+                        ))
+                    })
+                })
+                .await
+                .or_log("say the menu is unknown");
+            return;
+        }
+    };
+    // run defers it first thing, so anything that goes wrong is a followup
+    if let Err(why) = run(ctx, original, interaction, command, source).await {
+        create_followup_message(ctx, original, |msg: &mut CreateInteractionResponseFollowup| {
+            msg.ephemeral(true).content(why.report())
+        })
+        .await
+        .or_log("reply with the error");
+    }
+}
+
+fn parse_custom_id(custom_id: &str) -> Option<(Command, MessageId)> {
+    let (name, source) = custom_ids::block(custom_id)?;
+    Some((commands::by_id(name)?, source.into()))
+}
+
+async fn run(
+    ctx: &Context,
+    original: &Interaction,
+    interaction: &MessageComponentInteraction,
+    command: Command,
+    source: MessageId,
+) -> Result<(), CommandError> {
+    // the output is a reply or a followup of its own, so the menu itself doesn't change
+    interaction
+        .defer(&ctx)
+        .await
+        .map_err(CommandError::discord("discord-answer-menu"))?;
+    let channel = interaction
+        .channel_id
+        .to_channel(&ctx)
+        .await
+        .map_err(CommandError::discord("discord-channel"))?;
+    let message = get_ref(ctx, &channel, source)
+        .await
+        .ok_or_else(|| owo!("message-gone"))?;
+    let blocks = codeblocks(&message.content);
+    let all = interaction.data.values.first().map(String::as_str) == Some("all");
+    let picked = match interaction.data.values.first().map(String::as_str) {
+        Some("all") => blocks.iter().collect::<Vec<_>>(),
+        Some(i) => i
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| blocks.get(i))
+            .into_iter()
+            .collect(),
+        None => Vec::new(),
+    };
+    if picked.is_empty() {
+        return Err(owo!("codeblock-gone").into());
+    }
+    let guild_id = interaction.guild_id;
+    // all of them at once is still one pick
+    let roles = interaction.member.as_ref().map_or(&[][..], |member| &member.roles);
+    may_run(guild_id, interaction.user.id, roles, command)?;
+    let public = storage::public_output(interaction.user.id)
+        && storage::posts_in(guild_id, listed_channel(ctx, guild_id, interaction.channel_id));
+    let reply_to = if public {
+        ReplyMethod::PublicReference(&message)
+    } else {
+        ReplyMethod::EphemeralFollowup(original)
+    };
+    if all && matches!(command, Command::Highlight | Command::Render) {
+        return run_all(ctx, &channel, command, &picked, reply_to, interaction.user.id).await;
+    }
+    for &(lang, code) in picked {
+        let config = get_guild_language(guild_id, lang)
+            .ok_or_else(|| owo!("unknown-language", language = lang))?;
+        run_command(
+            ctx,
+            &channel,
+            command,
+            Options::default(),
+            config,
+            code,
+            reply_to,
+            interaction.user.id,
+            &message.author,
+            false,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+// Every codeblock it understands, in the order they're in, with what language each one is.
+// The ones it doesn't understand are just left out
+async fn run_all(
+    ctx: &Context,
+    channel: &Channel,
+    command: Command,
+    blocks: &[&(&str, &str)],
+    reply_to: ReplyMethod<'_>,
+    invoker: UserId,
+) -> Result<(), CommandError> {
+    let guild_id = match channel {
+        Channel::Guild(channel) => Some(channel.guild_id),
+        _ => None,
+    };
+    let mut resolved = Vec::new();
+    for &&(lang, code) in blocks {
+        if let Some(config) = get_guild_language(guild_id, lang) {
+            resolved.push((languages::for_guild(guild_id, config), code.to_owned()));
+        }
+    }
+    let blocks = resolved;
+    if blocks.is_empty() {
+        return Err(owo!("blocks-none-understood").into());
+    }
+    let theme = theme::preferred(invoker, guild_id);
+    if command == Command::Render {
+        let options = RenderOptions {
+            theme,
+            line_numbers: storage::guild_setting(guild_id, |guild| guild.line_numbers)
+                .unwrap_or(false),
+            title: None,
+            wrap: Some(render::DEFAULT_WRAP_WIDTH),
+            tab_width: DEFAULT_TAB_WIDTH,
+            font: render::guild_font(guild_id),
+            size: render::preferred_size(invoker),
+            encoding: render::Encoding::default(),
+            range: None,
+            emphasis: Vec::new(),
+            transparent: false,
+            spoiler: false,
+            animate: false,
+        };
+        render::render_blocks_command(ctx, channel, blocks, options, reply_to, invoker).await?;
+    } else {
+        let (label, reset) = (theme.get(GRAY).ansi, theme.get(RESET).ansi);
+        let ansi = workers::run(Task::Highlight, move || {
+            let mut highlighted = Vec::new();
+            for (config, code) in &blocks {
+                let formatted = syntax_highlight(config, code, theme, DEFAULT_TAB_WIDTH)?;
+                highlighted.push(format!("{label}{}{reset}\n{formatted}", config.name));
+            }
+            Ok::<_, HighlightError>(highlighted.join("\n\n"))
+        })
+        .await?;
+        send_ansi(ctx, channel, &ansi, &Options::default(), reply_to, None).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use super::*;
+
+// Every button that isn't a page or a help example ends up here. What it does comes from its custom_id,
+// see custom_ids::button, and the message it's for is the one in the custom_id or the one the reply is to
+
+pub async fn handle(
+    ctx: &Context,
+    original_interaction: &Interaction,
+    interaction: &MessageComponentInteraction,
+) {
+    let ref message = interaction.message;
+    let channel = message.channel(ctx).await;
+    let channel = match channel.or_log("get the channel") {
+        Some(channel) => channel,
+        None => return,
+    };
+    // A lot of this stuff is legacy, because the bot used to work like this. I think only "highlight" is actually supposed to ever come through here now?
+    // but might as well keep the old buttons half-functional still. because why not.
+    let button = custom_ids::button(&interaction.data.custom_id);
+    let (interact_id, lang_override, ephemeralish) =
+        (button.kind, button.lang, button.ephemeralish);
+    // without an id in the button, it's whichever message it's a reply to
+    let reference_id = match button.message {
+        Some(reference_id) => Some(reference_id.into()),
+        None => message
+            .message_reference
+            .as_ref()
+            .and_then(|reference| reference.message_id),
+    };
+    async fn delete(ctx: &Context, message: &Message, ephemeralish: bool) {
+        if ephemeralish {
+            message.delete(ctx).await.or_log("delete the prompt");
+        } else {
+            message
+                .clone()
+                .edit(ctx, |msg| msg.set_components(Default::default()))
+                .await
+                .or_log("remove the buttons");
+        }
+    }
+
+    let referenced = match reference_id {
+        Some(reference_id) => get_ref(ctx, &channel, reference_id).await,
+        None => None,
+    };
+    let referenced = match referenced {
+        Some(referenced) => referenced,
+        None => {
+            // where is the replied message?? just delete it already we don't care
+            interaction.defer(ctx).await.or_log("defer the button");
+            return delete(ctx, message, ephemeralish).await;
+        }
+    };
+
+    fn can_delete(
+        ctx: &Context,
+        interaction: &MessageComponentInteraction,
+        channel: &Channel,
+        referenced: &Message,
+    ) -> bool {
+        let channel = match channel {
+            Channel::Guild(c) => c,
+            _ => {
+                return true;
+            }
+        };
+        if interaction.user == referenced.author {
+            // delete if user is author, since they might want the bot to fuck off
+            true
+        } else if channel
+            .permissions_for_user(ctx, interaction.user.id)
+            .map(|p| p.contains(Permissions::MANAGE_MESSAGES))
+            .unwrap_or(false)
+        {
+            // can delete messages anyways, let them do it through interaction
+            true
+        } else {
+            false
+        }
+    }
+
+    // the language was wrong, so ask again which one it is, see picker.rs
+    if let Some(name) = custom_ids::relang(interact_id) {
+        picker::retry(ctx, original_interaction, name, &referenced).await;
+        return;
+    }
+
+    let command = match interact_id {
+        "spoiler" => {
+            // same people who can delete it, so nobody else can unspoil a solution
+            if can_delete(ctx, interaction, &channel, &referenced) {
+                interaction.defer(ctx).await.or_log("defer the button");
+                if let Err(why) = render::toggle_spoiler(ctx, message).await {
+                    create_followup_message(
+                        ctx,
+                        original_interaction,
+                        |msg: &mut CreateInteractionResponseFollowup| {
+                            msg.ephemeral(true).content(why.report())
+                        },
+                    )
+                    .await
+                    .or_log("reply with the error");
+                }
+            } else {
+                interaction
+                    .create_interaction_response(ctx, |response| {
+                        response.interaction_response_data(|msg| {
+                            msg.ephemeral(true).content(owo!("button-not-yours-change"))
+                        })
+                    })
+                    .await
+                    .or_log("say they can't do that");
+            }
+            return;
+        }
+        "delete" => {
+            if can_delete(ctx, interaction, &channel, &referenced) {
+                interaction.defer(ctx).await.or_log("defer the button");
+                delete(ctx, message, ephemeralish).await;
+            } else {
+                interaction
+                    .create_interaction_response(ctx, |response| {
+                        response.interaction_response_data(|msg| {
+                            msg.ephemeral(true).content(owo!("button-not-yours-delete"))
+                        })
+                    })
+                    .await
+                    .or_log("say they can't do that");
+            }
+            return;
+        }
+        kind => match commands::by_button(kind) {
+            Some(command) => command,
+            None => {
+                interaction
+                    .create_interaction_response(ctx, |response| {
+                        response.interaction_response_data(|msg| {
+                            msg.ephemeral(true)
+                                .content(owo!("unknown-command", name = kind))
+                        })
+                    })
+                    .await
+                    .or_log("say the command is unknown");
+                return;
+            }
+        },
+    };
+    info!("clicked to execute {command:?}");
+    match run_command_from_interaction(
+        ctx,
+        command,
+        original_interaction,
+        &channel,
+        &referenced,
+        lang_override,
+        true,
+        true,
+    )
+    .await
+    {
+        // command was not acknowledged in this case, so must defer it
+        InteractionCommandResult::NoCodeblock
+        // the message was edited to be the wrong lang, so delete silently here too
+        | InteractionCommandResult::BadLang(_) => {
+            interaction.defer(ctx).await.or_log("defer the button");
+            delete(ctx, message, ephemeralish).await;
+        }
+        InteractionCommandResult::FinishedSuccessfully => (), // do nothing, no new public message was sent, it was ephemeral so leave the button for others
+        InteractionCommandResult::InformedError => (), // do nothing, we already informed the user of the error
+    }
+}
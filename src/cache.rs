@@ -0,0 +1,235 @@
+use std::time::{Duration, Instant};
+
+use super::*;
+use lru::LruCache;
+
+// Where the render cache and the cooldowns live. That's in memory by default, but with REDIS_URL
+// set (like redis://127.0.0.1/) it's in Redis instead, so every process of a bot that's split up
+// into a few of them shares the same ones. Redis being down just means nothing is cached and
+// nobody has a cooldown, until it's back
+pub trait Backend: Send + Sync {
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+    fn put(&self, key: &str, value: Vec<u8>);
+    // Takes a token from every bucket, or from none of them if any of them is empty,
+    // and says how long until they'd all have one (which is zero if they were taken)
+    fn take(&self, buckets: &[Bucket]) -> Duration;
+}
+
+pub struct Bucket {
+    pub key: String,
+    // how many it can be run in a row
+    pub burst: f64,
+    // how long it takes for one token to come back
+    pub every: Duration,
+}
+
+// these can be up to 8MB per image, so not too many
+const MEMORY_ENTRIES: usize = 32;
+// a bucket that gets forgotten is just full again, which is the same as one that hasn't been used in a while
+const MEMORY_BUCKETS: usize = 4096;
+// Redis doesn't limit how many there are, only how long they're kept
+const REDIS_TTL: Duration = Duration::from_secs(60 * 60);
+// These are called right from the async code, so a Redis that doesn't answer at all can't be waited on
+// for as long as the OS would. Connecting, and every read and write, gets this long
+const REDIS_TIMEOUT: Duration = Duration::from_secs(1);
+// and after something went wrong, it's left alone for this long before connecting again
+const REDIS_RETRY: Duration = Duration::from_secs(30);
+const PREFIX: &str = "custom-highlight";
+
+lazy_static! {
+    static ref BACKEND: Box<dyn Backend> = match config::var("REDIS_URL") {
+        Some(url) => Box::new(Redis::new(&url)),
+        None => Box::new(Memory::default()),
+    };
+}
+
+pub fn backend() -> &'static dyn Backend {
+    &**BACKEND
+}
+
+struct Memory {
+    entries: std::sync::Mutex<LruCache<String, Vec<u8>>>,
+    buckets: std::sync::Mutex<LruCache<String, MemoryBucket>>,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Memory {
+            entries: std::sync::Mutex::new(LruCache::new(MEMORY_ENTRIES)),
+            buckets: std::sync::Mutex::new(LruCache::new(MEMORY_BUCKETS)),
+        }
+    }
+}
+
+struct MemoryBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl MemoryBucket {
+    // puts back the tokens that came back since it was last looked at
+    fn refill(&mut self, bucket: &Bucket, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed / bucket.every.as_secs_f64()).min(bucket.burst);
+        self.updated = now;
+    }
+
+    fn wait(&self, bucket: &Bucket) -> Duration {
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            bucket.every.mul_f64(1.0 - self.tokens)
+        }
+    }
+}
+
+impl Backend for Memory {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    fn put(&self, key: &str, value: Vec<u8>) {
+        self.entries.lock().unwrap().put(key.to_owned(), value);
+    }
+
+    fn take(&self, buckets: &[Bucket]) -> Duration {
+        let now = Instant::now();
+        let mut memory = self.buckets.lock().unwrap();
+        let mut wait = Duration::ZERO;
+        for bucket in buckets {
+            if !memory.contains(&bucket.key) {
+                memory.put(
+                    bucket.key.clone(),
+                    MemoryBucket {
+                        tokens: bucket.burst,
+                        updated: now,
+                    },
+                );
+            }
+            let remembered = memory.get_mut(&bucket.key).unwrap();
+            remembered.refill(bucket, now);
+            wait = wait.max(remembered.wait(bucket));
+        }
+        if wait.is_zero() {
+            for bucket in buckets {
+                memory.get_mut(&bucket.key).unwrap().tokens -= 1.0;
+            }
+        }
+        wait
+    }
+}
+
+// The same as Memory::take, but in one go in Redis, so two processes can't both take the last token.
+// KEYS are the buckets, and ARGV is the burst and how often a token comes back (in ms) for each of them
+const TAKE_SCRIPT: &str = "
+local time = redis.call('TIME')
+local now = time[1] * 1000 + time[2] / 1000
+local tokens = {}
+local wait = 0
+for i, key in ipairs(KEYS) do
+    local burst = tonumber(ARGV[i * 2 - 1])
+    local every = tonumber(ARGV[i * 2])
+    local bucket = redis.call('HMGET', key, 'tokens', 'updated')
+    local updated = tonumber(bucket[2]) or now
+    tokens[i] = math.min((tonumber(bucket[1]) or burst) + (now - updated) / every, burst)
+    if tokens[i] < 1 then
+        wait = math.max(wait, (1 - tokens[i]) * every)
+    end
+end
+local taken = wait == 0 and 1 or 0
+for i, key in ipairs(KEYS) do
+    redis.call('HSET', key, 'tokens', tostring(tokens[i] - taken), 'updated', tostring(now))
+    redis.call('PEXPIRE', key, math.ceil(tonumber(ARGV[i * 2 - 1]) * tonumber(ARGV[i * 2])))
+end
+return math.ceil(wait)
+";
+
+struct Redis {
+    client: redis::Client,
+    // made again the next time it's needed whenever something goes wrong with it
+    connection: std::sync::Mutex<Option<redis::Connection>>,
+    // when that last happened, see REDIS_RETRY
+    failed: std::sync::Mutex<Option<Instant>>,
+    take: redis::Script,
+}
+
+impl Redis {
+    fn new(url: &str) -> Self {
+        Redis {
+            client: redis::Client::open(url).unwrap_or_else(|e| panic!("{url} isn't usable: {e}")),
+            connection: Default::default(),
+            failed: Default::default(),
+            take: redis::Script::new(TAKE_SCRIPT),
+        }
+    }
+
+    fn with_connection<T>(
+        &self,
+        f: impl FnOnce(&mut redis::Connection) -> redis::RedisResult<T>,
+    ) -> Option<T> {
+        let mut connection = self.connection.lock().unwrap();
+        if connection.is_none() {
+            if matches!(*self.failed.lock().unwrap(), Some(failed) if failed.elapsed() < REDIS_RETRY) {
+                return None;
+            }
+            match self.connect() {
+                Ok(new) => *connection = Some(new),
+                Err(e) => {
+                    error!("couldn't connect to redis: {e}");
+                    *self.failed.lock().unwrap() = Some(Instant::now());
+                    return None;
+                }
+            }
+        }
+        match f(connection.as_mut().unwrap()) {
+            Ok(result) => Some(result),
+            Err(e) => {
+                error!("redis: {e}");
+                *connection = None;
+                *self.failed.lock().unwrap() = Some(Instant::now());
+                None
+            }
+        }
+    }
+
+    fn connect(&self) -> redis::RedisResult<redis::Connection> {
+        let connection = self.client.get_connection_with_timeout(REDIS_TIMEOUT)?;
+        connection.set_read_timeout(Some(REDIS_TIMEOUT))?;
+        connection.set_write_timeout(Some(REDIS_TIMEOUT))?;
+        Ok(connection)
+    }
+}
+
+impl Backend for Redis {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.with_connection(|connection| {
+            redis::cmd("GET")
+                .arg(format!("{PREFIX}:{key}"))
+                .query::<Option<Vec<u8>>>(connection)
+        })
+        .flatten()
+    }
+
+    fn put(&self, key: &str, value: Vec<u8>) {
+        self.with_connection(|connection| {
+            redis::cmd("SET")
+                .arg(format!("{PREFIX}:{key}"))
+                .arg(value)
+                .arg("EX")
+                .arg(REDIS_TTL.as_secs())
+                .query::<()>(connection)
+        });
+    }
+
+    fn take(&self, buckets: &[Bucket]) -> Duration {
+        let mut invocation = self.take.prepare_invoke();
+        for bucket in buckets {
+            invocation
+                .key(format!("{PREFIX}:{}", bucket.key))
+                .arg(bucket.burst)
+                .arg(bucket.every.as_millis() as u64);
+        }
+        let wait = self.with_connection(|connection| invocation.invoke::<u64>(connection));
+        Duration::from_millis(wait.unwrap_or(0))
+    }
+}
@@ -0,0 +1,535 @@
+use super::*;
+use serenity::futures::future::BoxFuture;
+
+// Every command, every way there is to ask for it, which options it takes and what runs it.
+// Whatever reads a command from somewhere (parse_command for +prefixes, the context menus, buttons, menus,
+// /help and /server permissions) looks it up in COMMANDS, and run_command runs whatever its Spec says,
+// so a new command is a variant, a Spec here and an executor at the bottom
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Command {
+    Highlight,
+    Render,
+    PrettyParse,
+    PlainParse,
+    // the syntax tree as a JSON file, for people who want to do their own thing with it
+    JsonParse,
+    // what +highlight would color and as which capture, as a JSON file, see highlight_json
+    JsonHighlight,
+    // a standalone HTML page, see html.rs
+    HtmlHighlight,
+    // the syntax tree as a graph, see graph.rs
+    DotParse,
+    // just where the syntax errors are, see explain_errors
+    Errors,
+    // how big and deep the tree is and what's in it, see stats.rs
+    Stats,
+}
+
+pub struct Spec {
+    pub command: Command,
+    // what it's called in custom_ids and /server permissions. these end up in messages people already have,
+    // so they can't change
+    pub id: &'static str,
+    // the first word of a message that runs it, like +highlight
+    pub prefix: Option<&'static str>,
+    // words after the prefix that make it another command, like +parse json. everything else is an option
+    pub exports: &'static [(&'static str, Command)],
+    // the context menu command on messages. these can pick one of many codeblocks too, see blocks.rs
+    pub menu: Option<&'static str>,
+    // whether a button under a reply can run it, the custom_id is the id
+    pub button: bool,
+    // what can be written after it, like theme=light or animate. "range" is the 10..40 kind.
+    // parse_command says so when one of them doesn't do anything for the command
+    pub options: &'static [&'static str],
+    pub run: Executor,
+}
+
+// Everything run_command worked out before running it
+pub struct Invocation<'a> {
+    pub ctx: &'a Context,
+    pub channel: &'a Channel,
+    pub command: Command,
+    pub options: Options,
+    pub config: Arc<LanguageConfig>,
+    pub code: &'a str,
+    pub reply_to: ReplyMethod<'a>,
+    pub invoker: UserId,
+    // whoever wrote the code, which isn't always whoever asked for the command
+    pub author: &'a User,
+    pub add_components: bool,
+    pub guild_id: Option<GuildId>,
+    pub theme: &'static Theme,
+    pub tab_width: usize,
+}
+
+pub type Executor = for<'a> fn(Invocation<'a>) -> BoxFuture<'a, Result<(), CommandError>>;
+
+// an async fn below as an Executor, which can't be async itself
+macro_rules! executor {
+    ($run:ident) => {{
+        fn run(invocation: Invocation<'_>) -> BoxFuture<'_, Result<(), CommandError>> {
+            Box::pin($run(invocation))
+        }
+        run
+    }};
+}
+
+// what +highlight, +errors and +stats send goes through send_ansi, see there
+const ANSI_OPTIONS: &[&str] = &["theme", "tabs", "spoiler", "embed", "thread"];
+
+pub const COMMANDS: &[Spec] = &[
+    Spec {
+        command: Command::Highlight,
+        id: "highlight",
+        prefix: Some("+highlight"),
+        exports: &[
+            ("json", Command::JsonHighlight),
+            ("html", Command::HtmlHighlight),
+        ],
+        menu: Some("Highlight Codeblock"),
+        button: true,
+        options: ANSI_OPTIONS,
+        run: executor!(highlight),
+    },
+    Spec {
+        command: Command::Render,
+        id: "render",
+        prefix: Some("+render"),
+        exports: &[],
+        menu: Some("Render Codeblock"),
+        button: true,
+        options: &[
+            "theme",
+            "lines",
+            "title",
+            "wrap",
+            "tabs",
+            "size",
+            "format",
+            "range",
+            "hl",
+            "transparent",
+            "spoiler",
+            "animate",
+        ],
+        run: executor!(render),
+    },
+    Spec {
+        command: Command::PrettyParse,
+        id: "pretty-parse",
+        prefix: Some("+parse"),
+        exports: &[("json", Command::JsonParse), ("dot", Command::DotParse)],
+        menu: Some("Pretty Parse Syntax"),
+        button: true,
+        options: &["theme", "tabs", "spoiler", "verbose", "depth"],
+        run: executor!(parse),
+    },
+    Spec {
+        command: Command::PlainParse,
+        id: "plain-parse",
+        prefix: Some("+pparse"),
+        exports: &[],
+        menu: Some("Parse Syntax"),
+        button: true,
+        options: &["tabs", "spoiler", "verbose", "depth"],
+        run: executor!(parse),
+    },
+    Spec {
+        command: Command::Errors,
+        id: "errors",
+        prefix: Some("+errors"),
+        exports: &[],
+        menu: Some("Explain Errors"),
+        button: false,
+        options: ANSI_OPTIONS,
+        run: executor!(errors),
+    },
+    Spec {
+        command: Command::Stats,
+        id: "stats",
+        prefix: Some("+stats"),
+        exports: &[],
+        menu: None,
+        button: false,
+        options: ANSI_OPTIONS,
+        run: executor!(stats),
+    },
+    // the button under a syntax tree that sends it as a file instead
+    Spec {
+        command: Command::JsonParse,
+        id: "parse-json",
+        prefix: None,
+        exports: &[],
+        menu: None,
+        button: true,
+        options: &[],
+        run: executor!(json_parse),
+    },
+    Spec {
+        command: Command::JsonHighlight,
+        id: "highlight-json",
+        prefix: None,
+        exports: &[],
+        menu: None,
+        button: false,
+        options: &[],
+        run: executor!(json_highlight),
+    },
+    Spec {
+        command: Command::HtmlHighlight,
+        id: "highlight-html",
+        prefix: None,
+        exports: &[],
+        menu: None,
+        button: false,
+        options: &["theme", "tabs"],
+        run: executor!(html_highlight),
+    },
+    Spec {
+        command: Command::DotParse,
+        id: "parse-dot",
+        prefix: None,
+        exports: &[],
+        menu: None,
+        button: false,
+        options: &[],
+        run: executor!(dot_parse),
+    },
+];
+
+impl Command {
+    pub fn spec(self) -> &'static Spec {
+        COMMANDS
+            .iter()
+            .find(|spec| spec.command == self)
+            .expect("every command is in COMMANDS")
+    }
+
+    pub fn id(self) -> &'static str {
+        self.spec().id
+    }
+
+    // for /help, which names the context menu commands
+    pub fn menu_name(self) -> &'static str {
+        self.spec().menu.unwrap_or_default()
+    }
+}
+
+pub fn by_id(id: &str) -> Option<Command> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.id == id)
+        .map(|spec| spec.command)
+}
+
+pub fn by_prefix(word: &str) -> Option<&'static Spec> {
+    COMMANDS.iter().find(|spec| spec.prefix == Some(word))
+}
+
+pub fn by_menu(name: &str) -> Option<Command> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.menu == Some(name))
+        .map(|spec| spec.command)
+}
+
+pub fn by_button(id: &str) -> Option<Command> {
+    COMMANDS
+        .iter()
+        .find(|spec| spec.button && spec.id == id)
+        .map(|spec| spec.command)
+}
+
+// the context menu commands, these are also the ones /server permissions can limit
+pub fn menus() -> impl Iterator<Item = &'static Spec> {
+    COMMANDS.iter().filter(|spec| spec.menu.is_some())
+}
+
+async fn highlight(run: Invocation<'_>) -> Result<(), CommandError> {
+    let Invocation {
+        ctx,
+        channel,
+        command,
+        options,
+        config,
+        code,
+        reply_to,
+        invoker,
+        add_components,
+        theme,
+        tab_width,
+        ..
+    } = run;
+    // what the workers get, they can't borrow anything
+    let work = (config, code.to_owned());
+    let formatted = workers::run(Task::Highlight, move || {
+        let (config, code) = work;
+        syntax_highlight(&config, &code, theme, tab_width)
+    })
+    .await?;
+    let replies = match webhook::send(ctx, channel, &formatted, &options, reply_to).await {
+        Some(replies) => replies,
+        None => {
+            // the button needs to know which message to highlight again
+            let components = match reply_to {
+                ReplyMethod::PublicReference(referenced) => {
+                    let mut components = CreateComponents::default();
+                    components.create_action_row(|row| {
+                        relang_button(row, command, referenced.id)
+                    });
+                    Some(components)
+                }
+                ReplyMethod::EphemeralFollowup(_) | ReplyMethod::PublicFollowup(_) => None,
+            };
+            send_ansi(ctx, channel, &formatted, &options, reply_to, components).await?
+        }
+    };
+    // editing the codeblock highlights it again
+    if let ReplyMethod::PublicReference(referenced) = reply_to {
+        rerun::track(
+            referenced.id,
+            rerun::Ran {
+                command,
+                options,
+                invoker,
+                add_components,
+                replies,
+            },
+        );
+    }
+    Ok(())
+}
+
+async fn parse(run: Invocation<'_>) -> Result<(), CommandError> {
+    let Invocation {
+        ctx,
+        channel,
+        command,
+        options,
+        config,
+        code,
+        reply_to,
+        invoker,
+        guild_id,
+        theme,
+        ..
+    } = run;
+    // what the workers get, they can't borrow anything
+    let work = (config.clone(), code.to_owned());
+    let plain = command == Command::PlainParse;
+    let parse_options = options.clone();
+    let (tree, (chunks, depth, collapsed)) = workers::run(Task::Parse, move || {
+        let (config, code) = work;
+        let tree = workers::parse(&config, &code, None)?;
+        let chunks = pretty_parse_chunks(
+            &tree,
+            &code,
+            if plain { None } else { Some(theme) },
+            &[],
+            &parse_options,
+        )?;
+        Ok::<_, CommandError>((tree, chunks))
+    })
+    .await?;
+    // the buttons have to say which message to parse again, which is only known when replying to it
+    let components = match reply_to {
+        ReplyMethod::PublicReference(referenced) => {
+            parse_components(referenced.id, plain, options.verbose, depth, &collapsed, true)
+        }
+        ReplyMethod::EphemeralFollowup(_) | ReplyMethod::PublicFollowup(_) => None,
+    };
+    let replies = send_chunked_message_with_commands(ctx, channel, chunks, reply_to, components)
+        .await
+        .map_err(CommandError::discord("discord-send-tree"))?;
+    // editing the codeblock edits these to match
+    if let ReplyMethod::PublicReference(referenced) = reply_to {
+        reparse::track(
+            referenced.id,
+            reparse::Tracked {
+                config,
+                tree,
+                code: code.to_owned(),
+                plain,
+                theme: theme::preferred(invoker, guild_id),
+                options,
+                replies,
+            },
+        );
+    }
+    Ok(())
+}
+
+async fn errors(run: Invocation<'_>) -> Result<(), CommandError> {
+    let Invocation { ctx, channel, options, config, code, reply_to, theme, .. } = run;
+    // what the workers get, they can't borrow anything
+    let work = (config, code.to_owned());
+    let explained = workers::run(Task::Parse, move || {
+        let (config, code) = work;
+        explain_errors(&config, &code, theme)
+    })
+    .await?;
+    send_ansi(ctx, channel, &explained, &options, reply_to, None).await?;
+    Ok(())
+}
+
+async fn stats(run: Invocation<'_>) -> Result<(), CommandError> {
+    let Invocation { ctx, channel, options, config, code, reply_to, theme, .. } = run;
+    // what the workers get, they can't borrow anything
+    let work = (config, code.to_owned());
+    let stats = workers::run(Task::Parse, move || {
+        let (config, code) = work;
+        stats::stats(&config, &code, theme)
+    })
+    .await?;
+    send_ansi(ctx, channel, &stats, &options, reply_to, None).await?;
+    Ok(())
+}
+
+async fn json_parse(run: Invocation<'_>) -> Result<(), CommandError> {
+    let Invocation { ctx, channel, config, code, reply_to, .. } = run;
+    // what the workers get, they can't borrow anything
+    let work = (config, code.to_owned());
+    let json = workers::run(Task::Parse, move || {
+        let (config, code) = work;
+        parse_json(&config, &code)
+    })
+    .await?;
+    if json.len() > *render::UPLOAD_LIMIT {
+        return Err(tr!("upload-tree-too-big").into());
+    }
+    send_files(ctx, channel, reply_to, "", &[(json.as_bytes(), "tree.json")]).await?;
+    Ok(())
+}
+
+async fn json_highlight(run: Invocation<'_>) -> Result<(), CommandError> {
+    let Invocation { ctx, channel, config, code, reply_to, .. } = run;
+    // what the workers get, they can't borrow anything
+    let work = (config, code.to_owned());
+    let json = workers::run(Task::Highlight, move || {
+        let (config, code) = work;
+        highlight_json(&config, &code)
+    })
+    .await?;
+    if json.len() > *render::UPLOAD_LIMIT {
+        return Err(tr!("upload-highlights-too-big").into());
+    }
+    send_files(ctx, channel, reply_to, "", &[(json.as_bytes(), "highlights.json")]).await?;
+    Ok(())
+}
+
+async fn html_highlight(run: Invocation<'_>) -> Result<(), CommandError> {
+    let Invocation { ctx, channel, config, code, reply_to, theme, tab_width, .. } = run;
+    // what the workers get, they can't borrow anything
+    let work = (config, code.to_owned());
+    let html = workers::run(Task::Highlight, move || {
+        let (config, code) = work;
+        html::highlight_html(&config, &code, theme, tab_width)
+    })
+    .await?;
+    if html.len() > *render::UPLOAD_LIMIT {
+        return Err(tr!("upload-html-too-big").into());
+    }
+    send_files(ctx, channel, reply_to, "", &[(html.as_bytes(), "code.html")]).await?;
+    Ok(())
+}
+
+async fn dot_parse(run: Invocation<'_>) -> Result<(), CommandError> {
+    let Invocation { ctx, channel, config, code, reply_to, .. } = run;
+    // what the workers get, they can't borrow anything
+    let work = (config, code.to_owned());
+    let (dot, nodes) = workers::run(Task::Parse, move || {
+        let (config, code) = work;
+        let tree = workers::parse(&config, &code, None)?;
+        Ok::<_, CommandError>(graph::dot(&tree, &code))
+    })
+    .await?;
+    if dot.len() > *render::UPLOAD_LIMIT {
+        return Err(tr!("upload-tree-too-big").into());
+    }
+    // the DOT is still useful on its own, so dot failing isn't the end of the world
+    let (image, note) = match graph::draw(&dot, nodes).await {
+        Ok(image) => (image, String::new()),
+        Err(why) => {
+            error!("{why}");
+            (None, tr!("graph-failed"))
+        }
+    };
+    let mut files = vec![(dot.as_bytes(), "tree.dot")];
+    if let Some(ref image) = image {
+        files.push((&image[..], "tree.png"));
+    }
+    send_files(ctx, channel, reply_to, &note, &files).await?;
+    Ok(())
+}
+
+async fn render(run: Invocation<'_>) -> Result<(), CommandError> {
+    let Invocation {
+        ctx,
+        channel,
+        command,
+        options,
+        config,
+        code,
+        reply_to,
+        invoker,
+        author,
+        add_components,
+        guild_id,
+        theme,
+        tab_width,
+    } = run;
+    let line_numbers = options.line_numbers.unwrap_or_else(|| {
+        storage::guild_setting(guild_id, |guild| guild.line_numbers).unwrap_or(false)
+    });
+    // config goes into render_command, so this can't be worked out in the middle of calling it
+    let title = if options.title {
+        Some(render::title(author, &config.name).await)
+    } else {
+        None
+    };
+    let sent = render_command(
+        ctx,
+        channel,
+        config,
+        code,
+        RenderOptions {
+            theme,
+            line_numbers,
+            title,
+            wrap: match options.wrap.unwrap_or(render::DEFAULT_WRAP_WIDTH) {
+                0 => None,
+                width => Some(width),
+            },
+            tab_width,
+            font: render::guild_font(guild_id),
+            size: options
+                .size
+                .unwrap_or_else(|| render::preferred_size(invoker)),
+            encoding: options.format,
+            range: options.range,
+            emphasis: options.emphasis.clone(),
+            transparent: options.transparent,
+            spoiler: options.spoiler,
+            animate: options.animate,
+        },
+        reply_to,
+        invoker,
+        add_components,
+    )
+    .await?;
+    // editing the codeblock renders it again
+    if let ReplyMethod::PublicReference(referenced) = reply_to {
+        rerun::track(
+            referenced.id,
+            rerun::Ran {
+                command,
+                options,
+                invoker,
+                add_components,
+                replies: vec![sent],
+            },
+        );
+    }
+    Ok(())
+}
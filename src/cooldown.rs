@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use super::*;
+
+// Mashing a button (or spamming a command) makes the bot post over and over, and the render queue only stops
+// someone from having two renders at once. So every command has a bucket of tokens for each user and a bigger one
+// for each server: running it takes a token from both, and tokens come back one at a time.
+// An empty bucket means waiting for the next one. Automatic responses don't take any, nobody asked for those
+
+struct Limit {
+    // how many it can be run in a row
+    burst: f64,
+    // how long it takes for one token to come back
+    every: Duration,
+}
+
+const USER: Limit = Limit {
+    burst: 5.0,
+    every: Duration::from_secs(3),
+};
+const GUILD: Limit = Limit {
+    burst: 20.0,
+    every: Duration::from_secs(1),
+};
+// renders take a lot longer than anything else
+const RENDER_USER: Limit = Limit {
+    burst: 3.0,
+    every: Duration::from_secs(10),
+};
+const RENDER_GUILD: Limit = Limit {
+    burst: 10.0,
+    every: Duration::from_secs(3),
+};
+
+// Takes a token for running this command, or says how long until there is one.
+// The buckets are wherever the caches are, so they're shared between processes too, see cache.rs
+pub fn take(user: UserId, guild: Option<GuildId>, command: Command) -> Result<(), String> {
+    let (user_limit, guild_limit) = match command {
+        Command::Render => (&RENDER_USER, &RENDER_GUILD),
+        _ => (&USER, &GUILD),
+    };
+    let bucket = |key: String, limit: &Limit| cache::Bucket {
+        key,
+        burst: limit.burst,
+        every: limit.every,
+    };
+    let mut buckets = vec![bucket(format!("cooldown:user:{user}:{command:?}"), user_limit)];
+    if let Some(guild) = guild {
+        buckets.push(bucket(format!("cooldown:guild:{guild}:{command:?}"), guild_limit));
+    }
+    // both have to have a token, or neither of them loses one
+    let wait = cache::backend().take(&buckets);
+    if !wait.is_zero() {
+        let seconds = wait.as_secs_f64().ceil() as u64;
+        return Err(tr!("cooldown", seconds = seconds));
+    }
+    Ok(())
+}
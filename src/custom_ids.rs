@@ -0,0 +1,155 @@
+// The custom_ids the bot puts on its buttons, menus and modals, and taking them apart again. They come back
+// the way the bot made them, but anyone can send an interaction with whatever custom_id they want,
+// so they're fuzzed like the rest of what people type, see fuzz/fuzz_targets/custom_id.rs.
+// Every kind is made and taken apart right next to each other here, so the two can't drift apart.
+// This is in the bot's lib.rs so fuzz/ can get at it, and that doesn't know about serenity,
+// so the message ids are just numbers
+
+pub struct Button<'a> {
+    // what it does, like "highlight", "spoiler" or "relang-render"
+    pub kind: &'a str,
+    // "render@ursl" means the language was detected, so the codeblock itself doesn't say it
+    pub lang: Option<&'a str>,
+    // "highlight-991266068330975302" says which message, otherwise it's whichever one the reply is to
+    pub message: Option<u64>,
+    // the prompt is deleted afterwards instead of just losing its buttons
+    pub ephemeralish: bool,
+}
+
+pub fn button(custom_id: &str) -> Button<'_> {
+    let (custom_id, ephemeralish) = match custom_id.strip_suffix("-ephemeralish") {
+        Some(custom_id) => (custom_id, true),
+        None => (custom_id, false),
+    };
+    // a language can have a - in it, like objective-c, so a detected one never has a message after it
+    if let Some((kind, lang)) = custom_id.split_once('@') {
+        return Button {
+            kind,
+            lang: Some(lang),
+            message: None,
+            ephemeralish,
+        };
+    }
+    // something that isn't an id (like the parse in "pretty-parse") is still part of the kind
+    let (kind, message) = match custom_id.rsplit_once('-') {
+        Some((kind, message)) => match message.parse() {
+            Ok(message) => (kind, Some(message)),
+            Err(_) => (custom_id, None),
+        },
+        None => (custom_id, None),
+    };
+    Button {
+        kind,
+        lang: None,
+        message,
+        ephemeralish,
+    }
+}
+
+// <kind>, or <kind>-<message id> for a button that isn't under a reply to that message
+pub fn button_id(kind: &str, message: Option<u64>) -> String {
+    match message {
+        Some(message) => format!("{kind}-{message}"),
+        None => kind.to_owned(),
+    }
+}
+
+// <kind>@<language>, for running it as a language the codeblock doesn't say, see detect.rs
+pub fn detected_id(kind: &str, lang: &str) -> String {
+    format!("{kind}@{lang}")
+}
+
+// <kind>-ephemeralish, for a prompt that's deleted once it's answered
+pub fn ephemeralish_id(kind: &str) -> String {
+    format!("{kind}-ephemeralish")
+}
+
+// The command in a button's kind of relang-<command>, see picker.rs
+pub fn relang(kind: &str) -> Option<&str> {
+    kind.strip_prefix("relang-")
+}
+
+pub fn relang_id(command: &str, message: u64) -> String {
+    button_id(&format!("relang-{command}"), Some(message))
+}
+
+// The command in a button's kind of help-<command>, the language is the detected one, see help.rs
+pub fn help(kind: &str) -> Option<&str> {
+    kind.strip_prefix("help-")
+}
+
+pub fn help_id(command: &str, lang: &str) -> String {
+    detected_id(&format!("help-{command}"), lang)
+}
+
+// block-<command>-<message id>, see blocks.rs
+pub fn block(custom_id: &str) -> Option<(&str, u64)> {
+    let (command, source) = custom_id.strip_prefix("block-")?.rsplit_once('-')?;
+    Some((command, source.parse().ok()?))
+}
+
+pub fn block_id(command: &str, message: u64) -> String {
+    format!("block-{command}-{message}")
+}
+
+// lang-<command>-<message id>-<which menu>, see picker.rs
+pub fn lang(custom_id: &str) -> Option<(&str, u64)> {
+    // which menu it was doesn't matter, it's only there because every custom_id has to be different
+    let (rest, _) = custom_id.strip_prefix("lang-")?.rsplit_once('-')?;
+    let (command, source) = rest.rsplit_once('-')?;
+    Some((command, source.parse().ok()?))
+}
+
+pub fn lang_id(command: &str, message: u64, menu: usize) -> String {
+    format!("lang-{command}-{message}-{menu}")
+}
+
+// expand-<mode>-<depth>-<message id>, see expand.rs
+pub fn expand(custom_id: &str) -> Option<(&str, usize, u64)> {
+    let mut parts = custom_id.strip_prefix("expand-")?.splitn(3, '-');
+    let mode = parts.next()?;
+    let depth = parts.next()?.parse().ok()?;
+    let source = parts.next()?.parse().ok()?;
+    Some((mode, depth, source))
+}
+
+pub fn expand_id(mode: &str, depth: usize, message: u64) -> String {
+    format!("expand-{mode}-{depth}-{message}")
+}
+
+// page-prev-<page>, page-at-<page> or page-next-<page>, see pages.rs
+pub fn page(custom_id: &str) -> Option<usize> {
+    let (_, page) = custom_id.strip_prefix("page-")?.rsplit_once('-')?;
+    page.parse().ok()
+}
+
+pub fn page_id(button: &str, page: usize) -> String {
+    format!("page-{button}-{page}")
+}
+
+// code-<command>-<language> for /highlight, which already knows the language, or scratchpad-<command>
+// for /scratchpad, which asks for it in the modal, see modal.rs. Whether it's the scratchpad,
+// the command, and the language if it's not the scratchpad
+pub fn modal(custom_id: &str) -> Option<(bool, &str, &str)> {
+    let mut parts = custom_id.splitn(3, '-');
+    let scratchpad = match parts.next()? {
+        "code" => false,
+        "scratchpad" => true,
+        _ => return None,
+    };
+    let command = parts.next()?;
+    let lang = if scratchpad {
+        ""
+    } else {
+        parts.next().unwrap_or_default()
+    };
+    Some((scratchpad, command, lang))
+}
+
+pub fn code_modal_id(command: &str, lang: &str) -> String {
+    format!("code-{command}-{lang}")
+}
+
+pub fn scratchpad_id(command: &str) -> String {
+    format!("scratchpad-{command}")
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use super::*;
+
+// how many times more errors the runner-up has to have for the best grammar to count as a winner
+const MARGIN: usize = 2;
+// and how many more at least, since 0 against 1 is a lot more than twice as many, but that one error
+// is just as likely to be a typo in a short snippet
+const MIN_GAP: usize = 2;
+// and how many errors per line the winner is allowed to have before it's too much of a guess
+const MAX_ERRORS_PER_LINE: f32 = 0.25;
+// Every grammar only gets this long, so one slow grammar doesn't use up the whole parse budget (see workers.rs).
+// one that takes longer than that probably isn't the right one anyway
+const GRAMMAR_TIMEOUT: Duration = Duration::from_millis(50);
+// Only this much of the start of a codeblock is parsed, which is plenty to tell languages apart
+const MAX_DETECT_BYTES: usize = 4096;
+
+fn count_errors(cursor: &mut TreeCursor) -> usize {
+    let node = cursor.node();
+    // has_error covers the whole subtree, so clean subtrees can be skipped entirely
+    if !node.has_error() {
+        return 0;
+    }
+    let mut count = (node.is_error() || node.is_missing()) as usize;
+    if cursor.goto_first_child() {
+        loop {
+            count += count_errors(cursor);
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+        cursor.goto_parent();
+    }
+    count
+}
+
+// Parses the code with every grammar we have, and picks the one with the fewest errors
+// but only if it wins convincingly, otherwise it's better to not guess at all.
+// It runs on a worker like any other parse, and if that doesn't work out it's just not guessed
+pub async fn detect(code: &str) -> Option<Arc<LanguageConfig>> {
+    let code = start_of(code).to_owned();
+    match workers::run(Task::Parse, move || Ok::<_, CommandError>(best_guess(&code))).await {
+        Ok(config) => config,
+        Err(e) => {
+            debug!("couldn't detect a language: {e}");
+            None
+        }
+    }
+}
+
+// The first MAX_DETECT_BYTES of the code, cut off after a whole line if there is one in there
+fn start_of(code: &str) -> &str {
+    if code.len() <= MAX_DETECT_BYTES {
+        return code;
+    }
+    let end = (0..=MAX_DETECT_BYTES)
+        .rev()
+        .find(|&i| code.is_char_boundary(i))
+        .unwrap_or(0);
+    match code[..end].rfind('\n') {
+        Some(newline) => &code[..newline],
+        None => &code[..end],
+    }
+}
+
+fn best_guess(code: &str) -> Option<Arc<LanguageConfig>> {
+    let mut scores = Vec::new();
+    for config in all_languages() {
+        // these come from the same pool as every other command's, see pool.rs
+        let mut parser = match parser(&config) {
+            Ok(parser) => parser,
+            Err(_) => continue,
+        };
+        parser.set_timeout_micros(GRAMMAR_TIMEOUT.as_micros() as u64);
+        if let Some(tree) = parser.parse(code, None) {
+            scores.push((count_errors(&mut tree.walk()), config));
+        }
+    }
+    scores.sort_by_key(|&(errors, _)| errors);
+    let mut scores = scores.into_iter();
+
+    let (best, config) = scores.next()?;
+    if best as f32 > code.lines().count() as f32 * MAX_ERRORS_PER_LINE {
+        return None;
+    }
+    match scores.next() {
+        Some((runner_up, _)) if best * MARGIN >= runner_up || runner_up - best < MIN_GAP => None,
+        _ => {
+            debug!("detected bare codeblock as {}", config.name);
+            Some(config)
+        }
+    }
+}
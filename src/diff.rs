@@ -0,0 +1,171 @@
+use super::*;
+use serenity::model::{
+    application::interaction::application_command::ApplicationCommandInteraction,
+    channel::{Attachment, MessageReference},
+};
+
+// "Diff Codeblocks" is used on a reply, and diffs the codeblock it replied to (the old code) against its own (the new code).
+// It also works on a message with two attachments, which are diffed in the order they were attached
+pub const COMMAND_NAME: &str = "Diff Codeblocks";
+
+// attachments get highlighted in full twice, so they can't be huge
+const MAX_ATTACHMENT_SIZE: u64 = 1_000_000;
+
+pub async fn handle(
+    ctx: &Context,
+    original: &Interaction,
+    interaction: &ApplicationCommandInteraction,
+) {
+    let channel = interaction.channel_id.to_channel(&ctx).await;
+    let channel = match channel.or_log("get the channel") {
+        Some(channel) => channel,
+        None => return,
+    };
+    // discord always says which message it was for message commands
+    let target = match interaction.data.target_id {
+        Some(target) => target.to_message_id(),
+        None => return,
+    };
+    let message = match interaction.data.resolved.messages.get(&target) {
+        Some(message) => Some(message.clone()),
+        None => get_ref(ctx, &channel, target).await,
+    };
+    let guild_id = interaction.guild_id;
+
+    let found = match message {
+        Some(message) => find_versions(ctx, &channel, &message)
+            .await
+            .map(|versions| (message, versions)),
+        None => Err(owo!("message-gone")),
+    };
+    let (message, versions) = match found {
+        Ok(found) => found,
+        Err(why) => {
+            interaction
+                .create_interaction_response(&ctx, |response| {
+                    response.interaction_response_data(|msg| msg.ephemeral(true).content(why))
+                })
+                .await
+                .or_log("reply with the error");
+            return;
+        }
+    };
+    let ((old_lang, old), (new_lang, new)) = versions;
+    // the new code decides the language, unless it doesn't say
+    let lang = if new_lang.is_empty() { &old_lang } else { &new_lang };
+    let config = match get_guild_language(guild_id, lang) {
+        Some(config) => languages::for_guild(guild_id, config),
+        None => {
+            interaction
+                .create_interaction_response(&ctx, |response| {
+                    response.interaction_response_data(|msg| {
+                        msg.ephemeral(true)
+                            .content(owo!("diff-unknown-language", language = lang.as_str()))
+                    })
+                })
+                .await
+                .or_log("reply with the error");
+            return;
+        }
+    };
+
+    interaction
+        .create_interaction_response(&ctx, |response| {
+            response
+                .interaction_response_data(|msg| msg.ephemeral(true).content(tr!("diff-rendering")))
+        })
+        .await
+        .or_log("say it's rendering");
+    let options = RenderOptions {
+        theme: theme::preferred(interaction.user.id, guild_id),
+        line_numbers: true,
+        title: None,
+        wrap: Some(render::DEFAULT_WRAP_WIDTH),
+        tab_width: DEFAULT_TAB_WIDTH,
+        font: render::guild_font(guild_id),
+        size: render::preferred_size(interaction.user.id),
+        encoding: render::Encoding::default(),
+        range: None,
+        emphasis: Vec::new(),
+        transparent: false,
+        spoiler: false,
+        animate: false,
+    };
+    let key = render::cache_key(&[&config], &[&old, &new], &options);
+    let description = tr!("diff-description", language = config.name.as_str());
+    let description = render::describe(&description, &new, None);
+    let draw = move |options: &RenderOptions| render::render_diff(&config, &old, &new, options);
+    let result = render::send_render(
+        ctx,
+        &channel,
+        options,
+        key,
+        draw,
+        &description,
+        ReplyMethod::PublicReference(&message),
+        interaction.user.id,
+        false,
+    )
+    .await;
+    if let Err(why) = result {
+        create_followup_message(
+            ctx,
+            original,
+            |msg: &mut CreateInteractionResponseFollowup| {
+                msg.ephemeral(true).content(why.report())
+            },
+        )
+        .await
+        .or_log("reply with the error");
+    }
+}
+
+// ((old language, old code), (new language, new code))
+type Versions = ((String, String), (String, String));
+
+async fn find_versions(
+    ctx: &Context,
+    channel: &Channel,
+    message: &Message,
+) -> Result<Versions, String> {
+    if let [old, new, ..] = &message.attachments[..] {
+        return Ok((download(old).await?, download(new).await?));
+    }
+    let (_, new_lang, new, _) =
+        codeblock(&message.content).ok_or_else(|| owo!("not-a-codeblock"))?;
+    let referenced = match message.message_reference {
+        Some(MessageReference {
+            message_id: Some(id),
+            ..
+        }) => get_ref(ctx, channel, id)
+            .await
+            .ok_or_else(|| owo!("diff-reply-gone"))?,
+        _ => return Err(tr!("diff-how")),
+    };
+    let (_, old_lang, old, _) =
+        codeblock(&referenced.content).ok_or_else(|| owo!("diff-reply-not-a-codeblock"))?;
+    Ok((
+        (old_lang.to_owned(), old.to_owned()),
+        (new_lang.to_owned(), new.to_owned()),
+    ))
+}
+
+// the language is the file extension, which is close enough most of the time
+async fn download(attachment: &Attachment) -> Result<(String, String), String> {
+    if attachment.size > MAX_ATTACHMENT_SIZE {
+        return Err(owo!("diff-too-big", file = attachment.filename.as_str()));
+    }
+    let bytes = attachment.download().await.map_err(|e| {
+        error!("{e:?}");
+        owo!("diff-download", file = attachment.filename.as_str())
+    })?;
+    let code = String::from_utf8(bytes)
+        .map_err(|_| owo!("diff-not-text", file = attachment.filename.as_str()))?;
+    let lang = match attachment.filename.rsplit_once('.') {
+        Some((_, extension)) => extension.to_owned(),
+        None => String::new(),
+    };
+    // same as what message() does to codeblocks
+    let code = code.lines().collect::<Vec<_>>().join("\n");
+    Ok((lang, code))
+}
@@ -0,0 +1,123 @@
+use std::future::Future;
+
+use super::*;
+
+// Everything a command can fail with. Whoever ran it is told what it says, and anything that
+// isn't just a problem with what they asked for is logged in full too, see report
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error(transparent)]
+    Highlight(#[from] HighlightError),
+    // discord not doing what it was asked, like when the permissions changed in the meantime.
+    // what is the id in locales/en-US.ftl that says what it was
+    #[error("{what}")]
+    Discord {
+        what: &'static str,
+        #[source]
+        source: serenity::Error,
+    },
+    #[error("The worker running it failed to join")]
+    Join(#[from] tokio::task::JoinError),
+    // the bot's own checks, which are already worded for whoever ran it
+    #[error("{0}")]
+    User(String),
+}
+
+impl From<String> for CommandError {
+    fn from(why: String) -> Self {
+        CommandError::User(why)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(why: &str) -> Self {
+        CommandError::User(why.to_owned())
+    }
+}
+
+impl CommandError {
+    pub fn discord(what: &'static str) -> impl FnOnce(serenity::Error) -> CommandError {
+        move |source| CommandError::Discord { what, source }
+    }
+
+    // What to tell whoever ran the command. The rest of it only goes in the logs
+    pub fn report(self) -> String {
+        let internal = match self {
+            CommandError::Highlight(ref e) => e.is_internal(),
+            CommandError::Discord { .. } | CommandError::Join(_) => true,
+            CommandError::User(_) => false,
+        };
+        if internal {
+            error!("{self:?}");
+        }
+        match self {
+            CommandError::Highlight(why) => match why {
+                HighlightError::TreeSitter { .. } => tr!("error-tree-sitter"),
+                HighlightError::NoGrammar { .. } => tr!("error-no-grammar"),
+                HighlightError::NodeGone { .. } => tr!("error-node-gone"),
+                HighlightError::Serialize { what, .. } => tr!("error-serialize", what = what),
+                HighlightError::Encode { what, .. } => tr!("error-encode", what = what),
+                HighlightError::OutOfRange { .. } => tr!("error-out-of-range"),
+                // core says how big it was and what the limit is, so that's left as it is
+                HighlightError::TooBig(why) => why,
+            },
+            CommandError::Discord { what, .. } => locale::translate(&locale::current(), what, &[]),
+            CommandError::Join(_) => tr!("error-join"),
+            CommandError::User(why) => why,
+        }
+    }
+}
+
+// For discord calls that nothing after them really depends on. The channel being deleted or a
+// permission being taken away in the meantime is only worth a line in the logs, not the whole event
+pub trait OrLog<T> {
+    fn or_log(self, what: &str) -> Option<T>;
+}
+
+impl<T> OrLog<T> for serenity::Result<T> {
+    fn or_log(self, what: &str) -> Option<T> {
+        match self {
+            Ok(value) => Some(value),
+            Err(why) => {
+                warn!("couldn't {what}: {why}");
+                None
+            }
+        }
+    }
+}
+
+// Every event is handled on its own task, so a panic (which is always a bug) only takes that event
+// with it. Whoever used an interaction is told it went wrong, instead of discord just saying
+// "This interaction failed" a few seconds later. autocomplete has nowhere to say it, so it's None
+pub async fn boundary(
+    ctx: &Context,
+    interaction: Option<&Interaction>,
+    handler: impl Future<Output = ()> + Send + 'static,
+) {
+    let why = match tokio::spawn(handler).await {
+        Err(why) if why.is_panic() => why,
+        _ => return,
+    };
+    // the panic message itself was already printed by the panic hook
+    error!("the handler panicked: {why}");
+    let interaction = match interaction {
+        Some(interaction) => interaction,
+        None => return,
+    };
+    // this is outside of the handler's locale::scope
+    let panicked = tr!(&locale::of(interaction), "panicked");
+    // it depends on how far it got whether it already responded, so whichever one works
+    let responded = create_interaction_response(ctx, interaction, |response| {
+        response.interaction_response_data(|msg| msg.ephemeral(true).content(&panicked))
+    })
+    .await;
+    if responded.is_err() {
+        create_followup_message(
+            ctx,
+            interaction,
+            |msg: &mut CreateInteractionResponseFollowup| msg.ephemeral(true).content(&panicked),
+        )
+        .await
+        .or_log("say that the interaction panicked");
+    }
+}
@@ -0,0 +1,105 @@
+use super::*;
+
+// The menu under +parse output, made by parse_components. Picking a node parses the codeblock again,
+// but only that node, and sends it just to whoever picked it, with its own menu to go further down.
+// The custom_id is expand-<pretty or plain, maybe with .verbose>-<depth>-<message id>,
+// and the value is the path to the node
+pub async fn handle(
+    ctx: &Context,
+    original: &Interaction,
+    interaction: &MessageComponentInteraction,
+) {
+    let result = match parse_custom_id(&interaction.data.custom_id) {
+        Some((mode, depth, source)) => {
+            expand(ctx, original, interaction, mode, depth, source).await
+        }
+        None => Err(owo!("unknown-menu", id = interaction.data.custom_id.as_str()).into()),
+    };
+    // anything that goes wrong, goes wrong before it's deferred
+    if let Err(why) = result {
+        let why = why.report();
+        interaction
+            .create_interaction_response(&ctx, |response| {
+                response.interaction_response_data(|msg| msg.ephemeral(true).content(why))
+            })
+            .await
+            .or_log("reply with the error");
+    }
+}
+
+// (plain, verbose)
+type Mode = (bool, bool);
+
+fn parse_custom_id(custom_id: &str) -> Option<(Mode, usize, MessageId)> {
+    let (mode, depth, source) = custom_ids::expand(custom_id)?;
+    let mode = match mode {
+        "pretty" => (false, false),
+        "plain" => (true, false),
+        "pretty.verbose" => (false, true),
+        "plain.verbose" => (true, true),
+        _ => return None,
+    };
+    Some((mode, depth, source.into()))
+}
+
+async fn expand(
+    ctx: &Context,
+    original: &Interaction,
+    interaction: &MessageComponentInteraction,
+    (plain, verbose): Mode,
+    depth: usize,
+    source: MessageId,
+) -> Result<(), CommandError> {
+    let path = interaction
+        .data
+        .values
+        .first()
+        .and_then(|value| {
+            value
+                .split('.')
+                .map(|i| i.parse().ok())
+                .collect::<Option<Vec<usize>>>()
+        })
+        .ok_or_else(|| owo!("expand-unknown-node"))?;
+    let channel = interaction
+        .channel_id
+        .to_channel(&ctx)
+        .await
+        .map_err(CommandError::discord("discord-channel"))?;
+    let message = get_ref(ctx, &channel, source).await;
+    let (_, lang, code, _) = message
+        .as_ref()
+        .and_then(|message| codeblock(&message.content))
+        .ok_or_else(|| owo!("codeblock-gone"))?;
+    let guild_id = interaction.guild_id;
+    let config = get_guild_language(guild_id, lang)
+        .ok_or_else(|| owo!("expand-unknown-language", language = lang))?;
+    let config = languages::for_guild(guild_id, config);
+    let theme = theme::preferred(interaction.user.id, guild_id);
+    let theme = if plain { None } else { Some(theme) };
+    let options = Options {
+        depth: Some(depth),
+        verbose,
+        ..Default::default()
+    };
+    let code = code.to_owned();
+    let (chunks, depth, collapsed) = workers::run(Task::Parse, move || {
+        let tree = workers::parse(&config, &code, None)?;
+        pretty_parse_chunks(&tree, &code, theme, &path, &options)
+    })
+    .await?;
+    defer(ctx, original, true)
+        .await
+        .map_err(CommandError::discord("discord-answer-menu"))?;
+    let components = parse_components(source, plain, verbose, depth, &collapsed, false);
+    send_chunked_message_with_commands(
+        ctx,
+        &channel,
+        chunks,
+        ReplyMethod::EphemeralFollowup(original),
+        components,
+    )
+    .await
+    .or_log("send the expanded node");
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use super::*;
+use tokio::task::JoinHandle;
+
+// The bot's own prompts (like asking about a codeblock that doesn't say its language) delete themselves
+// after a while, so nobody has to clean up the ones that got ignored. /server prompt-timeout says how long,
+// and 0 keeps them. They're only kept track of in memory, so the ones from before a restart just stay
+const DEFAULT_TIMEOUT_MINUTES: u64 = 10;
+
+lazy_static! {
+    static ref PENDING: std::sync::Mutex<HashMap<MessageId, JoinHandle<()>>> = Default::default();
+}
+
+pub fn schedule(ctx: &Context, guild_id: Option<GuildId>, prompt: &Message) {
+    let minutes = storage::guild_setting(guild_id, |guild| guild.prompt_timeout)
+        .unwrap_or(DEFAULT_TIMEOUT_MINUTES);
+    if minutes == 0 {
+        return;
+    }
+    let (ctx, channel, id) = (ctx.clone(), prompt.channel_id, prompt.id);
+    let task = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(minutes * 60)).await;
+        PENDING.lock().unwrap().remove(&id);
+        if let Err(why) = channel.delete_message(&ctx, id).await {
+            error!("{why}");
+        }
+    });
+    PENDING.lock().unwrap().insert(id, task);
+}
+
+// someone already deleted it (or pressed a button on it, which does too)
+pub fn cancel(prompt: MessageId) {
+    if let Some(task) = PENDING.lock().unwrap().remove(&prompt) {
+        task.abort();
+    }
+}
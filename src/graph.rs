@@ -0,0 +1,122 @@
+use std::{fmt::Write as _, io::ErrorKind, process::Stdio, time::Duration};
+
+use super::*;
+use tokio::{io::AsyncWriteExt, process::Command as Process};
+
+// +parse dot, for grammar authors who'd rather look at a picture of the tree than read it.
+// The graph is Graphviz's DOT, and if the dot program is installed it also gets drawn as a PNG.
+// dot gets really slow on big graphs, so past MAX_DRAWN_NODES it's only the DOT
+const MAX_DRAWN_NODES: usize = 500;
+const DOT_TIMEOUT: Duration = Duration::from_secs(20);
+// leaves show their text, but only this much of it
+const MAX_LEAF_TEXT: usize = 32;
+
+// the DOT, and how many nodes are in it
+pub fn dot(tree: &Tree, code: &str) -> (String, usize) {
+    let mut out = String::from("digraph tree {\n");
+    out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+    out.push_str("    edge [fontname=\"monospace\", fontsize=10];\n");
+    let mut count = 0;
+    write_node(&mut tree.walk(), code, &mut out, &mut count);
+    out.push_str("}\n");
+    (out, count)
+}
+
+// The same nodes pretty_parse shows, so anonymous tokens like punctuation don't clutter it up
+fn write_node(
+    cursor: &mut TreeCursor,
+    code: &str,
+    out: &mut String,
+    count: &mut usize,
+) -> usize {
+    let id = *count;
+    *count += 1;
+    let node = cursor.node();
+    let mut label = escape(node.kind());
+    let color = if node.is_error() || node.is_missing() {
+        "red"
+    } else if node.is_extra() {
+        "gray"
+    } else {
+        "black"
+    };
+
+    let mut children = Vec::new();
+    if cursor.goto_first_child() {
+        loop {
+            if cursor.field_name().is_some()
+                || cursor.node().is_named()
+                || cursor.node().child_count() > 0
+            {
+                let field = cursor.field_name();
+                children.push((write_node(cursor, code, out, count), field));
+            }
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+        cursor.goto_parent();
+    }
+    if children.is_empty() && node.is_named() {
+        let text = &code[node.byte_range()];
+        let mut shown = text.chars().take(MAX_LEAF_TEXT).collect::<String>();
+        if shown.len() < text.len() {
+            shown.push('…');
+        }
+        label.push_str("\\n");
+        label.push_str(&escape(&shown));
+    }
+    writeln!(out, "    n{id} [label=\"{label}\", color={color}, fontcolor={color}];").unwrap();
+    for (child, field) in children {
+        match field {
+            Some(field) => writeln!(out, "    n{id} -> n{child} [label=\"{}\"];", escape(field)),
+            None => writeln!(out, "    n{id} -> n{child};"),
+        }
+        .unwrap();
+    }
+    id
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Ok(None) means dot isn't installed, or the graph is too big to bother
+pub async fn draw(dot: &str, nodes: usize) -> Result<Option<Vec<u8>>, String> {
+    if nodes > MAX_DRAWN_NODES {
+        return Ok(None);
+    }
+    let child = Process::new("dot")
+        .arg("-Tpng")
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("dot couldn't start: {e}")),
+    };
+    let mut stdin = child.stdin.take().unwrap();
+    stdin
+        .write_all(dot.as_bytes())
+        .await
+        .map_err(|e| format!("dot couldn't read the graph: {e}"))?;
+    // closing it is how dot knows the graph is over
+    drop(stdin);
+    let output = match tokio::time::timeout(DOT_TIMEOUT, child.wait_with_output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(format!("dot failed: {e}")),
+        Err(_) => return Err(format!("dot took longer than {DOT_TIMEOUT:?}")),
+    };
+    if output.status.success() {
+        Ok(Some(output.stdout))
+    } else {
+        Err(format!("dot failed: {}", String::from_utf8_lossy(&output.stderr)))
+    }
+}
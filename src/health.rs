@@ -0,0 +1,217 @@
+use std::time::{Duration, Instant};
+
+use super::*;
+use serenity::{
+    client::{
+        bridge::gateway::{ShardId, ShardManager},
+        RawEventHandler,
+    },
+    gateway::ConnectionStage,
+    model::event::Event,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+// For running the bot under something that restarts it when it's unhealthy, like kubernetes.
+// With HEALTH_ADDR set (like 0.0.0.0:8080) it serves /healthz and /readyz, which both say what
+// every shard is doing and how long ago it last got an event. /readyz is only 200 while all of
+// them are connected to the gateway, and /healthz stops being 200 once the watchdog couldn't help.
+//
+// The watchdog restarts a shard that hasn't gotten any event in WATCHDOG_TIMEOUT seconds, because
+// a connection that died without closing never comes back on its own. 0 turns it off.
+// a bot in only a few quiet servers can go that long without events, that just costs a resume
+const DEFAULT_WATCHDOG_TIMEOUT: u64 = 600;
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    static ref SHARDS: std::sync::Mutex<HashMap<u64, Shard>> = Default::default();
+    static ref WATCHDOG_TIMEOUT: u64 =
+        config::parse("WATCHDOG_TIMEOUT").unwrap_or(DEFAULT_WATCHDOG_TIMEOUT);
+}
+
+struct Shard {
+    stage: ConnectionStage,
+    last_event: Option<Instant>,
+    events: u64,
+    // when it was first heard of, for how long it's really been quiet
+    seen_since: Instant,
+    // when it was connected or restarted, so one that never got an event still gets restarted
+    watched_since: Instant,
+}
+
+impl Shard {
+    fn new() -> Self {
+        Shard {
+            stage: ConnectionStage::Disconnected,
+            last_event: None,
+            events: 0,
+            seen_since: Instant::now(),
+            watched_since: Instant::now(),
+        }
+    }
+
+    fn silent_for(&self) -> Duration {
+        self.last_event.unwrap_or(self.seen_since).elapsed()
+    }
+
+    // how long the watchdog has been waiting on it, which starts over when it's restarted
+    fn quiet_for(&self) -> Duration {
+        self.last_event
+            .map_or(self.watched_since, |last| last.max(self.watched_since))
+            .elapsed()
+    }
+}
+
+// Every event from discord, to know they're still coming. The rest of the bot is in Handler
+pub struct Events;
+
+#[async_trait]
+impl RawEventHandler for Events {
+    async fn raw_event(&self, ctx: Context, _event: Event) {
+        let mut shards = SHARDS.lock().unwrap();
+        let shard = shards.entry(ctx.shard_id).or_insert_with(Shard::new);
+        shard.last_event = Some(Instant::now());
+        shard.events += 1;
+    }
+}
+
+// called from Handler::shard_stage_update
+pub fn stage_changed(shard_id: u64, stage: ConnectionStage) {
+    SHARDS
+        .lock()
+        .unwrap()
+        .entry(shard_id)
+        .or_insert_with(Shard::new)
+        .stage = stage;
+}
+
+// (connected, all of them), for /about bot
+pub fn shards() -> (usize, usize) {
+    let shards = SHARDS.lock().unwrap();
+    let connected = shards
+        .values()
+        .filter(|shard| shard.stage == ConnectionStage::Connected)
+        .count();
+    (connected, shards.len())
+}
+
+pub fn start(manager: Arc<Mutex<ShardManager>>) {
+    if let Some(addr) = config::var("HEALTH_ADDR") {
+        tokio::spawn(serve(addr));
+    }
+    if *WATCHDOG_TIMEOUT > 0 {
+        tokio::spawn(watchdog(manager));
+    }
+}
+
+async fn watchdog(manager: Arc<Mutex<ShardManager>>) {
+    let timeout = Duration::from_secs(*WATCHDOG_TIMEOUT);
+    let mut interval = tokio::time::interval(WATCHDOG_INTERVAL);
+    loop {
+        interval.tick().await;
+        let stuck = {
+            let mut shards = SHARDS.lock().unwrap();
+            shards
+                .iter_mut()
+                .filter(|(_, shard)| shard.quiet_for() > timeout)
+                .map(|(&id, shard)| {
+                    // and it gets a whole timeout to reconnect before it's restarted again
+                    shard.watched_since = Instant::now();
+                    id
+                })
+                .collect::<Vec<_>>()
+        };
+        for id in stuck {
+            error!(
+                shard = id,
+                "no events in {}s, restarting the gateway connection",
+                timeout.as_secs()
+            );
+            manager.lock().await.restart(ShardId(id)).await;
+        }
+    }
+}
+
+async fn serve(addr: String) {
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(why) => {
+            error!("couldn't serve the health endpoint on {addr}: {why}");
+            return;
+        }
+    };
+    info!("serving the health endpoint on {addr}");
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(respond(stream));
+            }
+            Err(why) => debug!("couldn't accept a health check: {why}"),
+        }
+    }
+}
+
+// Just enough HTTP for a health check. Only the path matters, everything else is ignored
+async fn respond(mut stream: TcpStream) {
+    let mut buffer = [0; 1024];
+    let read = stream.read(&mut buffer).await.unwrap_or(0);
+    let request = String::from_utf8_lossy(&buffer[..read]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+    let (healthy, body) = report();
+    let status = match path {
+        "/healthz" if healthy.alive => "200 OK",
+        "/readyz" if healthy.ready => "200 OK",
+        "/healthz" | "/readyz" => "503 Service Unavailable",
+        _ => "404 Not Found",
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\n\
+        content-type: application/json\r\n\
+        content-length: {}\r\n\
+        connection: close\r\n\
+        \r\n\
+        {body}",
+        body.len()
+    );
+    if let Err(why) = stream.write_all(response.as_bytes()).await {
+        debug!("couldn't answer a health check: {why}");
+    }
+}
+
+struct Healthy {
+    // the watchdog is still keeping up, it had twice as long as it should've needed
+    alive: bool,
+    // every shard is connected and can respond to things
+    ready: bool,
+}
+
+fn report() -> (Healthy, String) {
+    let shards = SHARDS.lock().unwrap();
+    let limit = Duration::from_secs(*WATCHDOG_TIMEOUT * 2);
+    let healthy = Healthy {
+        alive: *WATCHDOG_TIMEOUT == 0 || shards.values().all(|shard| shard.silent_for() < limit),
+        ready: !shards.is_empty()
+            && shards
+                .values()
+                .all(|shard| shard.stage == ConnectionStage::Connected),
+    };
+    let shards = shards
+        .iter()
+        .map(|(id, shard)| {
+            let report = serde_json::json!({
+                "stage": shard.stage.to_string(),
+                "last_event_seconds_ago": shard.last_event.map(|last| last.elapsed().as_secs()),
+                "events": shard.events,
+            });
+            (id.to_string(), report)
+        })
+        .collect::<serde_json::Map<_, _>>();
+    let body = serde_json::json!({
+        "alive": healthy.alive,
+        "ready": healthy.ready,
+        "shards": shards,
+    });
+    (healthy, body.to_string())
+}
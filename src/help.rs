@@ -0,0 +1,139 @@
+use super::*;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+
+// /help, which explains the different ways to use the bot and has buttons to try some of them on an example.
+// The buttons run the command on the example of whichever language it's showing, only for whoever pressed it.
+// The custom_id is help-<the command's id, see commands.rs>@<language>, and buttons from before there was
+// a language in there use the first one
+const TRY: &[(&str, char, &str)] = &[
+    ("highlight", '📋', "+highlight"),
+    ("render", '🖼', "+render"),
+    ("pretty-parse", '🌳', "+parse"),
+];
+
+// The example of that language, or of the first one that has an example if it doesn't have one
+fn example(lang: Option<&str>) -> Option<(Arc<LanguageConfig>, String)> {
+    let config = lang
+        .and_then(get_language)
+        .filter(|config| config.example.is_some())
+        .or_else(|| {
+            all_languages()
+                .into_iter()
+                .find(|config| config.example.is_some())
+        })?;
+    let example = config.example.clone()?;
+    Some((config, example))
+}
+
+pub async fn show(ctx: &Context, interaction: &ApplicationCommandInteraction, lang: Option<&str>) {
+    let locale = &interaction.locale;
+    let mut content = tr!(
+        locale,
+        "help",
+        highlight = Command::Highlight.menu_name(),
+        render = Command::Render.menu_name(),
+        pretty_parse = Command::PrettyParse.menu_name(),
+        plain_parse = Command::PlainParse.menu_name(),
+        errors = Command::Errors.menu_name(),
+    );
+    let example = example(lang);
+    if let Some((ref config, ref code)) = example {
+        let try_it = tr!(locale, "help-example", language = config.name.as_str());
+        let shown = format!("\n\n{try_it}\n```{}\n{code}\n```", config.name);
+        // it's just a nice extra, the rest of it still helps without it
+        if content.len() + shown.len() <= 2000 {
+            content += &shown;
+        }
+    }
+    interaction
+        .create_interaction_response(&ctx, |response| {
+            response.interaction_response_data(|msg| {
+                msg.ephemeral(true).content(content);
+                if let Some((ref config, _)) = example {
+                    msg.components(|c| {
+                        c.create_action_row(|row| {
+                            for &(name, emoji, prefix) in TRY {
+                                row.create_button(|button| {
+                                    button
+                                        .custom_id(custom_ids::help_id(name, &config.name))
+                                        .emoji(emoji)
+                                        .label(tr!(locale, "help-try", command = prefix))
+                                        .style(ButtonStyle::Secondary)
+                                });
+                            }
+                            row
+                        })
+                    });
+                }
+                msg
+            })
+        })
+        .await
+        .or_log("show the help");
+}
+
+pub async fn handle(
+    ctx: &Context,
+    original: &Interaction,
+    interaction: &MessageComponentInteraction,
+) {
+    let button = custom_ids::button(&interaction.data.custom_id);
+    let command = custom_ids::help(button.kind).and_then(commands::by_id);
+    let (command, config, code) = match (command, example(button.lang)) {
+        (Some(command), Some((config, code))) => (command, config, code),
+        // no language has an example anymore, since /reload-languages
+        _ => {
+            interaction
+                .create_interaction_response(&ctx, |response| {
+                    response.interaction_response_data(|msg| {
+                        msg.ephemeral(true).content(owo!("help-example-gone"))
+                    })
+                })
+                .await
+                .or_log("say the example is gone");
+            return;
+        }
+    };
+    let roles = interaction
+        .member
+        .as_ref()
+        .map_or(&[][..], |member| &member.roles);
+    if let Err(why) = may_run(interaction.guild_id, interaction.user.id, roles, command) {
+        interaction
+            .create_interaction_response(&ctx, |response| {
+                response.interaction_response_data(|msg| msg.ephemeral(true).content(why))
+            })
+            .await
+            .or_log("reply with the error");
+        return;
+    }
+    let deferred = defer(ctx, original, true).await;
+    if deferred.or_log("defer the example").is_none() {
+        return;
+    }
+    let result = match interaction.channel_id.to_channel(&ctx).await {
+        Ok(channel) => {
+            run_command(
+                ctx,
+                &channel,
+                command,
+                Options::default(),
+                config,
+                &code,
+                ReplyMethod::EphemeralFollowup(original),
+                interaction.user.id,
+                &interaction.user,
+                false,
+            )
+            .await
+        }
+        Err(why) => Err(CommandError::discord("discord-channel")(why)),
+    };
+    if let Err(why) = result {
+        create_followup_message(ctx, original, |msg: &mut CreateInteractionResponseFollowup| {
+            msg.ephemeral(true).content(why.report())
+        })
+        .await
+        .or_log("reply with the error");
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    ffi::OsStr,
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use super::*;
+use libloading::{Library, Symbol};
+use storage::InstalledGrammar;
+use tokio::process::Command as Process;
+
+// Grammars installed with /grammar install end up in grammars/<guild id>/<language name>/
+//
+// git and the C compiler run in a bubblewrap (bwrap) sandbox, see sandboxed. Nothing but the grammar's own
+// parser.c and scanner.c get compiled (no build scripts, no makefiles), and without bwrap nothing is built
+// at all. The result is still native code loaded into the bot though, the sandbox only covers building it.
+// That's why only the owner of the bot can install grammars, and why it's meant for grammars they've at
+// least glanced at.
+const GRAMMARS_DIR: &str = "grammars";
+// each step (cloning, compiling) gets this long before it's killed
+const STEP_TIMEOUT: Duration = Duration::from_secs(120);
+// what prlimit lets each step use, on top of STEP_TIMEOUT: memory, CPU seconds, file size and open files.
+// the generated parser.c of a big grammar can take a few GB to compile
+const LIMITS: &[&str] = &[
+    "--as=4294967296",
+    "--cpu=120",
+    "--fsize=536870912",
+    "--nofile=256",
+];
+// set by build.rs, cc needs to know what it's compiling for
+const TARGET: &str = env!("TARGET");
+// What the compiler writes to, before it's renamed to grammar-<hash>.so. Libraries are never unloaded
+// (see load), and loading the same path again just gives back the one that's already loaded,
+// so a grammar that's installed again needs a new file name for the new build to be used at all
+const BUILD_OUTPUT: &str = "build.so";
+
+fn library_name(library: u64) -> String {
+    format!("grammar-{library:016x}.so")
+}
+
+lazy_static! {
+    // grammars that have been loaded, keyed by (guild, language name)
+    static ref LOADED: std::sync::Mutex<HashMap<(u64, String), Arc<LanguageConfig>>> =
+        Default::default();
+}
+
+fn grammar_dir(guild_id: u64, name: &str) -> PathBuf {
+    Path::new(GRAMMARS_DIR)
+        .join(guild_id.to_string())
+        .join(name)
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// The program, in its own namespaces with the whole filesystem read-only except for dir (which has to be
+// absolute), an empty /tmp, and the bot's own directory (with its token, config and database) hidden.
+// It only has a network if it's cloning, so the compiler can't send anything anywhere
+fn sandboxed(dir: &Path, network: bool, program: impl AsRef<OsStr>) -> Result<Process, String> {
+    let bot_dir =
+        std::env::current_dir().map_err(|e| tr!("install-no-bot-dir", why = e.to_string()))?;
+    let mut command = Process::new("prlimit");
+    command
+        .args(LIMITS)
+        .arg("--")
+        .arg("bwrap")
+        .args(["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"])
+        .args(["--tmpfs", "/tmp"]);
+    // unless the bot runs right in /, which would hide everything
+    if bot_dir != Path::new("/") {
+        command.arg("--tmpfs").arg(&bot_dir);
+    }
+    command
+        .arg("--bind")
+        .arg(dir)
+        .arg(dir)
+        .arg("--chdir")
+        .arg(dir)
+        .args(["--unshare-all", "--die-with-parent", "--new-session"]);
+    if network {
+        command.arg("--share-net");
+    }
+    command.arg("--").arg(program);
+    Ok(command)
+}
+
+async fn run(command: &mut Process, what: &str) -> Result<(), String> {
+    let command = command
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .kill_on_drop(true);
+    let output = match tokio::time::timeout(STEP_TIMEOUT, command.output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(tr!("install-no-sandbox", step = what))
+        }
+        Ok(Err(e)) => return Err(tr!("install-step-start", step = what, why = e.to_string())),
+        Err(_) => {
+            return Err(tr!(
+                "install-step-timeout",
+                step = what,
+                seconds = STEP_TIMEOUT.as_secs(),
+            ))
+        }
+    };
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // the end of the output is where the actual error usually is
+        let start = (stderr.len().saturating_sub(1000)..stderr.len())
+            .find(|&i| stderr.is_char_boundary(i))
+            .unwrap_or(stderr.len());
+        Err(tr!(
+            "install-step-failed",
+            step = what,
+            output = &stderr[start..]
+        ))
+    }
+}
+
+// The name the grammar gave itself in grammar.js, which is what the tree_sitter_* function is called
+async fn grammar_name(dir: &Path) -> Result<String, String> {
+    #[derive(serde::Deserialize)]
+    struct GrammarJson {
+        name: String,
+    }
+    let json = tokio::fs::read_to_string(dir.join("src").join("grammar.json"))
+        .await
+        .map_err(|_| tr!("install-no-grammar-json"))?;
+    let grammar: GrammarJson = serde_json::from_str(&json)
+        .map_err(|e| tr!("install-broken-grammar-json", why = e.to_string()))?;
+    if valid_name(&grammar.name) {
+        Ok(grammar.name)
+    } else {
+        Err(tr!(
+            "install-weird-name",
+            name = format!("{:?}", grammar.name)
+        ))
+    }
+}
+
+async fn exists(path: &Path) -> bool {
+    tokio::fs::metadata(path).await.is_ok()
+}
+
+// The hash of the compiled library, see library_name
+async fn compile(dir: &Path) -> Result<u64, String> {
+    let src = dir.join("src");
+    if exists(&src.join("scanner.cc")).await {
+        return Err(tr!("install-cpp-scanner"));
+    }
+    let compiler = cc::Build::new()
+        .cargo_metadata(false)
+        .target(TARGET)
+        .host(TARGET)
+        .opt_level(2)
+        .debug(false)
+        .warnings(false)
+        .try_get_compiler()
+        .map_err(|e| tr!("install-no-compiler", why = e.to_string()))?;
+    let mut command = sandboxed(dir, false, compiler.path())?;
+    command
+        .args(compiler.args())
+        .arg("-shared")
+        .arg("-fPIC")
+        .arg("-I")
+        .arg(&src)
+        .arg(src.join("parser.c"));
+    if exists(&src.join("scanner.c")).await {
+        command.arg(src.join("scanner.c"));
+    }
+    command.arg("-o").arg(dir.join(BUILD_OUTPUT));
+    run(&mut command, &tr!("install-step-compiling")).await?;
+
+    let library = tokio::fs::read(dir.join(BUILD_OUTPUT))
+        .await
+        .map_err(|e| tr!("install-unreadable", why = e.to_string()))?;
+    let library = {
+        let mut hasher = DefaultHasher::new();
+        library.hash(&mut hasher);
+        hasher.finish()
+    };
+    tokio::fs::rename(dir.join(BUILD_OUTPUT), dir.join(library_name(library)))
+        .await
+        .map_err(|e| tr!("install-unrenamable", why = e.to_string()))?;
+    Ok(library)
+}
+
+fn load(guild_id: u64, name: &str, installed: &InstalledGrammar) -> Result<LanguageConfig, String> {
+    let dir = grammar_dir(guild_id, name);
+    let source = GrammarSource::Dylib {
+        url: installed.url.clone(),
+        library: installed.library,
+    };
+    let language = unsafe {
+        let library = Library::new(dir.join(library_name(installed.library)))
+            .map_err(|e| tr!("install-unloadable", why = e.to_string()))?;
+        let symbol = format!("tree_sitter_{}", installed.grammar.replace('-', "_"));
+        let language = {
+            let constructor: Symbol<unsafe extern "C" fn() -> Language> = library
+                .get(symbol.as_bytes())
+                .map_err(|_| tr!("install-no-symbol", symbol = symbol.as_str()))?;
+            constructor()
+        };
+        // the Language points into the library, so it can never be unloaded
+        std::mem::forget(library);
+        language
+    };
+    let version = language.version();
+    if !(tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION..=tree_sitter::LANGUAGE_VERSION)
+        .contains(&version)
+    {
+        return Err(tr!(
+            "install-abi",
+            version = version,
+            min = tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+            max = tree_sitter::LANGUAGE_VERSION,
+        ));
+    }
+    let query = fs::read_to_string(dir.join("queries").join("highlights.scm"))
+        .map_err(|_| tr!("install-no-highlights"))?;
+    languages::standard_config(name, &installed.grammar, source, language, &query)
+}
+
+// A grammar installed in that guild, if there is one with that name
+pub fn guild_language(guild_id: GuildId, lang: &str) -> Option<Arc<LanguageConfig>> {
+    let lang = lang.to_lowercase();
+    let key = (guild_id.0, lang.clone());
+    if let Some(config) = LOADED.lock().unwrap().get(&key) {
+        return Some(config.clone());
+    }
+    let installed = storage::read(|data| {
+        data.guilds
+            .get(&guild_id.0)
+            .and_then(|guild| guild.grammars.get(&lang))
+            .cloned()
+    })?;
+    match load(guild_id.0, &lang, &installed) {
+        Ok(config) => {
+            let config = Arc::new(config);
+            LOADED.lock().unwrap().insert(key, config.clone());
+            Some(config)
+        }
+        Err(why) => {
+            error!("the {lang} grammar in guild {guild_id} didn't load: {why}");
+            None
+        }
+    }
+}
+
+// Every grammar installed in that guild that loads, sorted by name
+pub fn guild_languages(guild_id: GuildId) -> Vec<Arc<LanguageConfig>> {
+    let mut names = storage::read(|data| {
+        data.guilds
+            .get(&guild_id.0)
+            .map(|guild| guild.grammars.keys().cloned().collect::<Vec<_>>())
+            .unwrap_or_default()
+    });
+    names.sort();
+    names
+        .iter()
+        .filter_map(|name| guild_language(guild_id, name))
+        .collect()
+}
+
+// The error is meant to be shown to whoever is installing it
+pub async fn install(guild_id: GuildId, name: &str, url: &str) -> Result<(), String> {
+    let name = name.to_lowercase();
+    if !valid_name(&name) {
+        return Err(tr!("install-bad-name"));
+    }
+    if get_language(&name).is_some() {
+        return Err(tr!("install-built-in", name = name));
+    }
+    if !url.starts_with("https://") {
+        return Err(tr!("install-https-only"));
+    }
+    let dir = grammar_dir(guild_id.0, &name);
+    match tokio::fs::remove_dir_all(&dir).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(tr!("install-not-removed", why = e.to_string()))
+        }
+        _ => {}
+    }
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| tr!("install-not-created", why = e.to_string()))?;
+    // the sandbox needs the whole path
+    let dir = tokio::fs::canonicalize(&dir)
+        .await
+        .map_err(|e| tr!("install-not-found", why = e.to_string()))?;
+
+    run(
+        sandboxed(&dir, true, "git")?
+            .arg("clone")
+            .arg("--depth=1")
+            .arg("--")
+            .arg(url)
+            .arg(&dir),
+        &tr!("install-step-cloning"),
+    )
+    .await?;
+    let grammar = grammar_name(&dir).await?;
+    let library = compile(&dir).await?;
+
+    let installed = InstalledGrammar {
+        url: url.to_owned(),
+        grammar,
+        library,
+    };
+    // this also validates the highlight query, so nothing gets saved if it doesn't work
+    let config = tokio::task::spawn_blocking({
+        let name = name.clone();
+        let installed = installed.clone();
+        let locale = locale::current();
+        move || locale::sync_scope(locale, || load(guild_id.0, &name, &installed))
+    })
+    .await
+    .map_err(|e| tr!("install-join", why = format!("{e:?}")))??;
+
+    storage::write(|data| {
+        data.guilds
+            .entry(guild_id.0)
+            .or_default()
+            .grammars
+            .insert(name.clone(), installed)
+    });
+    LOADED
+        .lock()
+        .unwrap()
+        .insert((guild_id.0, name), Arc::new(config));
+    Ok(())
+}
+
+pub async fn uninstall(guild_id: GuildId, name: &str) -> Result<(), String> {
+    let name = name.to_lowercase();
+    let removed = storage::write(|data| {
+        data.guilds
+            .get_mut(&guild_id.0)
+            .and_then(|guild| guild.grammars.remove(&name))
+    });
+    if removed.is_none() {
+        return Err(tr!("install-not-installed", name = name));
+    }
+    LOADED.lock().unwrap().remove(&(guild_id.0, name.clone()));
+    // the library itself stays loaded until the bot restarts, but the files can go
+    if let Err(e) = tokio::fs::remove_dir_all(grammar_dir(guild_id.0, &name)).await {
+        error!("{e:?}");
+    }
+    Ok(())
+}
@@ -0,0 +1,87 @@
+// The registry itself lives in custom-highlight-core, this is everything that depends on a guild
+use super::*;
+pub use custom_highlight_core::languages::*;
+
+lazy_static! {
+    // compiled per-guild query overrides, keyed by (guild, language name)
+    static ref GUILD_OVERRIDES: std::sync::Mutex<HashMap<(u64, String), Arc<LanguageConfig>>> =
+        Default::default();
+}
+
+// The language as it should be highlighted in that guild, which is usually just the built-in one
+pub fn for_guild(guild_id: Option<GuildId>, config: Arc<LanguageConfig>) -> Arc<LanguageConfig> {
+    let guild_id = match guild_id {
+        Some(guild_id) => guild_id.0,
+        None => return config,
+    };
+    let key = (guild_id, config.name.clone());
+    if let Some(compiled) = GUILD_OVERRIDES.lock().unwrap().get(&key) {
+        return compiled.clone();
+    }
+    let query = storage::read(|data| {
+        data.guilds
+            .get(&guild_id)
+            .and_then(|guild| guild.queries.get(&config.name))
+            .cloned()
+    });
+    match query.map(|query| config.with_query(&query)) {
+        None => config,
+        Some(Ok(compiled)) => {
+            let compiled = Arc::new(compiled);
+            GUILD_OVERRIDES
+                .lock()
+                .unwrap()
+                .insert(key, compiled.clone());
+            compiled
+        }
+        Some(Err(e)) => {
+            // it compiled when it was uploaded, so the grammar must have changed since then
+            error!(
+                "the {} query for guild {guild_id} no longer compiles: {e}",
+                config.name
+            );
+            config
+        }
+    }
+}
+
+// None resets the language back to the built-in query
+pub fn set_guild_query(
+    guild_id: GuildId,
+    config: &LanguageConfig,
+    query: Option<String>,
+) -> Result<(), String> {
+    let key = (guild_id.0, config.name.clone());
+    match query {
+        Some(query) => {
+            let compiled = Arc::new(config.with_query(&query)?);
+            storage::write(|data| {
+                data.guilds
+                    .entry(guild_id.0)
+                    .or_default()
+                    .queries
+                    .insert(config.name.clone(), query)
+            });
+            GUILD_OVERRIDES.lock().unwrap().insert(key, compiled);
+        }
+        None => {
+            storage::write(|data| {
+                data.guilds
+                    .get_mut(&guild_id.0)
+                    .and_then(|guild| guild.queries.remove(&config.name))
+            });
+            GUILD_OVERRIDES.lock().unwrap().remove(&key);
+        }
+    }
+    Ok(())
+}
+
+// Swaps out the whole registry at once, so anything already running keeps the config it started with.
+// Returns how many languages there are now.
+pub fn reload() -> Result<usize, String> {
+    let languages = load()?;
+    *LANGUAGES.write().unwrap() = languages;
+    // these were compiled from the old configs
+    GUILD_OVERRIDES.lock().unwrap().clear();
+    Ok(all_languages().len())
+}
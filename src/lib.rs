@@ -0,0 +1,3 @@
+// The few parts of the bot that make sense on their own, so fuzz/ and tests/ can get at them.
+// Everything else is in the binary, see main.rs
+pub mod custom_ids;
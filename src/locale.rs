@@ -0,0 +1,152 @@
+use std::fs;
+
+use super::*;
+use fluent::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use unic_langid::LanguageIdentifier;
+
+// Translations of what the bot says, see locales/en-US.ftl. en-US is built in, and every other translation is read
+// from locales/<locale>.ftl at startup, named like the locale discord sends with every interaction (de, pt-BR, ...).
+// Anything a translation doesn't have falls back to en-US, so a translation can be done a bit at a time
+const LOCALES_DIR: &str = "locales";
+pub const FALLBACK: &str = "en-US";
+
+lazy_static! {
+    static ref BUNDLES: HashMap<String, FluentBundle<FluentResource>> = load();
+}
+
+// Which language tr! and owo! use. Like tone::scope, every event is handled inside scope() with
+// the locale of whoever it's for, and workers::run carries it over to the worker.
+// Anything outside of that just gets en-US
+tokio::task_local! {
+    static LOCALE: String;
+}
+
+pub async fn scope<F: std::future::Future>(locale: String, f: F) -> F::Output {
+    LOCALE.scope(locale, f).await
+}
+
+// for spawn_blocking, which task locals don't carry over to
+pub fn sync_scope<R>(locale: String, f: impl FnOnce() -> R) -> R {
+    LOCALE.sync_scope(locale, f)
+}
+
+pub fn current() -> String {
+    LOCALE
+        .try_with(Clone::clone)
+        .unwrap_or_else(|_| FALLBACK.to_owned())
+}
+
+// discord says which language whoever used an interaction has it in
+pub fn of(interaction: &Interaction) -> String {
+    match interaction {
+        Interaction::ApplicationCommand(interaction) => interaction.locale.clone(),
+        Interaction::MessageComponent(interaction) => interaction.locale.clone(),
+        Interaction::ModalSubmit(interaction) => interaction.locale.clone(),
+        Interaction::Autocomplete(interaction) => interaction.locale.clone(),
+        _ => FALLBACK.to_owned(),
+    }
+}
+
+// Messages and reactions don't say, so it's whatever the server has set as its language
+pub fn of_guild(ctx: &Context, guild_id: Option<GuildId>) -> String {
+    guild_id
+        .and_then(|guild_id| {
+            ctx.cache
+                .guild_field(guild_id, |guild| guild.preferred_locale.clone())
+        })
+        .unwrap_or_else(|| FALLBACK.to_owned())
+}
+
+// called at startup, so a broken translation is complained about right away
+pub fn start() {
+    lazy_static::initialize(&BUNDLES);
+}
+
+fn bundle(locale: &str, source: String) -> Option<FluentBundle<FluentResource>> {
+    let id = match locale.parse::<LanguageIdentifier>() {
+        Ok(id) => id,
+        Err(why) => {
+            error!("{locale}: {why}");
+            return None;
+        }
+    };
+    // whatever did parse is still usable
+    let resource = FluentResource::try_new(source).unwrap_or_else(|(resource, errors)| {
+        error!("{locale}: {errors:?}");
+        resource
+    });
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // the unicode isolation marks around arguments show up as boxes in some discord clients
+    bundle.set_use_isolating(false);
+    if let Err(errors) = bundle.add_resource(resource) {
+        error!("{locale}: {errors:?}");
+    }
+    Some(bundle)
+}
+
+fn load() -> HashMap<String, FluentBundle<FluentResource>> {
+    let mut bundles = HashMap::new();
+    let fallback = include_str!("../locales/en-US.ftl").to_owned();
+    bundles.insert(FALLBACK.to_owned(), bundle(FALLBACK, fallback).unwrap());
+    let entries = match fs::read_dir(LOCALES_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return bundles,
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        let locale = match (path.file_stem().and_then(|stem| stem.to_str()), path.extension()) {
+            (Some(locale), Some(extension)) if extension == "ftl" && locale != FALLBACK => locale,
+            _ => continue,
+        };
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(why) => {
+                error!("{locale}: {why}");
+                continue;
+            }
+        };
+        if let Some(bundle) = bundle(locale, source) {
+            bundles.insert(locale.to_owned(), bundle);
+        }
+    }
+    bundles
+}
+
+// Every translation of id besides en-US, for the slash command descriptions, which discord
+// is given all at once and picks from itself
+pub fn translations(id: &str) -> Vec<(String, String)> {
+    let mut translations = BUNDLES
+        .iter()
+        .filter(|(locale, bundle)| *locale != FALLBACK && bundle.has_message(id))
+        .map(|(locale, _)| (locale.clone(), translate(locale, id, &[])))
+        .collect::<Vec<_>>();
+    // the order they're registered in shouldn't change between restarts
+    translations.sort();
+    translations
+}
+
+// see tr!, which is what everything else uses
+pub fn translate(locale: &str, id: &str, args: &[(&str, FluentValue)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (key, value) in args {
+        fluent_args.set(*key, value.clone());
+    }
+    // pt-BR can use a pt translation if there's no pt-BR one
+    let language = locale.split('-').next().unwrap_or(locale);
+    for locale in [locale, language, FALLBACK] {
+        let bundle = match BUNDLES.get(locale) {
+            Some(bundle) => bundle,
+            None => continue,
+        };
+        if let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) {
+            let mut errors = Vec::new();
+            let text = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+            if !errors.is_empty() {
+                error!("{locale} {id}: {errors:?}");
+            }
+            return text.into_owned();
+        }
+    }
+    // it's not in en-US either, which is a typo in the id
+    error!("no translation for {id}");
+    id.to_owned()
+}
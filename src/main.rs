@@ -1,19 +1,22 @@
-mod render;
-use std::{collections::HashMap, fmt::Debug, iter, sync::Arc};
+use std::{borrow::Cow, collections::HashMap, iter, sync::Arc};
 
-use const_format::concatcp;
-use hex_literal::hex;
-use image::{codecs::png, ColorType, ImageEncoder, Rgb};
+use commands::Command;
+use custom_highlight::custom_ids;
+use custom_highlight_core::markdown::{codeblock, codeblocks, fence};
+// the highlighting and rendering itself, see core/src/lib.rs
+use custom_highlight_core::*;
+use error::{CommandError, OrLog};
 use lazy_static::lazy_static;
-use non_empty_vec::ne_vec;
 use owoify_rs::{Owoifiable, OwoifyLevel};
-use render::render_command;
+use regex::Regex;
+use render::{render_command, RenderOptions};
 use serenity::{
     async_trait,
     builder::{
-        CreateInteractionResponse, CreateInteractionResponseFollowup,
-        CreateMessage,
+        CreateActionRow, CreateComponents, CreateInteractionResponse,
+        CreateInteractionResponseFollowup, CreateMessage,
     },
+    client::bridge::gateway::event::ShardStageUpdateEvent,
     model::{
         application::{
             // these are aliases as the old name not because i'm lazy when updating for deprecations
@@ -26,216 +29,180 @@ use serenity::{
                 InteractionResponseType,
             },
         },
-        channel::{Channel, Message},
+        channel::{Channel, Message, Reaction},
+        event::MessageUpdateEvent,
         gateway::Ready,
-        id::{MessageId, UserId},
+        id::{ChannelId, GuildId, MessageId, RoleId, UserId},
+        user::User,
         Permissions,
     },
     prelude::*,
 };
-use tree_sitter::{Language, Parser, TreeCursor};
-use tree_sitter_highlight::{Highlight, HighlightConfiguration, HighlightEvent, Highlighter};
-use unicode_normalization::UnicodeNormalization;
+use pages::Page;
+use theme::Theme;
+use tracing::{debug, debug_span, error, info, info_span, warn, Instrument, Span};
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+use tree_sitter::{Language, Tree, TreeCursor};
+use workers::Task;
 
+// An error message, in whoever's language and in the server's tone, like owo!("unknown-command", name = name).
+// The same as tr! otherwise, see tone.rs
 macro_rules! owo {
-    ($($t:tt)*) => {
-        format!($($t)*)
-            .owoify(OwoifyLevel::Uvu)
-            .owoify(OwoifyLevel::Uvu)
-            .owoify(OwoifyLevel::Uvu)
-            .owoify(OwoifyLevel::Uvu)
+    ($id:literal $(, $key:ident = $value:expr)* $(,)?) => {
+        $crate::tone::apply(tr!($id $(, $key = $value)*))
     }
 }
 
-macro_rules! map {
-    (@key $name:literal) => { $name };
-    (@key $name:ident) => { stringify!($name) };
-    (@m $callback:ident ($($args:tt)*) $($k:tt => $v:expr),* $(,)?) => { $callback!($($args)* $((map!(@key $k), $v),)*) };
-    (@arr $($t:tt)*) => { [$($t)*] };
-    ($($t:tt)*) => { map!(@m map (@arr) $($t)*) };
-
-}
-macro_rules! unzip {
-    ($(($a:expr, $b:expr),)*) => {
-        (&[$($a),*], &[$($b),*])
-    };
-    ($($t:tt)*) => {
-        map!(@m unzip () $($t)*)
+// Text in whatever language whoever it's for has discord in, like tr!("help-example", language = name).
+// The id and the arguments are whatever locales/en-US.ftl calls them, see locale.rs. The language is
+// whatever locale::scope says, or it can be given first, like tr!(locale, "help-example", language = name)
+macro_rules! tr {
+    ($id:literal $(, $key:ident = $value:expr)* $(,)?) => {
+        tr!(&$crate::locale::current(), $id $(, $key = $value)*)
     };
+    ($locale:expr, $id:literal $(, $key:ident = $value:expr)* $(,)?) => {
+        $crate::locale::translate(
+            $locale,
+            $id,
+            &[$((stringify!($key), ::fluent::FluentValue::from($value))),*],
+        )
+    }
 }
 
-macro_rules! lang {
-    ($pkg:ident; $($t:tt)*) => {{
-        let language = $pkg::language();
-        let mut highlight = HighlightConfiguration::new(
-            language,
-            concatcp!("(ERROR) @error\n", $pkg::HIGHLIGHTS_QUERY),
-            "",
-            "",
-        ).unwrap();
-        let (recognized_names, formats): (&[&str], &[Color]) = unzip![error => ERROR, $($t)*];
-        highlight.configure(recognized_names);
-        LanguageConfig {
-            highlight: HighlightType::TreeSitter(highlight),
-            formats,
-            language: Some(language),
-        }
-    }};
-}
-
-pub trait ErrAs<E> {
-    type Err;
-    fn err_as(self, err: E) -> Self::Err;
-}
-
-impl<T, E: Debug, U> ErrAs<U> for Result<T, E> {
-    type Err = Result<T, U>;
-    fn err_as(self, err: U) -> Result<T, U> {
-        match self {
-            Ok(ok) => Ok(ok),
-            Err(actual_err) => {
-                println!("Error: {actual_err:?}");
-                Err(err)
-            }
-        }
-    }
+// these use the macros above, so they have to be declared after them
+mod attachments;
+mod blocks;
+mod buttons;
+mod cache;
+mod commands;
+mod cooldown;
+mod detect;
+mod diff;
+mod error;
+mod expand;
+mod expire;
+mod graph;
+mod health;
+mod help;
+mod install;
+mod languages;
+mod locale;
+mod menus;
+mod modal;
+mod pages;
+mod picker;
+mod query;
+mod queue;
+mod reactions;
+mod render;
+mod reparse;
+mod rerun;
+mod shutdown;
+mod slash;
+mod stats;
+mod storage;
+mod theme;
+mod tone;
+mod usage;
+mod webhook;
+mod workers;
+
+lazy_static! {
+    // behind a lock so /reload-languages can swap it out, see languages::reload
+    static ref LANGUAGES: std::sync::RwLock<HashMap<String, Arc<LanguageConfig>>> =
+        std::sync::RwLock::new(languages::load().unwrap_or_else(|e| panic!("{e}")));
 }
 
-pub const TS_ERROR: &str = "internal error from tree-sitter (not a syntax error)";
+// fences are case-insensitive, so ```URCL and ```urcl are the same thing
+// all the keys in LANGUAGES are lowercase for this to work
+fn get_language(lang: &str) -> Option<Arc<LanguageConfig>> {
+    LANGUAGES
+        .read()
+        .unwrap()
+        .get(&lang.to_lowercase())
+        .cloned()
+}
 
-enum HighlightType {
-    TreeSitter(HighlightConfiguration),
-    Plaintext,
+// every language once, without the aliases (or plaintext)
+fn all_languages() -> Vec<Arc<LanguageConfig>> {
+    let mut languages = LANGUAGES
+        .read()
+        .unwrap()
+        .values()
+        .filter(|config| !config.name.is_empty())
+        .cloned()
+        .collect::<Vec<_>>();
+    languages.sort_by(|a, b| a.name.cmp(&b.name));
+    languages.dedup_by(|a, b| a.name == b.name);
+    languages
 }
 
-pub struct LanguageConfig {
-    highlight: HighlightType,
-    formats: &'static [Color],
-    language: Option<Language>,
+// the built-in languages, and then the ones installed in that guild with /grammar install
+fn get_guild_language(guild_id: Option<GuildId>, lang: &str) -> Option<Arc<LanguageConfig>> {
+    get_language(lang).or_else(|| install::guild_language(guild_id?, lang))
 }
 
-#[derive(Clone, Copy, Debug)]
-struct Color {
-    ansi: &'static str,
-    rgb: Rgb<u8>,
-}
-
-macro_rules! colors {
-    ($($name:ident = $value:literal, $hex:literal)*) => {
-        $(const $name: Color = Color { ansi: concat!("\u{001b}[", $value, "m"), rgb: Rgb(hex!($hex)) };)*
-    }
-}
-
-// Note that there are not ANSI names, they are names that fit the specific colors
-// discord uses for the relevant ansi code (and also the hex codes discord uses for them)
-//
-// ERROR is just #FF0000 because that's distinct from RED's color
-// the same way with ANSI it uses underlines to be distinct from RED
-//
-// Some of these are using bold and other styles to achieve a finer granularity of styles
-// The renderer does not support these styles, so i'm using colors from dark_vs to make them
-// look distinct when rendereing
-colors! {
-    ERROR = "31;4", "ff0000"
-    RESET = 0, "b9bbbe"
-    GRAY = 30, "4f545c"
-    RED = 31, "dc322f"
-    LIGHT_GREEN = 32, "b5cea8" // dark_vs constant.numeric
-    DARK_GREEN = "32;1", "6a9955" // dark_vs comment
-    YELLOW = 33, "b58900"
-    BLUE = 34, "268bd2"
-    DARK_BLUE = "34;1", "569cd6" // dark_vs constant.language
-    PINK = 35, "d33682"
-    CYAN = 36, "2aa198"
-    WHITE = 37, "ffffff"
+// RUST_LOG (which can go in config.toml too, see config.rs in core) picks what's logged, like RUST_LOG=debug or RUST_LOG=custom_highlight=debug,warn.
+// By default it's info from the bot itself and only warnings from everything else.
+// LOG_FORMAT=json logs one JSON object per line instead, for whatever collects the logs.
+// Spans are logged when they close, so every interaction and render stage says how long it took
+fn init_logging() {
+    let filter = config::var("RUST_LOG")
+        .and_then(|filter| EnvFilter::try_new(filter).ok())
+        .unwrap_or_else(|| EnvFilter::new("warn,custom_highlight=info,custom_highlight_core=info"));
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE);
+    match config::var("LOG_FORMAT").as_deref() {
+        Some("json") => subscriber.json().init(),
+        Some("compact") => subscriber.compact().init(),
+        _ => subscriber.init(),
+    }
 }
 
-lazy_static! {
-    static ref LANGUAGES: HashMap<&'static str, LanguageConfig> = HashMap::from(map![
-        "" => {
-            LanguageConfig {
-                highlight: HighlightType::Plaintext,
-                formats: &[],
-                language: None,
-            }
-        },
-        ursl => lang![tree_sitter_ursl;
-            comment => GRAY,
-            number => LIGHT_GREEN,
-            port => DARK_GREEN,
-            label => YELLOW,
-            "label.data" => YELLOW,
-            function => YELLOW,
-            macro => PINK,
-            address => DARK_BLUE,
-            register => CYAN,
-            string => CYAN,
-            "string.special" => CYAN,
-            instruction => BLUE,
-            property => RED,
-            keyword => PINK,
-            "punctuation.delimiter" => GRAY,
-            "punctuation.bracket" => GRAY,
-        ],
-        urcl => lang![tree_sitter_urcl;
-            comment => GRAY,
-            header => PINK,
-            constant => YELLOW,
-            number => LIGHT_GREEN,
-            relative => LIGHT_GREEN,
-            port => DARK_GREEN,
-            macro => PINK,
-            label => YELLOW,
-            register => CYAN,
-            "register.special" => CYAN,
-            address => DARK_BLUE,
-            instruction => BLUE,
-            string => CYAN,
-            "string.special" => BLUE,
-            operator => GRAY,
-            "punctuation.bracket" => GRAY,
-            identifier => WHITE,
-            "identifier.placeholder" => WHITE,
-        ],
-        phinix => lang![tree_sitter_phinix;
-            comment => GRAY,
-            segment => RED,
-            param => DARK_GREEN,
-            label => YELLOW,
-            number => LIGHT_GREEN,
-            keyword => PINK,
-        ],
-        hexagn => lang![tree_sitter_hexagn;
-            comment => GRAY,
-            number => LIGHT_GREEN,
-            func_name => YELLOW,
-            keyword => PINK,
-            type => DARK_GREEN,
-        ],
-    ]);
+// DISCORD_TOKEN, or the token file it used to be compiled in from, so old setups still work
+fn token() -> String {
+    config::var("DISCORD_TOKEN")
+        .or_else(|| std::fs::read_to_string("token").ok())
+        .map(|token| token.trim().to_owned())
+        .expect("Error finding the token, set DISCORD_TOKEN or put discord_token in config.toml")
 }
 
 #[tokio::main]
 async fn main() {
-    let token = include_str!("../token");
+    config::load();
+    init_logging();
+    // load the languages up front so a broken languages.toml fails at startup, not on the first message
+    lazy_static::initialize(&LANGUAGES);
+    storage::start();
+    usage::start();
+    locale::start();
     let intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
-    let mut client = Client::builder(token, intents)
+    let mut client = Client::builder(token(), intents)
         .event_handler(Handler)
+        .raw_event_handler(health::Events)
         .await
         .expect("Error creating client");
-    if let Err(why) = client.start().await {
-        println!("An error occurred while running the client: {why:?}");
+    health::start(client.shard_manager.clone());
+    shutdown::start(client.shard_manager.clone());
+    // discord makes bots in enough servers split the gateway connection up into shards,
+    // and this asks discord how many there should be
+    if let Err(why) = client.start_autosharded().await {
+        error!("An error occurred while running the client: {why:?}");
     }
 }
 
 struct Handler;
 
-async fn get_ref(ctx: &Context, channel: &Channel, message_id: MessageId) -> Message {
-    match channel {
-        Channel::Guild(channel) => channel.message(ctx, message_id).await.unwrap(),
-        Channel::Private(channel) => channel.message(ctx, message_id).await.unwrap(),
-        _ => panic!("bad channel"),
-    }
+// threads, forum posts, announcement channels and the text chat of voice and stage channels
+// are all Channel::Guild, so going by the id works for anywhere a codeblock can be
+// None when it's not there anymore, like when it was deleted in the meantime
+async fn get_ref(ctx: &Context, channel: &Channel, message_id: MessageId) -> Option<Message> {
+    channel
+        .id()
+        .message(ctx, message_id)
+        .await
+        .or_log("get the message it's for")
 }
 
 async fn send<'a>(
@@ -243,58 +210,348 @@ async fn send<'a>(
     channel: &Channel,
     f: impl for<'b> FnOnce(&'b mut CreateMessage<'a>) -> &'b mut CreateMessage<'a>,
 ) -> serenity::Result<Message> {
-    match channel {
-        Channel::Guild(c) => c.send_message(&ctx, f).await,
-        Channel::Private(c) => c.send_message(&ctx, f).await,
-        &_ => panic!("bad channel"),
+    channel.id().send_message(&ctx, f).await
+}
+
+// Threads (forum posts are threads too) count as the channel they're in for /server channels and
+// /server autorespond, so a thread in a channel on the list is on it too
+fn listed_channel(ctx: &Context, guild_id: Option<GuildId>, channel: ChannelId) -> ChannelId {
+    guild_id
+        .and_then(|guild_id| {
+            ctx.cache.guild_field(guild_id, |guild| {
+                let thread = guild.threads.iter().find(|thread| thread.id == channel)?;
+                thread.parent_id
+            })
+        })
+        .flatten()
+        .unwrap_or(channel)
+}
+
+// Which of these the bot doesn't have in a channel. A thread goes by the channel it's in, and anything
+// that isn't in the cache (like DMs) is assumed to be fine, since the send can only fail like it used to
+fn missing_permissions(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    channel: ChannelId,
+    needed: Permissions,
+) -> Permissions {
+    let listed = listed_channel(ctx, guild_id, channel);
+    let needed = if listed != channel && needed.contains(Permissions::SEND_MESSAGES) {
+        needed - Permissions::SEND_MESSAGES | Permissions::SEND_MESSAGES_IN_THREADS
+    } else {
+        needed
+    };
+    let has = ctx
+        .cache
+        .guild_channel(listed)
+        .and_then(|listed| listed.permissions_for_user(ctx, ctx.cache.current_user_id()).ok());
+    match has {
+        Some(has) => needed - has,
+        None => Permissions::empty(),
     }
 }
 
+// Whether a reply here can have this
+fn reply_can(
+    ctx: &Context,
+    channel: &Channel,
+    reply_to: ReplyMethod<'_>,
+    needed: Permissions,
+) -> bool {
+    let guild_id = match channel {
+        Channel::Guild(channel) => Some(channel.guild_id),
+        _ => None,
+    };
+    match reply_to {
+        ReplyMethod::PublicReference(_) => {
+            missing_permissions(ctx, guild_id, channel.id(), needed).is_empty()
+        }
+        // followups don't need any, they're sent through the interaction
+        ReplyMethod::EphemeralFollowup(_) | ReplyMethod::PublicFollowup(_) => true,
+    }
+}
+
+// Whether the bot can reply to a message nobody asked it about. Not to other bots,
+// not where the server turned it off, and not to anyone who used /optout
+fn unprompted(ctx: &Context, message: &Message) -> bool {
+    let channel = listed_channel(ctx, message.guild_id, message.channel_id);
+    !message.author.bot
+        && storage::auto_responds(message.guild_id, channel)
+        && !storage::opted_out(message.author.id)
+}
+
+// Whether someone can run a command right now. If the server picked roles for it with /server permissions
+// they need one of those, and they can't have used it too much lately, see cooldown.rs
+fn may_run(
+    guild_id: Option<GuildId>,
+    user: UserId,
+    roles: &[RoleId],
+    command: Command,
+) -> Result<(), String> {
+    let name = command.id();
+    let allowed = storage::guild_setting(guild_id, |guild| guild.command_roles.get(name).cloned())
+        .unwrap_or_default();
+    if !allowed.is_empty() && !roles.iter().any(|role| allowed.contains(&role.0)) {
+        let roles = allowed
+            .iter()
+            .map(|id| format!("<@&{id}>"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(tr!("not-allowed", roles = roles, command = name));
+    }
+    cooldown::take(user, guild_id, command)
+}
+
+// normalize newlines to \n
+fn normalize_newlines(content: &str) -> String {
+    let mut content = content
+        .lines()
+        .fold(String::from("\n"), |out, line| out + line + "\n");
+    // trim trailing newline
+    content.pop();
+    content
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum ReplyMethod<'a> {
     PublicReference(&'a Message),
     EphemeralFollowup(&'a Interaction),
+    // there's no message to reply to, like with /scratchpad, so discord says who it's for
+    PublicFollowup(&'a Interaction),
 }
 
+impl ReplyMethod<'_> {
+    fn ephemeral(self) -> bool {
+        matches!(self, ReplyMethod::EphemeralFollowup(_))
+    }
+}
+
+// components go under it too, see parse_components
 async fn send_chunked_message_with_commands(
     ctx: &Context,
     channel: &Channel,
     chunks: Vec<String>,
     reply_to: ReplyMethod<'_>,
-) -> serenity::Result<()> {
-    let first = 0;
-    for i in 0..chunks.len() {
-        let chunk = &chunks[i];
-        match reply_to {
-            ReplyMethod::PublicReference(reply_to) => send(&ctx, channel, |msg| {
-                if i == first {
-                    msg.reference_message(reply_to)
-                        .allowed_mentions(|f| f.replied_user(false));
+    components: Option<CreateComponents>,
+) -> serenity::Result<Vec<Message>> {
+    let pages = chunks.into_iter().map(Page::Content).collect();
+    send_pages(ctx, channel, pages, reply_to, components).await
+}
+
+// Only the first page is sent, with buttons under it for the rest, see pages.rs
+async fn send_pages(
+    ctx: &Context,
+    channel: &Channel,
+    pages: Vec<Page>,
+    reply_to: ReplyMethod<'_>,
+    components: Option<CreateComponents>,
+) -> serenity::Result<Vec<Message>> {
+    let shown = if pages.len() > 1 {
+        Some(pages::components(pages.len(), 0, components.as_ref()))
+    } else {
+        components.clone()
+    };
+    let first = &pages[0];
+    let message = match reply_to {
+        ReplyMethod::PublicReference(reply_to) => {
+            send(&ctx, channel, |msg| {
+                msg.reference_message(reply_to)
+                    .allowed_mentions(|f| f.replied_user(false));
+                if let Some(ref shown) = shown {
+                    msg.set_components(shown.clone());
+                }
+                match first {
+                    Page::Content(content) => msg.content(content),
+                    Page::Embeds(embeds) => msg.add_embeds(pages::embeds(embeds)),
                 }
-                msg.content(&chunk)
+            })
+            .await?
+        }
+        ReplyMethod::EphemeralFollowup(interaction) | ReplyMethod::PublicFollowup(interaction) => {
+            create_followup_message(ctx, interaction, |msg| {
+                if let Some(ref shown) = shown {
+                    msg.set_components(shown.clone());
+                }
+                msg.ephemeral(reply_to.ephemeral());
+                match first {
+                    Page::Content(content) => msg.content(content),
+                    Page::Embeds(embeds) => msg.add_embeds(pages::embeds(embeds)),
+                }
+            })
+            .await?
+        }
+    };
+    if pages.len() > 1 {
+        pages::remember(message.id, pages, components);
+    }
+    Ok(vec![message])
+}
+
+async fn send_files(
+    ctx: &Context,
+    channel: &Channel,
+    reply_to: ReplyMethod<'_>,
+    content: &str,
+    files: &[(&[u8], &str)],
+) -> Result<Message, CommandError> {
+    let sent = match reply_to {
+        ReplyMethod::PublicReference(referenced) => send(ctx, channel, |msg| {
+            msg.reference_message(referenced)
+                .allowed_mentions(|mentions| mentions.replied_user(false))
+                .content(content)
+                .add_files(files.iter().copied())
+        })
+        .await,
+        ReplyMethod::EphemeralFollowup(interaction) | ReplyMethod::PublicFollowup(interaction) => {
+            create_followup_message(ctx, interaction, |msg| {
+                msg.ephemeral(reply_to.ephemeral())
+                    .content(content)
+                    .add_files(files.iter().copied())
             })
             .await
-            .unwrap(),
-            ReplyMethod::EphemeralFollowup(reply_to) => {
-                create_followup_message(ctx, reply_to, |msg| msg.ephemeral(true).content(&chunk))
-                    .await
-                    .unwrap()
-            }
+        }
+    };
+    sent.map_err(CommandError::discord("discord-send-files"))
+}
+
+// Every page as its own message, in a thread on the message it's for, so the channel only gets the thread.
+// Running it again (like after an edit) uses the same thread
+async fn send_thread(
+    ctx: &Context,
+    referenced: &Message,
+    pages: &[Page],
+) -> serenity::Result<Vec<Message>> {
+    // a thread started on a message has the same id as the message, so this finds the one from last time
+    let existing = ChannelId(referenced.id.0);
+    let thread = match existing.to_channel(ctx).await {
+        Ok(_) => existing,
+        Err(_) => {
+            let name = tr!("thread-name", author = referenced.author.name.as_str());
+            referenced
+                .channel_id
+                .create_public_thread(&ctx, referenced.id, |thread| thread.name(name))
+                .await?
+                .id
+        }
+    };
+    let mut sent = Vec::with_capacity(pages.len());
+    for page in pages {
+        let message = thread
+            .send_message(&ctx, |msg| match page {
+                Page::Content(content) => msg.content(content),
+                Page::Embeds(embeds) => msg.add_embeds(pages::embeds(embeds)),
+            })
+            .await?;
+        sent.push(message);
+    }
+    Ok(sent)
+}
+
+// past this many pages, ANSI is sent as a file instead
+const MAX_ANSI_PAGES: usize = 16;
+// An embed can have twice as much text as a message, and a message can have a few embeds.
+// The total can't be more than 6000 though, so two of these always fit on a page
+const MAX_EMBED_LENGTH: usize = 3000;
+const EMBEDS_PER_PAGE: usize = 2;
+
+lazy_static! {
+    static ref ANSI_ESCAPE: Regex = Regex::new("\u{001b}\\[[0-9;]*m").unwrap();
+}
+
+// ANSI in codeblocks, or as a file if that'd take too many pages or a line doesn't fit in one.
+// The file comes with a plain text version, since not much outside of discord understands ANSI.
+// embed packs the codeblocks into embeds instead, which fits about three times as much on a page
+async fn send_ansi(
+    ctx: &Context,
+    channel: &Channel,
+    ansi: &str,
+    options: &Options,
+    reply_to: ReplyMethod<'_>,
+    components: Option<CreateComponents>,
+) -> Result<Vec<Message>, CommandError> {
+    let guild_id = match channel {
+        Channel::Guild(channel) => Some(channel.guild_id),
+        _ => None,
+    };
+    let tab_width = options.tab_width.unwrap_or(DEFAULT_TAB_WIDTH);
+    let spoiler = options.spoiler;
+    // without embed links they'd just be empty messages
+    let embed = options.embed && reply_can(ctx, channel, reply_to, Permissions::EMBED_LINKS);
+    let max_length = if embed { MAX_EMBED_LENGTH } else { 2000 };
+    if let Ok(chunks) = chunk_ansi(ansi, tab_width, spoiler, max_length) {
+        let pages = if embed {
+            chunks
+                .chunks(EMBEDS_PER_PAGE)
+                .map(|embeds| Page::Embeds(embeds.to_vec()))
+                .collect::<Vec<_>>()
+        } else {
+            chunks.into_iter().map(Page::Content).collect()
         };
+        let thread = options.thread.unwrap_or_else(|| {
+            storage::guild_setting(guild_id, |guild| guild.threads).unwrap_or(false)
+        });
+        if let (true, true, ReplyMethod::PublicReference(referenced)) =
+            (thread, pages.len() > 1, reply_to)
+        {
+            // no permission to make threads, or it's in a thread already, so it's just pages then
+            match send_thread(ctx, referenced, &pages).await {
+                Ok(sent) => return Ok(sent),
+                Err(why) => error!("{why}"),
+            }
+        }
+        if pages.len() <= MAX_ANSI_PAGES {
+            let sent = send_pages(ctx, channel, pages, reply_to, components).await;
+            return sent.map_err(CommandError::discord("discord-send-output"));
+        }
+    }
+    let expanded = ansi
+        .split('\n')
+        .map(|line| Tabs::new(tab_width).expand(line))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let plain = ANSI_ESCAPE.replace_all(&expanded, "");
+    if expanded.len() + plain.len() > *render::UPLOAD_LIMIT {
+        return Err(tr!("ansi-too-big").into());
     }
-    Ok(())
+    if !reply_can(ctx, channel, reply_to, Permissions::ATTACH_FILES) {
+        return Err(owo!("ansi-no-files").into());
+    }
+    // discord decides what's a spoiler by the file name
+    let prefix = if spoiler { render::SPOILER_PREFIX } else { "" };
+    let (ansi_name, plain_name) = (format!("{prefix}code.ansi"), format!("{prefix}code.txt"));
+    let sent = send_files(
+        ctx,
+        channel,
+        reply_to,
+        &tr!("ansi-as-file"),
+        &[(expanded.as_bytes(), &ansi_name), (plain.as_bytes(), &plain_name)],
+    )
+    .await?;
+    Ok(vec![sent])
 }
 
-fn chunk_ansi(content: &str) -> Result<Vec<String>, &'static str> {
+// spoiler wraps every chunk in ||spoiler tags|| too, for channels where people post solutions
+fn chunk_ansi(
+    content: &str,
+    tab_width: usize,
+    spoiler: bool,
+    max_length: usize,
+) -> Result<Vec<String>, String> {
+    let (open, close) = if spoiler {
+        ("||```ansi\n", "```||")
+    } else {
+        ("```ansi\n", "```")
+    };
     let mut chunks = Vec::new();
     let mut chunk = String::new();
     for line in content.split("\n") {
-        if "```ansi\n".len() + chunk.len() + line.len() + "\n```".len() > 2000 {
-            if "```ansi\n".len() + line.len() + "\n```".len() > 2000 {
-                return Err("Line is too long");
+        let line = &*Tabs::new(tab_width).expand(line);
+        if open.len() + chunk.len() + line.len() + "\n".len() + close.len() > max_length {
+            if open.len() + line.len() + "\n".len() + close.len() > max_length {
+                return Err(tr!("line-too-long"));
             }
-            chunk.insert_str(0, "```ansi\n");
-            chunk.push_str("```");
+            chunk.insert_str(0, open);
+            chunk.push_str(close);
             chunks.push(chunk);
             chunk = String::new();
         }
@@ -302,31 +559,13 @@ fn chunk_ansi(content: &str) -> Result<Vec<String>, &'static str> {
         chunk.push('\n');
     }
     if !chunk.is_empty() {
-        chunk.insert_str(0, "```ansi\n");
-        chunk.push_str("```");
+        chunk.insert_str(0, open);
+        chunk.push_str(close);
         chunks.push(chunk);
     }
     Ok(chunks)
 }
 
-// the contents of this array will NOT be responded to automatically
-// "" is the plaintext highlighting, so you can test rendering without a lang
-// do not respond to plain codeblocks lmao
-const NO_AUTO_RESPOND: &[&str] = &[""];
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum Command {
-    Highlight,
-    Render,
-    PrettyParse,
-    PlainParse,
-}
-
-const COMMAND_NAME_HIGHLIGHT: &str = "Highlight Codeblock";
-const COMMAND_NAME_PLAIN_PARSE: &str = "Parse Syntax";
-const COMMAND_NAME_PRETTY_PARSE: &str = "Pretty Parse Syntax";
-const COMMAND_NAME_RENDER: &str = "Render Codeblock";
-
 async fn create_interaction_response<'a, F>(
     ctx: &Context,
     interaction: &Interaction,
@@ -343,6 +582,9 @@ where
         Interaction::ApplicationCommand(interaction) => {
             interaction.create_interaction_response(ctx, f).await
         }
+        Interaction::ModalSubmit(interaction) => {
+            interaction.create_interaction_response(ctx, f).await
+        }
         _ => panic!("bad interaction type"),
     }
 }
@@ -364,6 +606,52 @@ where
         Interaction::ApplicationCommand(interaction) => {
             interaction.create_followup_message(ctx, f).await
         }
+        Interaction::ModalSubmit(interaction) => interaction.create_followup_message(ctx, f).await,
+        _ => panic!("bad interaction type"),
+    }
+}
+
+async fn edit_followup_message(
+    ctx: &Context,
+    interaction: &Interaction,
+    message_id: MessageId,
+    content: &str,
+) -> serenity::Result<Message> {
+    match interaction {
+        Interaction::MessageComponent(interaction) => {
+            interaction
+                .edit_followup_message(ctx, message_id, |msg| msg.content(content))
+                .await
+        }
+        Interaction::ApplicationCommand(interaction) => {
+            interaction
+                .edit_followup_message(ctx, message_id, |msg| msg.content(content))
+                .await
+        }
+        Interaction::ModalSubmit(interaction) => {
+            interaction
+                .edit_followup_message(ctx, message_id, |msg| msg.content(content))
+                .await
+        }
+        _ => panic!("bad interaction type"),
+    }
+}
+
+async fn delete_followup_message(
+    ctx: &Context,
+    interaction: &Interaction,
+    message_id: MessageId,
+) -> serenity::Result<()> {
+    match interaction {
+        Interaction::MessageComponent(interaction) => {
+            interaction.delete_followup_message(ctx, message_id).await
+        }
+        Interaction::ApplicationCommand(interaction) => {
+            interaction.delete_followup_message(ctx, message_id).await
+        }
+        Interaction::ModalSubmit(interaction) => {
+            interaction.delete_followup_message(ctx, message_id).await
+        }
         _ => panic!("bad interaction type"),
     }
 }
@@ -376,7 +664,7 @@ async fn defer(ctx: &Context, interaction: &Interaction, ephemeral: bool) -> ser
                     Interaction::MessageComponent(_) => {
                         InteractionResponseType::DeferredUpdateMessage
                     }
-                    Interaction::ApplicationCommand(_) => {
+                    Interaction::ApplicationCommand(_) | Interaction::ModalSubmit(_) => {
                         InteractionResponseType::DeferredChannelMessageWithSource
                     }
                     _ => panic!("bad interaction type"),
@@ -388,6 +676,7 @@ async fn defer(ctx: &Context, interaction: &Interaction, ephemeral: bool) -> ser
         match interaction {
             Interaction::MessageComponent(interaction) => interaction.defer(ctx).await,
             Interaction::ApplicationCommand(interaction) => interaction.defer(ctx).await,
+            Interaction::ModalSubmit(interaction) => interaction.defer(ctx).await,
             _ => panic!("bad interaction type"),
         }
     }
@@ -397,300 +686,334 @@ async fn defer(ctx: &Context, interaction: &Interaction, ephemeral: bool) -> ser
 impl EventHandler for Handler {
     async fn ready(&self, ctx: Context, _ready: Ready) {
         ApplicationCommand::set_global_application_commands(&ctx, |commands| {
-            commands
-                .create_application_command(|cmd| {
-                    cmd.kind(ApplicationCommandType::Message)
-                        .name(COMMAND_NAME_HIGHLIGHT)
-                })
-                .create_application_command(|cmd| {
-                    cmd.kind(ApplicationCommandType::Message)
-                        .name(COMMAND_NAME_PLAIN_PARSE)
-                })
-                .create_application_command(|cmd| {
-                    cmd.kind(ApplicationCommandType::Message)
-                        .name(COMMAND_NAME_PRETTY_PARSE)
-                })
-                .create_application_command(|cmd| {
-                    cmd.kind(ApplicationCommandType::Message)
-                        .name(COMMAND_NAME_RENDER)
-                })
+            let menus = commands::menus()
+                .filter_map(|spec| spec.menu)
+                .chain([diff::COMMAND_NAME]);
+            for name in menus {
+                commands.create_application_command(|cmd| {
+                    cmd.kind(ApplicationCommandType::Message).name(name)
+                });
+            }
+            slash::register(commands)
         })
         .await
-        .unwrap();
+        .or_log("register the commands");
+    }
+
+    async fn shard_stage_update(&self, _ctx: Context, event: ShardStageUpdateEvent) {
+        let shard = event.shard_id.0;
+        info!(shard, "gateway went from {} to {}", event.old, event.new);
+        health::stage_changed(shard, event.new);
+    }
+
+    async fn message_update(
+        &self,
+        ctx: Context,
+        _old: Option<Message>,
+        _new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        let _job = match shutdown::job() {
+            Some(job) => job,
+            None => return,
+        };
+        let locale = locale::of_guild(&ctx, event.guild_id);
+        let handler = {
+            let ctx = ctx.clone();
+            tone::scope(event.guild_id, async move {
+                reparse::update(&ctx, &event).await;
+                rerun::update(&ctx, &event).await;
+            })
+        };
+        let handler = locale::scope(locale, handler);
+        error::boundary(&ctx, None, handler).await;
+    }
+
+    async fn message_delete(
+        &self,
+        ctx: Context,
+        _channel_id: ChannelId,
+        deleted_message_id: MessageId,
+        _guild_id: Option<GuildId>,
+    ) {
+        expire::cancel(deleted_message_id);
+        let handler = {
+            let ctx = ctx.clone();
+            async move { rerun::deleted(&ctx, deleted_message_id).await }
+        };
+        error::boundary(&ctx, None, handler).await;
+    }
+
+    async fn message_delete_bulk(
+        &self,
+        ctx: Context,
+        _channel_id: ChannelId,
+        multiple_deleted_messages_ids: Vec<MessageId>,
+        _guild_id: Option<GuildId>,
+    ) {
+        for &id in &multiple_deleted_messages_ids {
+            expire::cancel(id);
+        }
+        let handler = {
+            let ctx = ctx.clone();
+            async move {
+                for id in multiple_deleted_messages_ids {
+                    rerun::deleted(&ctx, id).await;
+                }
+            }
+        };
+        error::boundary(&ctx, None, handler).await;
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        let _job = match shutdown::job() {
+            Some(job) => job,
+            None => return,
+        };
+        let locale = locale::of_guild(&ctx, reaction.guild_id);
+        let handler = {
+            let ctx = ctx.clone();
+            tone::scope(reaction.guild_id, async move {
+                reactions::added(&ctx, &reaction).await
+            })
+        };
+        let handler = locale::scope(locale, handler);
+        error::boundary(&ctx, None, handler).await;
     }
 
     async fn message(&self, ctx: Context, message: Message) {
+        let _job = match shutdown::job() {
+            Some(job) => job,
+            None => return,
+        };
+        // most messages aren't code, so this one is only at debug level.
+        // the command span in run_command has who asked for it too
+        let span = debug_span!(
+            "message",
+            shard = ctx.shard_id,
+            user = %message.author.tag(),
+            guild = message.guild_id.map(|id| id.0),
+        );
+        let guild_id = message.guild_id;
+        let locale = locale::of_guild(&ctx, guild_id);
+        let handler = Handler::message(ctx.clone(), message);
+        let handler = tone::scope(guild_id, handler);
+        let handler = locale::scope(locale, handler).instrument(span.clone());
+        error::boundary(&ctx, None, handler).instrument(span).await;
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        // discord says the interaction failed, which is true enough while it's shutting down
+        let _job = match shutdown::job() {
+            Some(job) => job,
+            None => return,
+        };
+        let (guild_id, user) = match interaction {
+            Interaction::ApplicationCommand(ref interaction) => {
+                (interaction.guild_id, Some(&interaction.user))
+            }
+            Interaction::MessageComponent(ref interaction) => {
+                (interaction.guild_id, Some(&interaction.user))
+            }
+            Interaction::ModalSubmit(ref interaction) => {
+                (interaction.guild_id, Some(&interaction.user))
+            }
+            Interaction::Autocomplete(ref interaction) => {
+                (interaction.guild_id, Some(&interaction.user))
+            }
+            _ => (None, None),
+        };
+        let span = info_span!(
+            "interaction",
+            shard = ctx.shard_id,
+            user = user.map(User::tag),
+            guild = guild_id.map(|id| id.0),
+        );
+        // autocomplete can't be answered with a message, see error::boundary
+        let answerable = match interaction {
+            Interaction::ApplicationCommand(_)
+            | Interaction::MessageComponent(_)
+            | Interaction::ModalSubmit(_) => Some(interaction.clone()),
+            _ => None,
+        };
+        let locale = locale::of(&interaction);
+        let handler = Handler::interaction_create(ctx.clone(), interaction);
+        let handler = tone::scope(guild_id, handler);
+        let handler = locale::scope(locale, handler).instrument(span.clone());
+        error::boundary(&ctx, answerable.as_ref(), handler)
+            .instrument(span)
+            .await;
+    }
+}
+
+// the actual handlers, run in tone::scope and locale::scope so owo! knows which server and language it's for
+impl Handler {
+    async fn message(ctx: Context, message: Message) {
         if message.is_own(&ctx) {
             return;
         }
-        // normalize to NFKC because rusttype doesn't support ligatures
-        let content = message.content.nfkc().collect::<String>();
-
-        // normalize newlines to \n
-        let content = content
-            .lines()
-            .fold(String::from("\n"), |out, line| out + line + "\n");
-        // trim trailing newline
-        let content = &content[..(content.len() - 1)];
+        // everything in here replies publicly, see /server channels
+        let listed = listed_channel(&ctx, message.guild_id, message.channel_id);
+        if !storage::posts_in(message.guild_id, listed) {
+            return;
+        }
+        // it can't even say that it can't
+        let needed = Permissions::SEND_MESSAGES;
+        if !missing_permissions(&ctx, message.guild_id, message.channel_id, needed).is_empty() {
+            return;
+        }
+        let content = &normalize_newlines(&message.content);
+        // +query has two codeblocks, which nothing else does
+        if let Some((lang, code, source)) = query::parse_message(content) {
+            if !message.author.bot {
+                query::run(&ctx, &message, lang, code, source).await;
+            }
+            return;
+        }
         // hmm something feels wrong about this pyramid of doom. when eta let else stable
         if let Some((before, lang, code, after)) = codeblock(content) {
-            if let Some(config) = LANGUAGES.get(lang) {
-                let channel = message.channel(&ctx).await.unwrap();
+            if let Some(config) = get_guild_language(message.guild_id, lang) {
+                let channel = message.channel(&ctx).await;
+                let channel = match channel.or_log("get the channel") {
+                    Some(channel) => channel,
+                    None => return,
+                };
                 if let Some(command) = parse_command(before) {
                     if after.trim().is_empty() {
-                        if let Err(error) = run_command(
-                            &ctx,
-                            &channel,
-                            command,
-                            config,
-                            code,
-                            ReplyMethod::PublicReference(&message),
-                            message.author.id,
-                            false,
-                        )
-                        .await
-                        {
-                            message.reply(&ctx, error).await.unwrap();
+                        let roles = message
+                            .member
+                            .as_ref()
+                            .map_or(&[][..], |member| &member.roles);
+                        let command = command.and_then(|(command, options)| {
+                            may_run(message.guild_id, message.author.id, roles, command)
+                                .map(|()| (command, options))
+                        });
+                        let result = match command {
+                            Ok((command, options)) => run_command(
+                                &ctx,
+                                &channel,
+                                command,
+                                options,
+                                config,
+                                code,
+                                ReplyMethod::PublicReference(&message),
+                                message.author.id,
+                                &message.author,
+                                false,
+                            )
+                            .await,
+                            Err(why) => Err(why.into()),
+                        };
+                        if let Err(error) = result {
+                            message
+                                .reply(&ctx, error.report())
+                                .await
+                                .or_log("reply with the error");
                         }
                     }
-                } else if !NO_AUTO_RESPOND.contains(&lang) && !message.author.bot {
-                    run_command(
+                } else if lang.is_empty() && unprompted(&ctx, &message) {
+                    // plaintext is never responded to, but if it looks like something we know, offer to treat it as that
+                    if let Some(detected) = detect::detect(code).await {
+                        let prompt = send(&ctx, &channel, |msg| {
+                            msg.reference_message(&message)
+                                .allowed_mentions(|mentions| mentions.replied_user(false))
+                                .content(tr!("detected", language = detected.name.as_str()))
+                                .components(|c| {
+                                    c.create_action_row(|row| {
+                                        row.create_button(|button| {
+                                            button
+                                                .custom_id(custom_ids::detected_id(Command::Highlight.id(), &detected.name))
+                                                .emoji('📋')
+                                                .label(tr!("detected-highlight"))
+                                                .style(ButtonStyle::Primary)
+                                        })
+                                        .create_button(|button| {
+                                            button
+                                                .custom_id(custom_ids::detected_id(Command::Render.id(), &detected.name))
+                                                .emoji('🖼')
+                                                .label(tr!("detected-render"))
+                                                .style(ButtonStyle::Primary)
+                                        })
+                                        .create_button(|button| {
+                                            button
+                                                .custom_id(custom_ids::ephemeralish_id("delete"))
+                                                .label(tr!("detected-delete"))
+                                                .style(ButtonStyle::Danger)
+                                        })
+                                    })
+                                })
+                        })
+                        .await;
+                        if let Some(prompt) = prompt.or_log("offer to highlight it") {
+                            expire::schedule(&ctx, message.guild_id, &prompt);
+                        }
+                    }
+                } else if config.auto_respond && unprompted(&ctx, &message) {
+                    let result = run_command(
                         &ctx,
                         &channel,
                         Command::Render,
+                        Options::default(),
                         config,
                         code,
                         ReplyMethod::PublicReference(&message),
                         message.author.id,
+                        &message.author,
                         true,
                     )
-                    .await
-                    .unwrap();
+                    .await;
+                    // nobody asked for it, so there's nobody to tell
+                    if let Err(why) = result {
+                        debug!("couldn't respond automatically: {}", why.report());
+                    }
                 }
             }
+        } else if !message.attachments.is_empty() {
+            attachments::respond(&ctx, &message, content).await;
         }
     }
 
-    async fn interaction_create(&self, ctx: Context, original_interaction: Interaction) {
+    async fn interaction_create(ctx: Context, original_interaction: Interaction) {
         match original_interaction {
             Interaction::MessageComponent(ref interaction) => {
-                if interaction.data.component_type == ComponentType::Button {
-                    let ref message = interaction.message;
-                    let channel = message.channel(&ctx).await.unwrap();
-                    // A lot of this stuff is legacy, because the bot used to work like this. I think only "highlight" is actually supposed to ever come through here now?
-                    // but might as well keep the old buttons half-functional still. because why not.
-                    let interact_id = &interaction.data.custom_id[..];
-                    let (interact_id, ephemeralish) = if interact_id.ends_with("-ephemeralish") {
-                        (
-                            &interact_id[..(interact_id.len() - "-ephemeralish".len())],
-                            true,
-                        )
+                if interaction.data.custom_id.starts_with("page-") {
+                    info!("turned a page");
+                    pages::handle(&ctx, interaction).await;
+                } else if interaction.data.custom_id.starts_with("help-") {
+                    info!("tried an example");
+                    help::handle(&ctx, &original_interaction, interaction).await;
+                } else if interaction.data.component_type == ComponentType::SelectMenu {
+                    if interaction.data.custom_id.starts_with("block-") {
+                        info!("picked a codeblock");
+                        blocks::handle(&ctx, &original_interaction, interaction).await;
+                    } else if interaction.data.custom_id.starts_with("lang-") {
+                        info!("picked a language");
+                        picker::handle(&ctx, &original_interaction, interaction).await;
                     } else {
-                        (interact_id, false)
-                    };
-                    let (interact_id, reference_id) = interact_id
-                        // if the "custom_id" looks like this: "highlight-991266068330975302"
-                        // then the component contains the ID. Try parsing it.
-                        .rsplit_once("-")
-                        .and_then(|(interact_id, reference_id)| {
-                            // If that ID is invalid, ignore it, and pretend it was never there.
-                            // Always Some() on this path
-                            reference_id
-                                .parse::<u64>()
-                                .ok()
-                                .map(|reference_id| (interact_id, Some(reference_id.into())))
-                        })
-                        // Either it was absent (i.e. "highlight"), or invalid (i.e. "pretty-parse"), so assume it was absent.
-                        // Use message reference field as the referenced message instead.
-                        .unwrap_or((
-                            interact_id,
-                            message
-                                .message_reference
-                                .as_ref()
-                                .map(|reference| reference.message_id.unwrap()),
-                        ));
-                    async fn delete(ctx: &Context, message: &Message, ephemeralish: bool) {
-                        if ephemeralish {
-                            message.delete(&ctx).await.unwrap();
-                        } else {
-                            message
-                                .clone()
-                                .edit(ctx, |msg| msg.set_components(Default::default()))
-                                .await
-                                .unwrap()
-                        }
-                    }
-
-                    let referenced = match reference_id {
-                        Some(reference_id) => get_ref(&ctx, &channel, reference_id).await,
-                        None => {
-                            // where is the replied message?? just delete it already we don't care
-                            interaction.defer(&ctx).await.unwrap();
-                            return delete(&ctx, message, ephemeralish).await;
-                        }
-                    };
-
-                    fn can_delete(
-                        ctx: &Context,
-                        interaction: &MessageComponentInteraction,
-                        channel: &Channel,
-                        referenced: &Message,
-                    ) -> bool {
-                        let channel = match channel {
-                            Channel::Guild(c) => c,
-                            _ => {
-                                return true;
-                            }
-                        };
-                        if interaction.user == referenced.author {
-                            // delete if user is author, since they might want the bot to fuck off
-                            true
-                        } else if channel
-                            .permissions_for_user(&ctx, interaction.user.id)
-                            .map(|p| p.contains(Permissions::MANAGE_MESSAGES))
-                            .unwrap_or(false)
-                        {
-                            // can delete messages anyways, let them do it through interaction
-                            true
-                        } else {
-                            false
-                        }
-                    }
-
-                    let command = match interact_id {
-                        "highlight" => Command::Highlight,
-                        "render" => Command::Render,
-                        "pretty-parse" => Command::PrettyParse,
-                        "plain-parse" => Command::PlainParse,
-                        "delete" => {
-                            if can_delete(&ctx, &interaction, &channel, &referenced) {
-                                interaction.defer(&ctx).await.unwrap();
-                                delete(&ctx, message, ephemeralish).await;
-                            } else {
-                                interaction
-                                .create_interaction_response(&ctx, |response| {
-                                    response.interaction_response_data(|msg| {
-                                        msg.ephemeral(true).content(
-                                            owo!("You didn't send the original message, so you can't delete this.")
-                                        )
-                                    })
-                                })
-                                .await
-                                .unwrap();
-                            }
-                            return;
-                        }
-                        kind => {
-                            return interaction
-                                .create_interaction_response(&ctx, |response| {
-                                    response.interaction_response_data(|msg| {
-                                        msg.ephemeral(true)
-                                            .content(owo!("Unknown command `{kind}`"))
-                                    })
-                                })
-                                .await
-                                .unwrap()
-                        }
-                    };
-                    println!("{} clicked to execute {command:?}", interaction.user.tag());
-                    match run_command_from_interaction(
-                        &ctx,
-                        command,
-                        &original_interaction,
-                        &channel,
-                        &referenced,
-                        true,
-                        true,
-                    )
-                    .await
-                    {
-                        // command was not acknowledged in this case, so must defer it
-                        InteractionCommandResult::NoCodeblock
-                        // the message was edited to be the wrong lang, so delete silently here too
-                        | InteractionCommandResult::BadLang(_) => {
-                            interaction.defer(&ctx).await.unwrap();
-                            delete(&ctx, message, ephemeralish).await;
-                        }
-                        InteractionCommandResult::FinishedSuccessfully => (), // do nothing, no new public message was sent, it was ephemeral so leave the button for others
-                        InteractionCommandResult::InformedError => (), // do nothing, we already informed the user of the error
+                        info!("expanded a node");
+                        expand::handle(&ctx, &original_interaction, interaction).await;
                     }
+                } else if interaction.data.component_type == ComponentType::Button {
+                    buttons::handle(&ctx, &original_interaction, interaction).await;
                 }
             }
             Interaction::ApplicationCommand(ref interaction)
                 if interaction.data.kind == ApplicationCommandType::Message =>
             {
-                let command = match interaction.data.name.as_str() {
-                    COMMAND_NAME_HIGHLIGHT => Command::Highlight,
-                    COMMAND_NAME_RENDER => Command::Render,
-                    COMMAND_NAME_PRETTY_PARSE => Command::PrettyParse,
-                    COMMAND_NAME_PLAIN_PARSE => Command::PlainParse,
-                    name => {
-                        interaction
-                            .create_interaction_response(&ctx, |response| {
-                                response.interaction_response_data(|msg| {
-                                    msg.ephemeral(true)
-                                        .content(owo!("Unknown command `{name}`"))
-                                })
-                            })
-                            .await
-                            .unwrap();
-                        return;
-                    }
-                };
-                println!("{} clicked to execute {command:?}", interaction.user.tag());
-                let channel = interaction.channel_id.to_channel(&ctx).await.unwrap();
-                let target = interaction.data.target_id.unwrap().to_message_id();
-                let message = if let Some(message) = interaction.data.resolved.messages.get(&target)
-                {
-                    message.clone()
-                } else {
-                    get_ref(
-                        &ctx,
-                        &channel,
-                        interaction.data.target_id.unwrap().to_message_id(),
-                    )
-                    .await
-                };
-                match run_command_from_interaction(
-                    &ctx,
-                    command,
-                    &original_interaction,
-                    &channel,
-                    &message,
-                    false,
-                    true,
-                )
-                .await
-                {
-                    InteractionCommandResult::NoCodeblock => {
-                        interaction
-                            .create_interaction_response(&ctx, |response| {
-                                response.interaction_response_data(|msg| {
-                                    msg.ephemeral(true).content(owo!(
-                                        "That's not a codeblock. Am i a joke to you?"
-                                    ))
-                                })
-                            })
-                            .await
-                            .unwrap();
-                    }
-                    InteractionCommandResult::BadLang(lang) => {
-                        interaction
-                            .create_interaction_response(&ctx, |response| {
-                            response.interaction_response_data(|msg| {
-                                msg.ephemeral(true)
-                                    .content(
-                                        owo!("I know that's a codeblock and all, but like, i don't understand {lang}, sorry!")
-                                    )
-                                })
-                            })
-                            .await
-                            .unwrap();
-                    }
-                    // both other cases already responded to the user, so do nothing here
-                    InteractionCommandResult::FinishedSuccessfully
-                    | InteractionCommandResult::InformedError => (),
-                }
+                menus::handle(&ctx, &original_interaction, interaction).await;
+            }
+            Interaction::ApplicationCommand(ref interaction)
+                if interaction.data.kind == ApplicationCommandType::ChatInput =>
+            {
+                info!("used /{}", interaction.data.name);
+                slash::handle(&ctx, interaction).await;
+            }
+            Interaction::Autocomplete(ref interaction) => {
+                modal::autocomplete(&ctx, interaction).await;
+            }
+            Interaction::ModalSubmit(ref interaction) => {
+                info!("submitted code to a modal");
+                modal::submit(&ctx, &original_interaction, interaction).await;
             }
             _ => (),
         }
@@ -711,12 +1034,58 @@ async fn run_command_from_interaction<'a>(
     interaction: &Interaction,
     channel: &Channel,
     referenced: &'a Message,
+    lang_override: Option<&str>,
     add_components: bool,
     send_as_followup: bool,
 ) -> InteractionCommandResult<'a> {
-    if let Some((_, lang, code, _)) = codeblock(&referenced.content) {
-        if let Some(lang) = LANGUAGES.get(lang) {
-            if command == Command::Render && !send_as_followup {
+    let guild_id = match channel {
+        Channel::Guild(channel) => Some(channel.guild_id),
+        _ => None,
+    };
+    // it can still answer just them where it can't post, see /server channels
+    let listed = listed_channel(ctx, guild_id, channel.id());
+    let send_as_followup = send_as_followup || !storage::posts_in(guild_id, listed);
+    // or where it's not allowed to post that, a render can't go anywhere without attaching it
+    let needed = match command {
+        Command::Render => Permissions::SEND_MESSAGES | Permissions::ATTACH_FILES,
+        _ => Permissions::SEND_MESSAGES,
+    };
+    let send_as_followup = send_as_followup
+        || !missing_permissions(ctx, guild_id, channel.id(), needed).is_empty();
+    let found = match codeblock(&referenced.content) {
+        Some((_, lang, code, _)) => Some((lang, Cow::Borrowed(code))),
+        // no codeblock, but it might have a file with code in it
+        None => attachments::code(referenced, guild_id)
+            .await
+            .map(|(lang, code)| (lang, Cow::Owned(code))),
+    };
+    if let Some((lang, ref code)) = found {
+        let code = &**code;
+        let config = match lang_override {
+            Some(lang) => get_guild_language(guild_id, lang),
+            None => get_guild_language(guild_id, lang),
+        };
+        if let Some(lang) = config {
+            let (invoker, member) = match &interaction {
+                Interaction::MessageComponent(interaction) => {
+                    (interaction.user.id, interaction.member.as_ref())
+                }
+                Interaction::ApplicationCommand(interaction) => {
+                    (interaction.user.id, interaction.member.as_ref())
+                }
+                _ => unreachable!(),
+            };
+            let roles = member.map_or(&[][..], |member| &member.roles);
+            if let Err(why) = may_run(guild_id, invoker, roles, command) {
+                create_interaction_response(&ctx, &interaction, |response| {
+                    response.interaction_response_data(|msg| msg.ephemeral(true).content(why))
+                })
+                .await
+                .or_log("reply with the error");
+                return InteractionCommandResult::InformedError;
+            }
+            // without a response there's nothing to follow up on, so it can't go any further
+            let responded = if command == Command::Render && !send_as_followup {
                 create_interaction_response(&ctx, &interaction, |response| {
                     response.interaction_response_data(|msg| {
                     msg.ephemeral(true);
@@ -725,20 +1094,27 @@ async fn run_command_from_interaction<'a>(
                             || code.lines().count() > max_len
                     };
                     if bounds(700) {
-                        msg.content("Rendering... (this could take a while, especially if you're trying to break it intentionally)")
+                        msg.content(tr!("rendering-huge"))
                     } else if bounds(100) {
-                        msg.content("Rendering... (this could take a while, especially if the code is really big)")
+                        msg.content(tr!("rendering-big"))
                     } else {
-                        msg.content("Rendering...")
+                        msg.content(tr!("rendering"))
                     }})
-                }).await.unwrap();
+                }).await
             } else {
-                defer(&ctx, &interaction, send_as_followup).await.unwrap();
+                // a public reply is its own message, so a context menu command's own response is just for them
+                let ephemeral =
+                    send_as_followup || matches!(interaction, Interaction::ApplicationCommand(_));
+                defer(&ctx, &interaction, ephemeral).await
+            };
+            if responded.or_log("respond to the interaction").is_none() {
+                return InteractionCommandResult::InformedError;
             }
             if let Err(why) = run_command(
                 &ctx,
                 &channel,
                 command,
+                Options::default(),
                 lang,
                 code,
                 if send_as_followup {
@@ -746,11 +1122,8 @@ async fn run_command_from_interaction<'a>(
                 } else {
                     ReplyMethod::PublicReference(referenced)
                 },
-                match &interaction {
-                    Interaction::MessageComponent(interaction) => interaction.user.id,
-                    Interaction::ApplicationCommand(interaction) => interaction.user.id,
-                    _ => unreachable!(),
-                },
+                invoker,
+                &referenced.author,
                 add_components,
             )
             .await
@@ -758,12 +1131,23 @@ async fn run_command_from_interaction<'a>(
                 create_followup_message(
                     &ctx,
                     &interaction,
-                    |msg: &mut CreateInteractionResponseFollowup| msg.ephemeral(true).content(why),
+                    |msg: &mut CreateInteractionResponseFollowup| {
+                        msg.ephemeral(true).content(why.report())
+                    },
                 )
                 .await
-                .unwrap();
+                .or_log("reply with the error");
                 InteractionCommandResult::InformedError
             } else {
+                if let (false, Interaction::ApplicationCommand(interaction)) =
+                    (send_as_followup, interaction)
+                {
+                    // the output is out there now, so "Rendering..." or the deferred response is just clutter
+                    interaction
+                        .delete_original_interaction_response(&ctx)
+                        .await
+                        .or_log("delete the response");
+                }
                 InteractionCommandResult::FinishedSuccessfully
             }
         } else {
@@ -774,214 +1158,378 @@ async fn run_command_from_interaction<'a>(
     }
 }
 
-fn parse_command(before: &str) -> Option<Command> {
-    match before {
-        "+highlight" => Some(Command::Highlight),
-        "+render" => Some(Command::Render),
-        "+parse" => Some(Command::PrettyParse),
-        "+pparse" => Some(Command::PlainParse),
-        _ => None,
+// Things that can be written after the command, like +render theme=light
+#[derive(Clone, Default)]
+struct Options {
+    // None means whatever the user or their server picked, see theme::preferred
+    theme: Option<&'static Theme>,
+    // None means whatever the server picked with /server line-numbers
+    line_numbers: Option<bool>,
+    title: bool,
+    // in pixels, 0 turns wrapping off. None means render::DEFAULT_WRAP_WIDTH
+    wrap: Option<u32>,
+    tab_width: Option<usize>,
+    // in pixels. None means whatever the user picked with /preferences size
+    size: Option<u32>,
+    format: render::Encoding,
+    // only these lines get rendered, like +render 10..40. both ends are included and start at 1
+    range: Option<(usize, usize)>,
+    // lines to draw a background behind, like hl=3,7-9. both ends are included
+    emphasis: Vec<(usize, usize)>,
+    transparent: bool,
+    // images are uploaded as spoilers and ANSI goes in ||spoiler tags||
+    spoiler: bool,
+    // ANSI goes in embeds instead of the message itself, see send_ansi
+    embed: bool,
+    // long ANSI goes in a thread. None means whatever the server picked with /server threads
+    thread: Option<bool>,
+    // +render animate
+    animate: bool,
+    // +parse verbose, see pretty_parse_node
+    verbose: bool,
+    // how deep +parse goes before it collapses nodes. None means as deep as fits, see pretty_parse_chunks
+    depth: Option<usize>,
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "on" | "yes" | "true" => Ok(true),
+        "off" | "no" | "false" => Ok(false),
+        _ => Err(tr!("option-on-off", option = key)),
     }
 }
 
-async fn run_command(
-    ctx: &Context,
-    channel: &Channel,
-    command: Command,
-    config: &'static LanguageConfig,
-    code: &str,
-    reply_to: ReplyMethod<'_>,
-    lock_render_for: UserId,
-    add_components: bool,
-) -> Result<(), &'static str> {
-    Ok(match command {
-        Command::Highlight => {
-            let formatted = syntax_highlight(config, code)?;
-            send_chunked_message_with_commands(ctx, channel, chunk_ansi(&formatted)?, reply_to)
-                .await
-                .unwrap()
-        }
-        Command::PrettyParse => {
-            let formatted = pretty_parse(config, code, true)?;
-            send_chunked_message_with_commands(ctx, channel, chunk_ansi(&formatted)?, reply_to)
-                .await
-                .unwrap()
-        }
-        Command::PlainParse => {
-            let formatted = pretty_parse(config, code, false)?;
-            send_chunked_message_with_commands(ctx, channel, chunk_ansi(&formatted)?, reply_to)
-                .await
-                .unwrap()
+impl Options {
+    // options that are just a word, like +render animate
+    fn set_flag(&mut self, word: &str) -> Result<(), String> {
+        match word {
+            "animate" => self.animate = true,
+            "verbose" => self.verbose = true,
+            "embed" => self.embed = true,
+            "thread" => self.thread = Some(true),
+            _ => return Err(tr!("option-needs-value", word = word)),
         }
-        Command::Render => {
-            lazy_static! {
-                static ref DENY_RENDER: Mutex<HashMap<UserId, Arc<Mutex<()>>>> =
-                    Mutex::new(HashMap::new());
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "theme" => {
+                self.theme =
+                    Some(theme::by_name(value).ok_or_else(|| {
+                        tr!("option-theme", theme = value, themes = theme::names())
+                    })?)
             }
-            let user_mutex = {
-                let mut map = DENY_RENDER.lock().await;
-                map.entry(lock_render_for)
-                    .or_insert_with(|| Arc::new(Mutex::new(())))
-                    .clone()
-            };
-            // this is dropped after render_command() finishes
-            let _lock = user_mutex
-                .try_lock()
-                .err_as("You've already queued up a rendering task")?;
-            render_command(ctx, channel, config, code, reply_to, add_components).await?;
+            "lines" => self.line_numbers = Some(parse_bool(key, value)?),
+            "title" => self.title = parse_bool(key, value)?,
+            "transparent" => self.transparent = parse_bool(key, value)?,
+            "depth" => {
+                self.depth = Some(
+                    value
+                        .parse()
+                        .map_err(|_| tr!("option-depth", value = value))?,
+                )
+            }
+            "spoiler" => self.spoiler = parse_bool(key, value)?,
+            "embed" => self.embed = parse_bool(key, value)?,
+            "thread" => self.thread = Some(parse_bool(key, value)?),
+            "tabs" => {
+                self.tab_width = Some(match value.parse() {
+                    Ok(width) if (1..=MAX_TAB_WIDTH).contains(&width) => width,
+                    _ => return Err(tr!("option-tabs", max = MAX_TAB_WIDTH)),
+                })
+            }
+            "size" => {
+                self.size = Some(match value.parse() {
+                    Ok(size) if (render::MIN_TEXT_SIZE..=render::MAX_TEXT_SIZE).contains(&size) => {
+                        size
+                    }
+                    _ => {
+                        return Err(tr!(
+                            "option-size",
+                            min = render::MIN_TEXT_SIZE,
+                            max = render::MAX_TEXT_SIZE,
+                        ))
+                    }
+                })
+            }
+            "format" => {
+                self.format = render::Encoding::by_name(value)
+                    .ok_or_else(|| tr!("option-format", value = value))?
+            }
+            "hl" => {
+                for part in value.split(',') {
+                    let (start, end) = part.split_once('-').unwrap_or((part, part));
+                    match (start.parse(), end.parse()) {
+                        (Ok(start), Ok(end)) if start > 0 && start <= end => {
+                            self.emphasis.push((start, end))
+                        }
+                        _ => return Err(tr!("option-hl", value = value)),
+                    }
+                }
+            }
+            "wrap" => {
+                self.wrap = Some(match value {
+                    "off" => 0,
+                    _ => match value.parse() {
+                        Ok(width) if width >= render::MIN_WRAP_WIDTH => width,
+                        _ => return Err(tr!("option-wrap", min = render::MIN_WRAP_WIDTH)),
+                    },
+                })
+            }
+            _ => return Err(owo!("option-unknown", option = key)),
         }
-    })
-}
-
-fn codeblock(content: &str) -> Option<(&str, &str, &str, &str)> {
-    let (before, content) = content.split_once("```")?;
-    let (content, after) = content.split_once("```")?;
-    // multiple codeblocks, nontrivial, so abort
-    if after.contains("```") {
-        return None;
+        Ok(())
     }
-    let (lang, code) = content.split_once("\n").unwrap_or((content, ""));
-    let code = code.trim_matches('\n');
-    let (lang, code) = if code.is_empty() {
-        ("", lang)
-    } else if !lang.chars().all(char::is_alphanumeric) {
-        ("", content)
-    } else {
-        (lang, code)
-    };
-    if code.is_empty() {
-        None
-    } else {
-        Some((before.trim(), lang, code, after))
+
+    // 10..40, or 10.. for everything after line 10, or ..40 for everything up to line 40
+    fn set_range(&mut self, range: &str) -> Result<(), String> {
+        let bad = || tr!("option-range", range = range);
+        let (start, end) = range.split_once("..").ok_or_else(bad)?;
+        let start = match start {
+            "" => 1,
+            start => start.parse().map_err(|_| bad())?,
+        };
+        let end = match end {
+            "" => usize::MAX,
+            end => end.parse().map_err(|_| bad())?,
+        };
+        if start == 0 || start > end {
+            return Err(owo!("option-range-empty", range = range));
+        }
+        self.range = Some((start, end));
+        Ok(())
     }
 }
 
-fn syntax_highlight(config: &LanguageConfig, code: &str) -> Result<String, &'static str> {
-    match config.highlight {
-        HighlightType::TreeSitter(ref highlight) => {
-            let mut output = String::new();
-            let mut highlighter = Highlighter::new();
-            let mut colors = ne_vec![RESET];
-            for event in highlighter
-                .highlight(highlight, code.as_bytes(), None, |_| None)
-                .err_as(TS_ERROR)?
-            {
-                output += match event.err_as(TS_ERROR)? {
-                    HighlightEvent::HighlightStart(Highlight(u)) => {
-                        colors.push(config.formats[u]);
-                        colors.last().ansi
-                    }
-                    HighlightEvent::Source { start, end } => &code[start..end],
-                    HighlightEvent::HighlightEnd => {
-                        colors.pop();
-                        colors.last().ansi
-                    }
-                }
+// None if it's not a command at all, Err if it is but the options are wrong
+fn parse_command(before: &str) -> Option<Result<(Command, Options), String>> {
+    let mut words = before.split_whitespace();
+    let spec = commands::by_prefix(words.next()?)?;
+    // the codeblock has to come right after the command, so no newlines allowed
+    if before.contains('\n') {
+        return None;
+    }
+    let mut options = Options::default();
+    let mut export = None;
+    // the names in Spec::options, checked once it's known which command it is
+    let mut used = Vec::new();
+    for word in words {
+        let result = match word.split_once('=') {
+            Some((key, value)) => {
+                used.push(key);
+                options.set(key, value)
+            }
+            None if word.contains("..") => {
+                used.push("range");
+                options.set_range(word)
             }
-            Ok(output)
+            // +parse json, +highlight html and the like are different commands, not options
+            None => match spec.exports.iter().find(|&&(name, _)| name == word) {
+                Some(&(name, command)) => {
+                    export = Some((name, command));
+                    Ok(())
+                }
+                None => {
+                    used.push(word);
+                    options.set_flag(word)
+                }
+            },
+        };
+        if let Err(why) = result {
+            return Some(Err(why));
         }
-        HighlightType::Plaintext => Ok(code.to_string()),
     }
+    let (typed, command) = match export {
+        Some((name, command)) => (format!("{} {name}", spec.prefix?), command),
+        None => (spec.prefix?.to_owned(), spec.command),
+    };
+    if let Some(name) = used
+        .into_iter()
+        .find(|name| !command.spec().options.contains(name))
+    {
+        return Some(Err(owo!("option-not-for", option = name, command = typed)));
+    }
+    Some(Ok((command, options)))
 }
 
-fn pretty_parse(
-    config: &LanguageConfig,
+// The span is inside the one for the message or interaction that asked for it, see Handler
+#[tracing::instrument(
+    name = "command",
+    skip_all,
+    fields(
+        command = ?command,
+        user = invoker.0,
+        guild = tracing::field::Empty,
+        language = %config.name,
+        code_bytes = code.len(),
+    ),
+)]
+async fn run_command(
+    ctx: &Context,
+    channel: &Channel,
+    command: Command,
+    options: Options,
+    config: Arc<LanguageConfig>,
     code: &str,
-    colored: bool,
-) -> Result<String, &'static str> {
-    let mut parser = Parser::new();
-    parser
-        .set_language(
-            config
-                .language
-                .ok_or("This language doesn't have parsing support")?,
-        )
-        .err_as(TS_ERROR)?;
-    let tree = parser.parse(code, None).ok_or(TS_ERROR)?;
-    let mut cursor = tree.walk();
-    Ok(pretty_parse_node(
-        &mut cursor,
-        0,
-        String::new(),
+    reply_to: ReplyMethod<'_>,
+    invoker: UserId,
+    // whoever wrote the code, which isn't always whoever asked for the command
+    author: &User,
+    add_components: bool,
+) -> Result<(), CommandError> {
+    let guild_id = match channel {
+        Channel::Guild(channel) => Some(channel.guild_id),
+        _ => None,
+    };
+    if let Some(guild_id) = guild_id {
+        Span::current().record("guild", guild_id.0);
+    }
+    let config = languages::for_guild(guild_id, config);
+    let theme = options
+        .theme
+        .unwrap_or_else(|| theme::preferred(invoker, guild_id));
+    let tab_width = options.tab_width.unwrap_or(DEFAULT_TAB_WIDTH);
+    (command.spec().run)(commands::Invocation {
+        ctx,
+        channel,
+        command,
+        options,
+        config,
         code,
-        colored,
-    ))
+        reply_to,
+        invoker,
+        author,
+        add_components,
+        guild_id,
+        theme,
+        tab_width,
+    })
+    .await
 }
 
-fn pretty_parse_node(
-    cursor: &mut TreeCursor,
-    indent: usize,
-    mut string: String,
+// Big files make way too many messages when every node is shown,
+// so without depth= it goes less and less deep until it's at most this many
+const MAX_PARSE_MESSAGES: usize = 4;
+const FALLBACK_DEPTHS: &[usize] = &[12, 8, 5, 3, 1];
+
+// the chunks, the depth it ended up with, and whatever that collapsed
+fn pretty_parse_chunks(
+    tree: &Tree,
     code: &str,
-    colored: bool,
-) -> String {
-    const INDENT: &str = "    ";
-    string.extend(iter::repeat(INDENT).take(indent));
-    if let Some(field_name) = cursor.field_name() {
-        if colored {
-            string.push_str(YELLOW.ansi);
-        }
-        string.push_str(field_name);
-        string.push_str(": ");
-        if colored {
-            string.push_str(RESET.ansi);
-        }
-    }
-    if colored {
-        if cursor.node().is_error() {
-            string.push_str(RED.ansi);
-        } else if cursor.node().is_extra() {
-            string.push_str(GRAY.ansi);
-        } else {
-            string.push_str(LIGHT_GREEN.ansi);
+    theme: Option<&Theme>,
+    path: &[usize],
+    options: &Options,
+) -> Result<(Vec<String>, Option<usize>, Vec<Collapsed>), CommandError> {
+    let tab_width = options.tab_width.unwrap_or(DEFAULT_TAB_WIDTH);
+    let depths = match options.depth {
+        Some(depth) => vec![Some(depth)],
+        None => iter::once(None)
+            .chain(FALLBACK_DEPTHS.iter().copied().map(Some))
+            .collect(),
+    };
+    let mut result = None;
+    for depth in depths {
+        let (formatted, collapsed) =
+            pretty_parse(tree, code, theme, options.verbose, path, depth)?;
+        let chunks = chunk_ansi(&formatted, tab_width, options.spoiler, 2000)?;
+        let fits = chunks.len() <= MAX_PARSE_MESSAGES;
+        result = Some((chunks, depth, collapsed));
+        if fits {
+            break;
         }
     }
-    string.push_str(cursor.node().kind());
-    if colored {
-        string.push_str(RESET.ansi);
-    }
+    Ok(result.unwrap())
+}
 
-    let printed = cursor.goto_first_child() && {
-        let mut printed = false;
-        loop {
-            if cursor.field_name().is_some()
-                || cursor.node().is_named()
-                || cursor.node().child_count() > 0
-            {
-                printed = true;
-                string.push('\n');
-                string = pretty_parse_node(cursor, indent + 1, string, code, colored);
-            }
-            if !cursor.goto_next_sibling() {
-                break;
-            }
-        }
-        cursor.goto_parent();
-        printed
-    };
-    if !printed {
-        if colored {
-            string.push_str(PINK.ansi);
-        }
-        string.push_str(" [");
-        let tree_sitter::Point { row, column } = cursor.node().start_position();
-        string.push_str(&(row + 1).to_string());
-        string.push_str(", ");
-        string.push_str(&(column + 1).to_string());
-        string.push_str("] ");
-        if cursor.node().is_named() {
-            if colored {
-                if cursor.node().is_extra() {
-                    string.push_str(GRAY.ansi);
-                } else {
-                    string.push_str(BLUE.ansi);
-                }
-            }
-            string.push_str(&code[cursor.node().byte_range()]);
-            if colored {
-                string.push_str(RESET.ansi);
-            }
-        }
+// discord's limits on select menus
+const MAX_MENU_OPTIONS: usize = 25;
+const MAX_MENU_VALUE: usize = 100;
+
+// The menu to expand what was collapsed, and the button to export the whole tree as JSON.
+// Only the first chunk replies to the codeblock, so they say which message it was themselves
+fn parse_components(
+    source: MessageId,
+    plain: bool,
+    verbose: bool,
+    depth: Option<usize>,
+    collapsed: &[Collapsed],
+    json_button: bool,
+) -> Option<CreateComponents> {
+    let options = collapsed
+        .iter()
+        .map(|node| {
+            let path = node.path.iter().map(usize::to_string).collect::<Vec<_>>();
+            (node, path.join("."))
+        })
+        .filter(|(_, path)| path.len() <= MAX_MENU_VALUE)
+        .take(MAX_MENU_OPTIONS)
+        .collect::<Vec<_>>();
+    if options.is_empty() && !json_button {
+        return None;
+    }
+    let mut components = CreateComponents::default();
+    if let (false, Some(depth)) = (options.is_empty(), depth) {
+        let mode = match (plain, verbose) {
+            (false, false) => "pretty",
+            (true, false) => "plain",
+            (false, true) => "pretty.verbose",
+            (true, true) => "plain.verbose",
+        };
+        components.create_action_row(|row| {
+            row.create_select_menu(|menu| {
+                menu.custom_id(custom_ids::expand_id(mode, depth, source.0))
+                    .placeholder(tr!("expand-placeholder"))
+                    .options(|menu_options| {
+                        for (node, path) in options {
+                            menu_options.create_option(|option| {
+                                option
+                                    .label(tr!(
+                                        "expand-option",
+                                        kind = node.kind,
+                                        line = node.row + 1
+                                    ))
+                                    .value(path)
+                            });
+                        }
+                        menu_options
+                    })
+            })
+        });
+    }
+    if json_button {
+        let command = if plain {
+            Command::PlainParse
+        } else {
+            Command::PrettyParse
+        };
+        components.create_action_row(|row| {
+            row.create_button(|button| {
+                button
+                    .custom_id(custom_ids::button_id(Command::JsonParse.id(), Some(source.0)))
+                    .emoji('📄')
+                    .label(tr!("export-json"))
+                    .style(ButtonStyle::Secondary)
+            });
+            relang_button(row, command, source)
+        });
     }
-    string
+    Some(components)
 }
+
+// Asks which language the codeblock actually is and runs the command again as that, see picker.rs.
+// For when the codeblock says the wrong language, or it was guessed wrong
+fn relang_button(
+    row: &mut CreateActionRow,
+    command: Command,
+    source: MessageId,
+) -> &mut CreateActionRow {
+    row.create_button(|button| {
+        button
+            .custom_id(custom_ids::relang_id(command.id(), source.0))
+            .emoji('🔤')
+            .label(tr!("wrong-language"))
+            .style(ButtonStyle::Secondary)
+    })
+}
+
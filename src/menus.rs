@@ -0,0 +1,98 @@
+use super::*;
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+
+// The message commands, from right clicking a message and going to Apps. Which command it is comes from
+// its name, see commands::by_menu, except for Diff Codeblocks, which needs two messages, see diff.rs
+
+pub async fn handle(
+    ctx: &Context,
+    original_interaction: &Interaction,
+    interaction: &ApplicationCommandInteraction,
+) {
+    if interaction.data.name == diff::COMMAND_NAME {
+        info!("clicked to diff");
+        diff::handle(ctx, original_interaction, interaction).await;
+        return;
+    }
+    let command = match commands::by_menu(&interaction.data.name) {
+        Some(command) => command,
+        None => {
+            let name = &interaction.data.name;
+            interaction
+                .create_interaction_response(ctx, |response| {
+                    response.interaction_response_data(|msg| {
+                        msg.ephemeral(true)
+                            .content(owo!("unknown-command", name = name.as_str()))
+                    })
+                })
+                .await
+                .or_log("say the command is unknown");
+            return;
+        }
+    };
+    info!("clicked to execute {command:?}");
+    let channel = interaction.channel_id.to_channel(ctx).await;
+    let channel = match channel.or_log("get the channel") {
+        Some(channel) => channel,
+        None => return,
+    };
+    // discord always says which message it was for message commands
+    let target = match interaction.data.target_id {
+        Some(target) => target.to_message_id(),
+        None => return,
+    };
+    let message = match interaction.data.resolved.messages.get(&target) {
+        Some(message) => Some(message.clone()),
+        None => get_ref(ctx, &channel, target).await,
+    };
+    let message = match message {
+        Some(message) => message,
+        None => {
+            interaction
+                .create_interaction_response(ctx, |response| {
+                    response.interaction_response_data(|msg| {
+                        msg.ephemeral(true).content(owo!("message-gone"))
+                    })
+                })
+                .await
+                .or_log("say the message is gone");
+            return;
+        }
+    };
+    // which codeblock is meant gets picked from a menu first
+    if blocks::offer(ctx, original_interaction, command, &message).await {
+        return;
+    }
+    let lang_override = picker::corrected(message.id);
+    match run_command_from_interaction(
+        ctx,
+        command,
+        original_interaction,
+        &channel,
+        &message,
+        lang_override.as_deref(),
+        false,
+        !storage::public_output(interaction.user.id),
+    )
+    .await
+    {
+        InteractionCommandResult::NoCodeblock => {
+            interaction
+                .create_interaction_response(ctx, |response| {
+                    response.interaction_response_data(|msg| {
+                        msg.ephemeral(true).content(owo!("not-a-codeblock"))
+                    })
+                })
+                .await
+                .or_log("say it's not a codeblock");
+        }
+        // they can just say what it is instead
+        InteractionCommandResult::BadLang(lang) => {
+            let lang = Some(lang);
+            picker::offer(ctx, original_interaction, command, &message, lang).await;
+        }
+        // both other cases already responded to the user, so do nothing here
+        InteractionCommandResult::FinishedSuccessfully
+        | InteractionCommandResult::InformedError => (),
+    }
+}
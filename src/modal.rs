@@ -0,0 +1,245 @@
+use super::*;
+use serenity::model::application::{
+    component::{ActionRowComponent, InputTextStyle},
+    interaction::{
+        application_command::ApplicationCommandInteraction, autocomplete::AutocompleteInteraction,
+        modal::ModalSubmitInteraction,
+    },
+};
+
+// /highlight, for when the code shouldn't be posted publicly first. It opens a modal to paste the code into,
+// and the highlight (or render) is only shown to whoever used it.
+// The modal's custom_id is code-<highlight or render>-<language>.
+// /scratchpad is the same, except the language goes in the modal too, and it's posted for everyone
+// as a reply to the command, so it still says who it's from. That one's custom_id is scratchpad-<command>
+
+// discord doesn't allow more than this in a text input
+const MAX_CODE_LENGTH: u64 = 4000;
+// or more choices than this in autocomplete
+const MAX_CHOICES: usize = 25;
+
+pub async fn open(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+    let options = &interaction.data.options;
+    let lang = options
+        .iter()
+        .find(|option| option.name == "language")
+        .and_then(|option| option.value.as_ref())
+        .and_then(|value| value.as_str())
+        .unwrap_or_default();
+    let render = options
+        .iter()
+        .find(|option| option.name == "render")
+        .and_then(|option| option.value.as_ref())
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false);
+    let result = match get_guild_language(interaction.guild_id, lang) {
+        Some(config) => {
+            let command = if render {
+                Command::Render
+            } else {
+                Command::Highlight
+            };
+            interaction
+                .create_interaction_response(&ctx, |response| {
+                    response
+                        .kind(InteractionResponseType::Modal)
+                        .interaction_response_data(|modal| {
+                            modal
+                                .custom_id(custom_ids::code_modal_id(command.id(), &config.name))
+                                .title(tr!("modal-title", language = config.name.as_str()))
+                                .components(|c| {
+                                    c.create_action_row(|row| {
+                                        row.create_input_text(|input| {
+                                            input
+                                                .custom_id("code")
+                                                .label(tr!("modal-code"))
+                                                .style(InputTextStyle::Paragraph)
+                                                .max_length(MAX_CODE_LENGTH)
+                                                .required(true)
+                                        })
+                                    })
+                                })
+                        })
+                })
+                .await
+        }
+        None => {
+            interaction
+                .create_interaction_response(&ctx, |response| {
+                    response.interaction_response_data(|msg| {
+                        msg.ephemeral(true)
+                            .content(owo!("unknown-language", language = lang))
+                    })
+                })
+                .await
+        }
+    };
+    result.or_log("open the modal");
+}
+
+pub async fn scratchpad(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+    let render = interaction
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "render")
+        .and_then(|option| option.value.as_ref())
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false);
+    let command = if render {
+        Command::Render
+    } else {
+        Command::Highlight
+    };
+    interaction
+        .create_interaction_response(&ctx, |response| {
+            response
+                .kind(InteractionResponseType::Modal)
+                .interaction_response_data(|modal| {
+                    modal
+                        .custom_id(custom_ids::scratchpad_id(command.id()))
+                        .title(tr!("modal-scratchpad"))
+                        .components(|c| {
+                            c.create_action_row(|row| {
+                                row.create_input_text(|input| {
+                                    input
+                                        .custom_id("language")
+                                        .label(tr!("modal-language"))
+                                        .style(InputTextStyle::Short)
+                                        .required(false)
+                                })
+                            })
+                            .create_action_row(|row| {
+                                row.create_input_text(|input| {
+                                    input
+                                        .custom_id("code")
+                                        .label(tr!("modal-code"))
+                                        .style(InputTextStyle::Paragraph)
+                                        .max_length(MAX_CODE_LENGTH)
+                                        .required(true)
+                                })
+                            })
+                        })
+                })
+        })
+        .await
+        .or_log("open the scratchpad");
+}
+
+// The languages that start with what's typed so far, or have it anywhere if none start with it
+pub async fn autocomplete(ctx: &Context, interaction: &AutocompleteInteraction) {
+    let typed = interaction
+        .data
+        .options
+        .iter()
+        .find(|option| option.focused)
+        .and_then(|option| option.value.as_ref())
+        .and_then(|value| value.as_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let languages = all_languages();
+    let names = languages.iter().map(|config| &config.name);
+    let starting = names
+        .clone()
+        .filter(|name| name.to_lowercase().starts_with(&typed))
+        .collect::<Vec<_>>();
+    let matching = if starting.is_empty() {
+        names
+            .filter(|name| name.to_lowercase().contains(&typed))
+            .collect()
+    } else {
+        starting
+    };
+    interaction
+        .create_autocomplete_response(&ctx, |response| {
+            for name in matching.into_iter().take(MAX_CHOICES) {
+                response.add_string_choice(name, name);
+            }
+            response
+        })
+        .await
+        .or_log("suggest languages");
+}
+
+pub async fn submit(ctx: &Context, original: &Interaction, interaction: &ModalSubmitInteraction) {
+    let (scratchpad, command, lang) = match custom_ids::modal(&interaction.data.custom_id) {
+        Some((scratchpad, command, lang)) => (scratchpad, commands::by_id(command), lang),
+        None => return,
+    };
+    let command = match command {
+        Some(command @ (Command::Highlight | Command::Render)) => command,
+        _ => return,
+    };
+    let code = input(interaction, "code").trim_matches('\n');
+    let lang = if scratchpad {
+        input(interaction, "language").trim()
+    } else {
+        lang
+    };
+    let roles = interaction.member.as_ref().map_or(&[][..], |member| &member.roles);
+    if let Err(why) = may_run(interaction.guild_id, interaction.user.id, roles, command) {
+        create_interaction_response(ctx, original, |response| {
+            response.interaction_response_data(|msg| msg.ephemeral(true).content(why))
+        })
+        .await
+        .or_log("reply with the error");
+        return;
+    }
+    // it can still be just for them where the bot can't post, see /server channels
+    let guild_id = interaction.guild_id;
+    let public = scratchpad
+        && storage::posts_in(guild_id, listed_channel(ctx, guild_id, interaction.channel_id));
+    let reply_to = if public {
+        ReplyMethod::PublicFollowup(original)
+    } else {
+        ReplyMethod::EphemeralFollowup(original)
+    };
+    let deferred = defer(ctx, original, !public).await;
+    if deferred.or_log("defer the modal").is_none() {
+        return;
+    }
+    let result = match get_guild_language(guild_id, lang) {
+        Some(config) if !code.is_empty() => match interaction.channel_id.to_channel(&ctx).await {
+            Ok(channel) => {
+                run_command(
+                    ctx,
+                    &channel,
+                    command,
+                    Options::default(),
+                    config,
+                    code,
+                    reply_to,
+                    interaction.user.id,
+                    &interaction.user,
+                    false,
+                )
+                .await
+            }
+            Err(why) => Err(CommandError::discord("discord-channel")(why)),
+        },
+        Some(_) => Err(owo!("modal-no-code").into()),
+        None if scratchpad => Err(owo!("unknown-language", language = lang).into()),
+        None => Err(owo!("unknown-language-anymore", language = lang).into()),
+    };
+    if let Err(why) = result {
+        create_followup_message(ctx, original, |msg: &mut CreateInteractionResponseFollowup| {
+            msg.ephemeral(true).content(why.report())
+        })
+        .await
+        .or_log("reply with the error");
+    }
+}
+
+// what was typed into one of the text inputs
+fn input<'a>(interaction: &'a ModalSubmitInteraction, id: &str) -> &'a str {
+    interaction
+        .data
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) if input.custom_id == id => Some(&input.value[..]),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,119 @@
+use super::*;
+use lru::LruCache;
+use serenity::builder::CreateEmbed;
+
+// Output that doesn't fit in one message is one message with ◀ ▶ buttons under it, which edit it to show another page.
+// The pages are kept here by the message they're on, and the custom_id is page-<prev, at or next>-<which page>.
+// Only the most recent ones are remembered, and after that the buttons just say so
+const REMEMBERED_PAGES: usize = 256;
+
+#[derive(Clone)]
+pub enum Page {
+    Content(String),
+    // each one is the description of an embed, see send_ansi
+    Embeds(Vec<String>),
+}
+
+struct Paged {
+    pages: Vec<Page>,
+    // whatever else goes under it, see parse_components
+    components: Option<CreateComponents>,
+}
+
+lazy_static! {
+    static ref PAGES: std::sync::Mutex<LruCache<MessageId, Paged>> =
+        std::sync::Mutex::new(LruCache::new(REMEMBERED_PAGES));
+}
+
+pub fn remember(message: MessageId, pages: Vec<Page>, components: Option<CreateComponents>) {
+    PAGES.lock().unwrap().put(message, Paged { pages, components });
+}
+
+pub fn embeds(descriptions: &[String]) -> Vec<CreateEmbed> {
+    descriptions
+        .iter()
+        .map(|description| {
+            let mut embed = CreateEmbed::default();
+            embed.description(description);
+            embed
+        })
+        .collect()
+}
+
+// The buttons for being on this page, under the other components
+pub fn components(
+    pages: usize,
+    page: usize,
+    others: Option<&CreateComponents>,
+) -> CreateComponents {
+    let mut components = others.cloned().unwrap_or_default();
+    let (prev, next) = (page.saturating_sub(1), (page + 1).min(pages - 1));
+    components.create_action_row(|row| {
+        row.create_button(|button| {
+            button
+                .custom_id(custom_ids::page_id("prev", prev))
+                .label("◀")
+                .style(ButtonStyle::Secondary)
+                .disabled(page == 0)
+        })
+        // every custom_id has to be different, even on a button that doesn't do anything
+        .create_button(|button| {
+            button
+                .custom_id(custom_ids::page_id("at", page))
+                .label(format!("{}/{pages}", page + 1))
+                .style(ButtonStyle::Secondary)
+                .disabled(true)
+        })
+        .create_button(|button| {
+            button
+                .custom_id(custom_ids::page_id("next", next))
+                .label("▶")
+                .style(ButtonStyle::Secondary)
+                .disabled(page == pages - 1)
+        })
+    });
+    components
+}
+
+pub async fn handle(ctx: &Context, interaction: &MessageComponentInteraction) {
+    let page = custom_ids::page(&interaction.data.custom_id);
+    let shown = {
+        let mut pages = PAGES.lock().unwrap();
+        match (pages.get(&interaction.message.id), page) {
+            (Some(paged), Some(page)) if page < paged.pages.len() => Some((
+                paged.pages[page].clone(),
+                components(paged.pages.len(), page, paged.components.as_ref()),
+            )),
+            _ => None,
+        }
+    };
+    let result = match shown {
+        Some((page, components)) => {
+            interaction
+                .create_interaction_response(&ctx, |response| {
+                    response
+                        .kind(InteractionResponseType::UpdateMessage)
+                        .interaction_response_data(|msg| {
+                            msg.set_components(components);
+                            match page {
+                                Page::Content(content) => msg.content(content),
+                                Page::Embeds(descriptions) => {
+                                    msg.set_embeds(embeds(&descriptions))
+                                }
+                            }
+                        })
+                })
+                .await
+        }
+        None => {
+            interaction
+                .create_interaction_response(&ctx, |response| {
+                    response.interaction_response_data(|msg| {
+                        msg.ephemeral(true).content(owo!("pages-forgotten"))
+                    })
+                })
+                .await
+        }
+    };
+    result.or_log("turn the page");
+}
@@ -0,0 +1,154 @@
+use super::*;
+use lru::LruCache;
+
+// When a context menu command doesn't understand a codeblock's language, it offers the languages it does understand,
+// and runs with whichever one gets picked. That's remembered for the message, so it doesn't have to ask every time.
+// The custom_id is lang-<command>-<message id>-<which menu>, and the value is the language's name.
+// Only the built-in languages are offered, someone who installed a grammar can just write its name on the codeblock.
+// The "Wrong language?" button on output offers them too, see relang_button
+
+const REMEMBERED_CORRECTIONS: usize = 256;
+// discord only allows this many rows of components on a message
+const MAX_MENUS: usize = 5;
+
+lazy_static! {
+    static ref CORRECTIONS: std::sync::Mutex<LruCache<MessageId, String>> =
+        std::sync::Mutex::new(LruCache::new(REMEMBERED_CORRECTIONS));
+}
+
+// what someone said the codeblock in this message actually is
+pub fn corrected(message: MessageId) -> Option<String> {
+    CORRECTIONS.lock().unwrap().get(&message).cloned()
+}
+
+pub async fn offer(
+    ctx: &Context,
+    interaction: &Interaction,
+    command: Command,
+    message: &Message,
+    // what the codeblock said it is, if that's why it's asking
+    lang: Option<&str>,
+) {
+    let name = command.id();
+    let languages = all_languages();
+    let question = match lang {
+        Some(lang) => owo!("picker-unknown", language = lang),
+        None => tr!("picker-question"),
+    };
+    create_interaction_response(ctx, interaction, |response| {
+        response.interaction_response_data(|msg| {
+            msg.ephemeral(true)
+                .content(question)
+                .components(|c| {
+                    let menus = languages.chunks(MAX_MENU_OPTIONS).take(MAX_MENUS);
+                    for (i, chunk) in menus.enumerate() {
+                        c.create_action_row(|row| {
+                            row.create_select_menu(|menu| {
+                                menu.custom_id(custom_ids::lang_id(name, message.id.0, i))
+                                    .placeholder(tr!(
+                                        "picker-placeholder",
+                                        first = chunk[0].name.as_str(),
+                                        last = chunk[chunk.len() - 1].name.as_str(),
+                                    ))
+                                    .options(|options| {
+                                        for config in chunk {
+                                            options.create_option(|option| {
+                                                option.label(&config.name).value(&config.name)
+                                            });
+                                        }
+                                        options
+                                    })
+                            })
+                        });
+                    }
+                    c
+                })
+        })
+    })
+    .await
+    .or_log("offer the languages");
+}
+
+// The "Wrong language?" button, name is which command it's under
+pub async fn retry(ctx: &Context, interaction: &Interaction, name: &str, message: &Message) {
+    match commands::by_id(name) {
+        Some(command) => offer(ctx, interaction, command, message, None).await,
+        None => {
+            create_interaction_response(ctx, interaction, |response| {
+                response.interaction_response_data(|msg| {
+                    msg.ephemeral(true)
+                        .content(owo!("unknown-command", name = name))
+                })
+            })
+            .await
+            .or_log("say the command is unknown");
+        }
+    }
+}
+
+pub async fn handle(
+    ctx: &Context,
+    original: &Interaction,
+    interaction: &MessageComponentInteraction,
+) {
+    let parsed = parse_custom_id(&interaction.data.custom_id);
+    let (command, source, lang) = match (parsed, interaction.data.values.first()) {
+        (Some((command, source)), Some(lang)) => (command, source, lang),
+        _ => {
+            interaction
+                .create_interaction_response(&ctx, |response| {
+                    response.interaction_response_data(|msg| {
+                        msg.ephemeral(true).content(owo!(
+                            "unknown-menu",
+                            id = interaction.data.custom_id.as_str()
+                        ))
+                    })
+                })
+                .await
+                .or_log("say the menu is unknown");
+            return;
+        }
+    };
+    CORRECTIONS.lock().unwrap().put(source, lang.clone());
+    let channel = interaction.channel_id.to_channel(&ctx).await;
+    let channel = match channel.or_log("get the channel") {
+        Some(channel) => channel,
+        None => return,
+    };
+    let message = get_ref(ctx, &channel, source).await;
+    let result = match message {
+        Some(ref message) => {
+            run_command_from_interaction(
+                ctx,
+                command,
+                original,
+                &channel,
+                message,
+                Some(lang.as_str()),
+                false,
+                !storage::public_output(interaction.user.id),
+            )
+            .await
+        }
+        None => InteractionCommandResult::NoCodeblock,
+    };
+    match result {
+        InteractionCommandResult::NoCodeblock | InteractionCommandResult::BadLang(_) => {
+            interaction
+                .create_interaction_response(&ctx, |response| {
+                    response.interaction_response_data(|msg| {
+                        msg.ephemeral(true).content(owo!("codeblock-gone"))
+                    })
+                })
+                .await
+                .or_log("say the codeblock is gone");
+        }
+        InteractionCommandResult::FinishedSuccessfully
+        | InteractionCommandResult::InformedError => (),
+    }
+}
+
+fn parse_custom_id(custom_id: &str) -> Option<(Command, MessageId)> {
+    let (name, source) = custom_ids::lang(custom_id)?;
+    Some((commands::by_id(name)?, source.into()))
+}
@@ -0,0 +1,129 @@
+use super::*;
+use tree_sitter::{Query, QueryCursor};
+
+// +query, for grammar authors debugging their queries. The first codeblock is the code, and the second is the query:
+//
+// +query ```rust
+// fn main() {}
+// ``` ```scheme
+// (function_item name: (identifier) @name)
+// ```
+//
+// every match gets listed with what each of its captures caught
+
+// past this, it just says how many more there were
+const MAX_MATCHES: usize = 100;
+// captured text is shown on one line, and only this much of it
+const MAX_CAPTURE_TEXT: usize = 60;
+
+// (lang, code, query)
+pub fn parse_message(content: &str) -> Option<(&str, &str, &str)> {
+    let rest = content.trim_start().strip_prefix("+query")?;
+    let mut parts = rest.split("```");
+    // anything before the first codeblock is ignored, and so is anything after the second one
+    parts.next()?;
+    let (lang, code) = fence(parts.next()?)?;
+    parts.next()?;
+    // the query's language doesn't matter, it's just so people can get their editor's highlighting for it
+    let (_, source) = fence(parts.next()?)?;
+    Some((lang, code, source))
+}
+
+pub async fn run(ctx: &Context, message: &Message, lang: &str, code: &str, source: &str) {
+    let channel = message.channel(&ctx).await;
+    let channel = match channel.or_log("get the channel") {
+        Some(channel) => channel,
+        None => return,
+    };
+    let guild_id = message.guild_id;
+    let result = match get_guild_language(guild_id, lang) {
+        Some(config) => {
+            let config = languages::for_guild(guild_id, config);
+            let theme = theme::preferred(message.author.id, guild_id);
+            let (code, source) = (code.to_owned(), source.to_owned());
+            workers::run(Task::Parse, move || query(&config, &code, &source, theme)).await
+        }
+        None => Err(owo!("query-unknown-language", language = lang).into()),
+    };
+    let result = match result {
+        Ok(listed) => {
+            let reply_to = ReplyMethod::PublicReference(message);
+            send_ansi(ctx, &channel, &listed, &Options::default(), reply_to, None).await
+        }
+        Err(why) => Err(why),
+    };
+    if let Err(why) = result {
+        message
+            .reply(&ctx, why.report())
+            .await
+            .or_log("reply with the error");
+    }
+}
+
+fn query(
+    config: &LanguageConfig,
+    code: &str,
+    source: &str,
+    theme: &Theme,
+) -> Result<String, CommandError> {
+    let language = config.language.ok_or_else(|| tr!("error-no-grammar"))?;
+    let query = Query::new(language, source).map_err(|e| {
+        // rows and columns start at 1, like in pretty_parse
+        tr!(
+            "query-broken",
+            row = e.row + 1,
+            column = e.column + 1,
+            kind = format!("{:?}", e.kind),
+            message = e.message,
+        )
+    })?;
+    let tree = workers::parse(config, code, None)?;
+    let ansi = |name| theme.get(name).ansi;
+    let names = query.capture_names();
+
+    let mut cursor = QueryCursor::new();
+    let mut listed = String::new();
+    let mut count = 0;
+    for found in cursor.matches(&query, tree.root_node(), code.as_bytes()) {
+        count += 1;
+        if count > MAX_MATCHES {
+            continue;
+        }
+        listed.push_str(&format!(
+            "{}match {count}{} (pattern {})\n",
+            ansi(LIGHT_GREEN),
+            ansi(RESET),
+            found.pattern_index + 1
+        ));
+        for capture in found.captures {
+            let node = capture.node;
+            let tree_sitter::Point { row, column } = node.start_position();
+            let text = code[node.byte_range()].lines().next().unwrap_or_default();
+            let mut shown = text.chars().take(MAX_CAPTURE_TEXT).collect::<String>();
+            if shown.len() < code[node.byte_range()].len() {
+                shown.push('…');
+            }
+            listed.push_str(&format!(
+                "    {}@{}{} {} {}[{}, {}]{} {}{shown}{}\n",
+                ansi(YELLOW),
+                names[capture.index as usize],
+                ansi(RESET),
+                node.kind(),
+                ansi(PINK),
+                row + 1,
+                column + 1,
+                ansi(RESET),
+                ansi(BLUE),
+                ansi(RESET),
+            ));
+        }
+    }
+    if count == 0 {
+        return Err(tr!("query-no-matches").into());
+    }
+    if count > MAX_MATCHES {
+        listed.push_str(&tr!("query-more-matches", count = count - MAX_MATCHES));
+        listed.push('\n');
+    }
+    Ok(listed)
+}
@@ -0,0 +1,153 @@
+use std::collections::{HashSet, VecDeque};
+
+use super::*;
+use tokio::sync::watch;
+
+// Renders take a while and a lot of memory, so only this many happen at once and everyone else waits in line.
+// everyone gets one spot in the line at a time, so one person can't fill it up with their own renders
+const WORKERS: usize = 2;
+
+#[derive(Default)]
+struct State {
+    running: usize,
+    waiting: VecDeque<(UserId, watch::Sender<usize>)>,
+    users: HashSet<UserId>,
+}
+
+lazy_static! {
+    static ref STATE: std::sync::Mutex<State> = Default::default();
+}
+
+// A spot in the line. position is how many jobs are ahead of it plus one, and 0 once it's running.
+// Dropping it gives up the spot (or the worker, if it was already running)
+pub struct Ticket {
+    user: UserId,
+    pub position: watch::Receiver<usize>,
+}
+
+pub fn join(user: UserId) -> Result<Ticket, String> {
+    let mut state = STATE.lock().unwrap();
+    if !state.users.insert(user) {
+        return Err(tr!("queue-already"));
+    }
+    let (sender, position) = watch::channel(usize::MAX);
+    state.waiting.push_back((user, sender));
+    advance(&mut state);
+    Ok(Ticket { user, position })
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let mut state = STATE.lock().unwrap();
+        state.users.remove(&self.user);
+        if *self.position.borrow() == 0 {
+            state.running -= 1;
+        } else {
+            state.waiting.retain(|(user, _)| *user != self.user);
+        }
+        advance(&mut state);
+    }
+}
+
+// The message that tells someone where they are in the line, which goes wherever the render is going to go
+pub struct Status<'a> {
+    reply_to: ReplyMethod<'a>,
+    message: Option<Message>,
+}
+
+impl<'a> Status<'a> {
+    pub fn new(reply_to: ReplyMethod<'a>) -> Status<'a> {
+        Status {
+            reply_to,
+            message: None,
+        }
+    }
+
+    // Sends it the first time, and edits it after that
+    pub async fn show(&mut self, ctx: &Context, channel: &Channel, content: &str) {
+        let result = match (self.reply_to, self.message.take()) {
+            // it's only for them either way
+            (
+                ReplyMethod::EphemeralFollowup(interaction)
+                | ReplyMethod::PublicFollowup(interaction),
+                None,
+            ) => {
+                create_followup_message(ctx, interaction, |msg| {
+                    msg.ephemeral(true).content(content)
+                })
+                .await
+            }
+            (
+                ReplyMethod::EphemeralFollowup(interaction)
+                | ReplyMethod::PublicFollowup(interaction),
+                Some(message),
+            ) => {
+                edit_followup_message(ctx, interaction, message.id, content).await
+            }
+            (ReplyMethod::PublicReference(referenced), None) => {
+                send(ctx, channel, |msg| {
+                    msg.reference_message(referenced)
+                        .allowed_mentions(|mentions| mentions.replied_user(false))
+                        .content(content)
+                })
+                .await
+            }
+            (ReplyMethod::PublicReference(_), Some(mut message)) => message
+                .edit(ctx, |msg| msg.content(content))
+                .await
+                .map(|_| message),
+        };
+        match result {
+            Ok(message) => self.message = Some(message),
+            Err(e) => error!("{e:?}"),
+        }
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.message.is_some()
+    }
+
+    // it's not needed anymore once the render is done
+    pub async fn clear(self, ctx: &Context) {
+        let message = match self.message {
+            Some(message) => message,
+            None => return,
+        };
+        let result = match self.reply_to {
+            ReplyMethod::EphemeralFollowup(interaction)
+            | ReplyMethod::PublicFollowup(interaction) => {
+                delete_followup_message(ctx, interaction, message.id).await
+            }
+            ReplyMethod::PublicReference(_) => message.delete(ctx).await,
+        };
+        if let Err(e) = result {
+            error!("{e:?}");
+        }
+    }
+}
+
+// (running, waiting), for /about bot
+pub fn depth() -> (usize, usize) {
+    let state = STATE.lock().unwrap();
+    (state.running, state.waiting.len())
+}
+
+// Starts as many jobs as there are free workers, and tells everyone else where they are now
+fn advance(state: &mut State) {
+    while state.running < WORKERS {
+        match state.waiting.pop_front() {
+            Some((_, sender)) => {
+                sender.send_replace(0);
+                state.running += 1;
+            }
+            None => break,
+        }
+    }
+    for (i, (_, sender)) in state.waiting.iter().enumerate() {
+        sender.send_if_modified(|position| {
+            let modified = *position != i + 1;
+            *position = i + 1;
+            modified
+        });
+    }
+}
@@ -0,0 +1,111 @@
+use super::*;
+use serenity::model::channel::ReactionType;
+
+// With /server reactions, reacting to a codeblock with one of these runs that command on it,
+// for people who'd rather do that than dig through the context menu.
+// It's the same as writing the command, so the same roles and cooldowns apply.
+// REACTION_HIGHLIGHT, REACTION_RENDER and REACTION_PRETTY_PARSE change which emoji they are,
+// and they can be the id of a custom emoji too (see config.rs in core)
+lazy_static! {
+    static ref TRIGGERS: Vec<(String, Command)> = [
+        ("REACTION_HIGHLIGHT", "🖍", Command::Highlight),
+        ("REACTION_RENDER", "🖼", Command::Render),
+        ("REACTION_PRETTY_PARSE", "🔣", Command::PrettyParse),
+    ]
+    .into_iter()
+    .map(|(name, default, command)| {
+        let emoji = config::var(name).unwrap_or_else(|| default.to_owned());
+        (emoji.trim_end_matches('\u{fe0f}').to_owned(), command)
+    })
+    .collect();
+}
+
+pub async fn added(ctx: &Context, reaction: &Reaction) {
+    let command = match reaction.emoji {
+        // some keyboards add the "show this as an emoji" selector, some don't
+        ReactionType::Unicode(ref emoji) => TRIGGERS
+            .iter()
+            .find(|(trigger, _)| emoji.trim_end_matches('\u{fe0f}') == *trigger)
+            .map(|&(_, command)| command),
+        ReactionType::Custom { id, .. } => TRIGGERS
+            .iter()
+            .find(|(trigger, _)| id.to_string() == *trigger)
+            .map(|&(_, command)| command),
+        _ => None,
+    };
+    let command = match command {
+        Some(command) => command,
+        None => return,
+    };
+    // it's off by default, since people react with these for other reasons too
+    let guild_id = reaction.guild_id;
+    if !storage::guild_setting(guild_id, |guild| guild.reactions).unwrap_or(false) {
+        return;
+    }
+    if !storage::posts_in(guild_id, listed_channel(ctx, guild_id, reaction.channel_id)) {
+        return;
+    }
+    let user = match reaction.user(&ctx).await {
+        Ok(user) if !user.bot => user,
+        Ok(_) => return,
+        Err(why) => {
+            error!("{why}");
+            return;
+        }
+    };
+    let message = match reaction.message(&ctx).await {
+        Ok(message) => message,
+        Err(why) => {
+            error!("{why}");
+            return;
+        }
+    };
+    let found = match codeblock(&normalize_newlines(&message.content)) {
+        Some((_, lang, code, _)) => Some((lang.to_owned(), code.to_owned())),
+        None => attachments::code(&message, guild_id)
+            .await
+            .map(|(lang, code)| (lang.to_owned(), code)),
+    };
+    // reacting to something that isn't code, or isn't code the bot understands, is just a reaction
+    let (config, code) = match found {
+        Some((lang, code)) => match get_guild_language(guild_id, &lang) {
+            Some(config) => (config, code),
+            None => return,
+        },
+        None => return,
+    };
+    info!("{} reacted with {:?}", user.tag(), command);
+    let roles = reaction
+        .member
+        .as_ref()
+        .map_or(&[][..], |member| &member.roles);
+    let channel = reaction.channel_id.to_channel(&ctx).await;
+    let channel = match channel.or_log("get the channel") {
+        Some(channel) => channel,
+        None => return,
+    };
+    let result = match may_run(guild_id, user.id, roles, command) {
+        Ok(()) => {
+            run_command(
+                ctx,
+                &channel,
+                command,
+                Options::default(),
+                config,
+                &code,
+                ReplyMethod::PublicReference(&message),
+                user.id,
+                &message.author,
+                false,
+            )
+            .await
+        }
+        Err(why) => Err(why.into()),
+    };
+    if let Err(why) = result {
+        message
+            .reply(&ctx, why.report())
+            .await
+            .or_log("reply with the error");
+    }
+}
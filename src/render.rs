@@ -1,85 +1,401 @@
-use std::{cmp, iter};
+// The drawing itself lives in custom-highlight-core, this is everything that has to do with discord:
+// guild fonts, the render cache, the queue, and actually sending the images
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::Path,
+};
 
 use super::*;
-use image::{codecs::png::PngDecoder, GenericImage, GenericImageView, Rgba, RgbaImage, SubImage};
-use image::{ImageDecoder, Pixel};
-use rusttype::{Font, Scale};
+pub use custom_highlight_core::render::*;
+use image::RgbaImage;
+use serde_json::Value;
+
+// Fonts uploaded with /server font are kept in fonts/<guild id>
+const FONTS_DIR: &str = "fonts";
+pub const MAX_FONT_SIZE: u64 = 10 * 1024 * 1024;
 
 lazy_static! {
-    static ref FONT: Font<'static> = Font::try_from_bytes(include_bytes!("../font.ttf")).unwrap();
+    static ref GUILD_FONTS: std::sync::Mutex<HashMap<u64, Arc<[u8]>>> = Default::default();
 }
 
-const TEXT_SIZE: u32 = 36;
-const SCALE: Scale = Scale {
-    // Scale::uniform isn't const, so therefore i have to WET (Write Everything Twice!)
-    x: TEXT_SIZE as f32,
-    y: TEXT_SIZE as f32,
-};
+// The font a guild uploaded, if it did and it still loads. Otherwise renders just use the bundled one
+pub fn guild_font(guild_id: Option<GuildId>) -> Option<Arc<[u8]>> {
+    let guild_id = guild_id?;
+    storage::guild_setting(Some(guild_id), |guild| guild.font.clone())?;
+    let mut fonts = GUILD_FONTS.lock().unwrap();
+    if let Some(font) = fonts.get(&guild_id.0) {
+        return Some(font.clone());
+    }
+    match fs::read(Path::new(FONTS_DIR).join(guild_id.to_string())) {
+        Ok(bytes) if is_font(&bytes) => {
+            let font: Arc<[u8]> = bytes.into();
+            fonts.insert(guild_id.0, font.clone());
+            Some(font)
+        }
+        Ok(_) => {
+            error!("the font of guild {guild_id} doesn't load anymore");
+            None
+        }
+        Err(e) => {
+            error!("couldn't read the font of guild {guild_id}: {e:?}");
+            None
+        }
+    }
+}
+
+// None goes back to the bundled font. The name is just so /server font can say what it is
+pub fn set_guild_font(guild_id: GuildId, font: Option<(&str, Vec<u8>)>) -> Result<(), String> {
+    let path = Path::new(FONTS_DIR).join(guild_id.to_string());
+    GUILD_FONTS.lock().unwrap().remove(&guild_id.0);
+    let name = match font {
+        Some((name, bytes)) => {
+            if !is_font(&bytes) {
+                return Err(tr!("font-not-a-font", file = name));
+            }
+            fs::create_dir_all(FONTS_DIR)
+                .map_err(|e| tr!("font-not-saved", why = e.to_string()))?;
+            fs::write(&path, bytes).map_err(|e| tr!("font-not-saved", why = e.to_string()))?;
+            Some(name.to_owned())
+        }
+        None => {
+            if let Err(e) = fs::remove_file(&path) {
+                error!("{e:?}");
+            }
+            None
+        }
+    };
+    storage::write(|data| data.guilds.entry(guild_id.0).or_default().font = name);
+    Ok(())
+}
 
-#[derive(Debug)]
-enum LineHighlightEvent<'a> {
-    Color(Color),
-    Segment(&'a str),
-    Newline,
+// What someone picked with /preferences size, or the default if they didn't
+pub fn preferred_size(user: UserId) -> u32 {
+    storage::read(|data| data.users.get(&user.0).and_then(|settings| settings.size))
+        // the bounds could have changed since they picked it
+        .map_or(DEFAULT_TEXT_SIZE, |size| size.clamp(MIN_TEXT_SIZE, MAX_TEXT_SIZE))
+}
+
+pub fn set_preferred_size(user: UserId, size: Option<u32>) {
+    storage::write(|data| data.users.entry(user.0).or_default().size = size);
+}
+
+pub async fn title(author: &User, language: &str) -> Title {
+    let avatar = match avatar(author).await {
+        Ok(avatar) => Some(avatar),
+        Err(e) => {
+            error!("couldn't get the avatar of {}: {e}", author.tag());
+            None
+        }
+    };
+    Title {
+        author: author.tag(),
+        language: language.to_owned(),
+        avatar,
+    }
+}
+
+async fn avatar(user: &User) -> Result<RgbaImage, String> {
+    // avatar_url() is a webp, and image can't always decode those
+    let url = match user.avatar {
+        Some(ref hash) => format!("https://cdn.discordapp.com/avatars/{}/{hash}.png?size=64", user.id),
+        None => user.default_avatar_url(),
+    };
+    let bytes = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?
+        .bytes()
+        .await
+        .map_err(|e| e.to_string())?;
+    let image = image::load_from_memory(&bytes).map_err(|e| e.to_string())?;
+    // this is resized when it's drawn, it doesn't know how big the text is yet
+    Ok(image.to_rgba8())
 }
 
 pub async fn render_command(
     ctx: &Context,
     channel: &Channel,
-    config: &'static LanguageConfig,
+    config: Arc<LanguageConfig>,
     code: &str,
+    options: RenderOptions,
     reply_to: ReplyMethod<'_>,
+    invoker: UserId,
     add_components: bool,
-) -> Result<(), &'static str> {
-    println!("begin render ({} bytes)", code.len());
+) -> Result<Message, CommandError> {
+    debug!("begin render ({} bytes)", code.len());
+    let key = cache_key(&[&config], &[code], &options);
+    let description = tr!("render-description", language = config.name.as_str());
+    let description = describe(&description, code, options.range);
     let code = code.to_owned();
-    let buffer = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, &'static str> {
-        let image = render(config, &code)?;
-        println!("Begin encode: {}x{}", image.width(), image.height());
-        // I've tested all other encodings that ``image`` comes with
-        // and the only other one that even worked was JPEG
-        // which is too moldy for text, and therefore unacceptable.
-        // PNG is the only acceptable encoding.
-        //
-        // I've hand-picked these settings through trial and error:
-        //
-        // CompressionType = Run length encoding
-        //
-        // Because most of the image is gonna be the same gray BG color
-        // especially when the image is big enough that
-        // the choice of these settings actually matter
-        //
-        // FilterType = Up (scanline above)
-        //
-        // Because text generally contains a lot of vertical lines
-        // and this measurably decreased size by about 20% with no noticeable delay
-        // for the example.ursl in URSL repository
-        let mut buffer = Vec::new();
-        let png = png::PngEncoder::new_with_quality(
-            &mut buffer,
-            png::CompressionType::Rle,
-            png::FilterType::Up,
-        );
-        png.write_image(&image, image.width(), image.height(), ColorType::Rgba8)
-            .err_as("The image failed to encode")?;
-        Ok(buffer)
-    })
+    let draw = move |options: &RenderOptions| render(&config, &code, options);
+    send_render(
+        ctx,
+        channel,
+        options,
+        key,
+        draw,
+        &description,
+        reply_to,
+        invoker,
+        add_components,
+    )
     .await
-    .err_as("The rendering task failed to join")??;
-    let bytes = &buffer[..];
-    println!("encoded png ({} bytes)", bytes.len());
-    // discord has an upload limit of 8MB. Is that actually MiB? I don't know, and i'd rather be on the safe side of that margin
-    if bytes.len() > 8_000_000 {
-        return Err("The resulting image is WAYY TOO BIG, get lost");
+}
+
+// A few codeblocks from the same message in one reply, see blocks.rs
+pub async fn render_blocks_command(
+    ctx: &Context,
+    channel: &Channel,
+    blocks: Vec<(Arc<LanguageConfig>, String)>,
+    options: RenderOptions,
+    reply_to: ReplyMethod<'_>,
+    invoker: UserId,
+) -> Result<Message, CommandError> {
+    debug!("begin render of {} codeblocks", blocks.len());
+    let configs = blocks.iter().map(|(config, _)| config).collect::<Vec<_>>();
+    let sources = blocks.iter().map(|(_, code)| code.as_str()).collect::<Vec<_>>();
+    let key = cache_key(&configs, &sources, &options);
+    let description = tr!("render-blocks-description", count = blocks.len());
+    let draw = move |options: &RenderOptions| render_blocks(&blocks, options);
+    send_render(
+        ctx,
+        channel,
+        options,
+        key,
+        draw,
+        &description,
+        reply_to,
+        invoker,
+        false,
+    )
+    .await
+}
+
+// discord cuts descriptions off at 1024 characters, and nobody wants a screen reader to read a minified line anyways
+const DESCRIBED_LINE_LENGTH: usize = 200;
+
+// Alt text, since to a screen reader a render is just an image: what it is, how many lines, and the first of them
+pub fn describe(what: &str, code: &str, range: Option<(usize, usize)>) -> String {
+    let (first, last) = range.unwrap_or((1, usize::MAX));
+    let mut lines = code.lines().skip(first - 1).take(last - (first - 1));
+    let first_line = lines.next().unwrap_or_default().trim();
+    let count = 1 + lines.count();
+    let mut description = tr!("render-alt-text", what = what, lines = count);
+    description.extend(first_line.chars().take(DESCRIBED_LINE_LENGTH));
+    if first_line.chars().count() > DESCRIBED_LINE_LENGTH {
+        description.push('…');
+    }
+    description
+}
+
+// serenity doesn't know about attachment descriptions, but discord reads them from the attachments in the payload,
+// matched up with the files by their index
+fn describe_files(payload: &mut HashMap<&'static str, Value>, descriptions: &[String]) {
+    let attachments = descriptions
+        .iter()
+        .enumerate()
+        .map(|(i, description)| serde_json::json!({ "id": i, "description": description }))
+        .collect();
+    payload.insert("attachments", Value::Array(attachments));
+}
+
+// Renders that were already sent, so pressing Render on the same codeblock again
+// (which happens a lot when a few people all want to see it) doesn't draw it all over again.
+// They're kept in cache::backend(), so with Redis every process of the bot shares them
+pub struct CacheKey {
+    hash: u64,
+}
+
+impl CacheKey {
+    fn name(&self) -> String {
+        format!("render:{:016x}", self.hash)
+    }
+}
+
+// the only names a render can have, so a cached one can go back to being a &'static str
+const FILE_NAMES: [&str; 2] = ["code.png", "code.webp"];
+
+// The size it ended up at, then every file as the length of its name, the name, the length of it, and it
+fn encode_cached(files: &[(Vec<u8>, &'static str)], size: u32) -> Vec<u8> {
+    let mut bytes = size.to_le_bytes().to_vec();
+    for (file, name) in files {
+        bytes.push(name.len() as u8);
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&(file.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(file);
     }
-    match reply_to {
-        ReplyMethod::EphemeralFollowup(interaction) => {
+    bytes
+}
+
+// None for anything that isn't what encode_cached made, which is just a render that isn't cached
+fn decode_cached(bytes: &[u8]) -> Option<(Vec<(Vec<u8>, &'static str)>, u32)> {
+    let size = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?);
+    let mut rest = &bytes[4..];
+    let mut files = Vec::new();
+    while let Some((&name_len, after)) = rest.split_first() {
+        let name_len = name_len as usize;
+        let name = after.get(..name_len)?;
+        let name = *FILE_NAMES.iter().find(|known| known.as_bytes() == name)?;
+        let after = &after[name_len..];
+        let len = u64::from_le_bytes(after.get(..8)?.try_into().ok()?) as usize;
+        let file = after.get(8..8usize.checked_add(len)?)?;
+        files.push((file.to_vec(), name));
+        rest = &after[8 + len..];
+    }
+    Some((files, size))
+}
+
+// Everything that changes what the render looks like. Languages with the same name can still be different
+// (every guild can have its own query), so where the highlighting came from is part of it too
+pub fn cache_key(
+    configs: &[&Arc<LanguageConfig>],
+    sources: &[&str],
+    options: &RenderOptions,
+) -> CacheKey {
+    let mut hasher = DefaultHasher::new();
+    for config in configs {
+        config.fingerprint.hash(&mut hasher);
+        // the colors can change with /theme set-capture
+        format!("{:?}", config.formats.read().unwrap()).hash(&mut hasher);
+    }
+    sources.hash(&mut hasher);
+    options.theme.name.hash(&mut hasher);
+    options.line_numbers.hash(&mut hasher);
+    if let Some(ref title) = options.title {
+        title.author.hash(&mut hasher);
+        title.language.hash(&mut hasher);
+        title.avatar.as_ref().map(|avatar| avatar.as_raw()).hash(&mut hasher);
+    }
+    options.wrap.hash(&mut hasher);
+    options.tab_width.hash(&mut hasher);
+    options.font.hash(&mut hasher);
+    options.size.hash(&mut hasher);
+    options.encoding.hash(&mut hasher);
+    options.range.hash(&mut hasher);
+    options.emphasis.hash(&mut hasher);
+    options.transparent.hash(&mut hasher);
+    options.animate.hash(&mut hasher);
+    CacheKey {
+        hash: hasher.finish(),
+    }
+}
+
+// Draws it in the background (maybe more than once, see render_to_fit) and sends it,
+// or just sends it if the exact same thing was rendered recently. The message it sent is returned
+pub async fn send_render(
+    ctx: &Context,
+    channel: &Channel,
+    options: RenderOptions,
+    key: CacheKey,
+    draw: impl Fn(&RenderOptions) -> Result<Vec<RgbaImage>, HighlightError> + Send + 'static,
+    description: &str,
+    reply_to: ReplyMethod<'_>,
+    invoker: UserId,
+    add_components: bool,
+) -> Result<Message, CommandError> {
+    // checked before drawing it, so it doesn't wait in the queue for nothing
+    if !reply_can(ctx, channel, reply_to, Permissions::ATTACH_FILES) {
+        return Err(tr!("render-no-files").into());
+    }
+    let requested_size = options.size;
+    let spoiler = options.spoiler;
+    let cached = cache::backend()
+        .get(&key.name())
+        .and_then(|bytes| decode_cached(&bytes));
+    let (files, size) = match cached {
+        Some(cached) => {
+            debug!("sending a cached render");
+            usage::rendered(true);
+            cached
+        }
+        None => {
+            // this gives up the spot in the queue when it's dropped, after the render is done
+            let mut ticket = queue::join(invoker)?;
+            let mut status = queue::Status::new(reply_to);
+            let rendered = async {
+                loop {
+                    let position = *ticket.position.borrow_and_update();
+                    if position == 0 {
+                        break;
+                    }
+                    let content = tr!("queue-position", position = position);
+                    status.show(ctx, channel, &content).await;
+                    if ticket.position.changed().await.is_err() {
+                        // the spot was taken out of the line, which means it's running now
+                        break;
+                    }
+                }
+                // only worth saying if they were told about the queue in the first place
+                if status.is_shown() {
+                    status.show(ctx, channel, &tr!("rendering")).await;
+                }
+                workers::run(Task::Render, move || {
+                    render_to_fit(draw, options, *UPLOAD_LIMIT)
+                })
+                .await
+            };
+            // when the bot is shutting down and this takes too long, it's given up on,
+            // so the message about the queue doesn't stay there forever. see shutdown.rs
+            let result = tokio::select! {
+                result = rendered => Some(result),
+                () = shutdown::given_up() => None,
+            };
+            drop(ticket);
+            status.clear(ctx).await;
+            let (files, size) = match result {
+                Some(result) => result?,
+                None => return Err(tr!("interrupted").into()),
+            };
+            usage::rendered(false);
+            cache::backend().put(&key.name(), encode_cached(&files, size));
+            (files, size)
+        }
+    };
+    // numbered when there's more than one, so they stay in order
+    let attachments = files
+        .iter()
+        .enumerate()
+        .map(|(i, (bytes, file_name))| {
+            let file_name = match files.len() {
+                1 => file_name.to_string(),
+                _ => file_name.replacen('.', &format!("-{}.", i + 1), 1),
+            };
+            // discord decides what's a spoiler by the file name
+            let file_name = if spoiler {
+                format!("{SPOILER_PREFIX}{file_name}")
+            } else {
+                file_name
+            };
+            (&bytes[..], file_name)
+        })
+        .collect::<Vec<_>>();
+    let descriptions = (1..=attachments.len())
+        .map(|i| match attachments.len() {
+            1 => description.to_owned(),
+            n => tr!(
+                "render-part",
+                description = description,
+                part = i,
+                parts = n
+            ),
+        })
+        .collect::<Vec<_>>();
+    let note = if size < requested_size {
+        tr!("render-shrunk", size = size, requested = requested_size)
+    } else {
+        String::new()
+    };
+    let sent = match reply_to {
+        ReplyMethod::EphemeralFollowup(interaction) | ReplyMethod::PublicFollowup(interaction) => {
             create_followup_message(ctx, interaction, |msg| {
-                println!("ephemeral msg");
-                msg.ephemeral(true).add_file((bytes, "code.png"))
+                describe_files(&mut msg.0, &descriptions);
+                msg.ephemeral(reply_to.ephemeral())
+                    .content(&note)
+                    .add_files(attachments.iter().map(|&(bytes, ref name)| (bytes, name.as_str())))
             })
             .await
-            .unwrap()
         }
         ReplyMethod::PublicReference(referenced) => send(ctx, channel, |msg| {
             if add_components {
@@ -87,229 +403,72 @@ pub async fn render_command(
                     c.create_action_row(|row| {
                         row.create_button(|button| {
                             button
-                                .custom_id("highlight")
+                                .custom_id(custom_ids::button_id(Command::Highlight.id(), None))
                                 .emoji('📋')
-                                .label("Highlight as ANSI")
+                                .label(tr!("render-highlight"))
                                 .style(ButtonStyle::Primary)
                         })
+                        .create_button(|button| {
+                            button
+                                .custom_id(custom_ids::button_id("spoiler", None))
+                                .emoji('🙈')
+                                .label(tr!("render-spoiler"))
+                                .style(ButtonStyle::Secondary)
+                        });
+                        relang_button(row, Command::Render, referenced.id)
                     })
                 });
             }
+            describe_files(&mut msg.0, &descriptions);
             msg.reference_message(referenced)
                 .allowed_mentions(|mentions| mentions.replied_user(false))
-                .add_file((bytes, "code.png"))
+                .content(&note)
+                .add_files(attachments.iter().map(|&(bytes, ref name)| (bytes, name.as_str())))
         })
-        .await
-        .unwrap(),
+        .await,
     };
-    Ok(())
+    // the permissions can change while it's in the queue, or it's just too big after all
+    sent.map_err(CommandError::discord("discord-send-render"))
 }
 
-// Right-to-left text is completely unsupported because none of my spoken languages are right-to-left so it does not affect me personally, and is therefore seen as an inconvenience rather than a requirement.
-pub fn render(config: &LanguageConfig, code: &str) -> Result<RgbaImage, &'static str> {
-    let events = match config.highlight {
-        HighlightType::TreeSitter(ref highlight) => {
-            let mut highlighter = Highlighter::new();
-            let mut events = Vec::new();
-            let mut colors = ne_vec![RESET];
-            for event in highlighter
-                .highlight(highlight, code.as_bytes(), None, |_| None)
-                .err_as(TS_ERROR)?
-            {
-                match event.err_as(TS_ERROR)? {
-                    HighlightEvent::HighlightStart(Highlight(i)) => {
-                        colors.push(config.formats[i]);
-                        events.push(LineHighlightEvent::Color(*colors.last()))
-                    }
-                    HighlightEvent::Source { start, end } => {
-                        let text = &code[start..end];
-                        let (first, lines) = text
-                            .split_once("\n")
-                            .map_or((text, None), |(first, lines)| (first, Some(lines)));
-                        events.push(LineHighlightEvent::Segment(first));
-                        if let Some(lines) = lines {
-                            events.extend(lines.split("\n").flat_map(|line| {
-                                [
-                                    LineHighlightEvent::Newline,
-                                    LineHighlightEvent::Segment(line),
-                                ]
-                            }));
-                        }
-                    }
-                    HighlightEvent::HighlightEnd => {
-                        colors.pop();
-                        events.push(LineHighlightEvent::Color(*colors.last()))
-                    }
-                }
-            }
-            events
-        }
-        HighlightType::Plaintext => {
-            let (first, lines) = code
-                .split_once("\n")
-                .map_or((code, None), |(first, lines)| (first, Some(lines)));
-            let mut events = Vec::new();
-            events.push(LineHighlightEvent::Segment(first));
-            if let Some(lines) = lines {
-                events.extend(lines.split("\n").flat_map(|line| {
-                    [
-                        LineHighlightEvent::Newline,
-                        LineHighlightEvent::Segment(line),
-                    ]
-                }));
-            }
-            events
-        }
-    };
-
-    let lines = {
-        let mut next_color = RESET;
-        let mut lines = Vec::new();
-        let mut current_line = Vec::new();
+pub const SPOILER_PREFIX: &str = "SPOILER_";
 
-        for event in events {
-            match event {
-                LineHighlightEvent::Color(color) => next_color = color,
-                LineHighlightEvent::Segment(seg) => {
-                    current_line.push((next_color, seg));
-                }
-                LineHighlightEvent::Newline => {
-                    lines.push(current_line);
-                    current_line = Vec::new();
-                }
-            }
-        }
-        lines.push(current_line);
-        lines
+// The spoiler button on a render. Attachments can't be renamed, so they're downloaded and uploaded again
+// with or without SPOILER_ in front, depending on whether the first one has it right now
+pub async fn toggle_spoiler(ctx: &Context, message: &Message) -> Result<(), CommandError> {
+    let spoiler = match message.attachments.first() {
+        Some(attachment) => !attachment.filename.starts_with(SPOILER_PREFIX),
+        None => return Err(tr!("render-nothing-to-spoil").into()),
     };
-
-    let line_strings = lines
-        .iter()
-        .map(|segs| {
-            segs.iter()
-                .fold(String::new(), |line, &(_, seg)| line + seg)
-        })
-        .collect::<Vec<_>>();
-
-    let width = line_strings.iter().fold(0, |width, line| {
-        let mut caret = 0f32;
-        let mut last_glyph = None;
-
-        for ch in line.chars() {
-            let glyph = FONT.glyph(ch).scaled(SCALE);
-            if let Some(last) = last_glyph {
-                caret += FONT.pair_kerning(SCALE, last, glyph.id());
-            }
-            caret += glyph.h_metrics().advance_width;
-            last_glyph = Some(glyph.id());
-        }
-        cmp::max(width, caret.ceil() as u32)
-    });
-    let height = SCALE.y as u32 * lines.len() as u32;
-    println!("dimensions are {width}x{height}");
-
-    let mut image = RgbaImage::default();
-    let safe_area = &mut border::make_image(&mut image, width, height);
-
-    let mut y = 0f32;
-    let ascent = FONT.v_metrics(SCALE).ascent;
-    for (line, segments) in iter::zip(line_strings, lines) {
-        let colors = segments
-            .into_iter()
-            .flat_map(|(color, text)| iter::repeat(color).take(text.len()));
-        for (color, glyph) in iter::zip(
-            colors,
-            FONT.layout(
-                &line,
-                SCALE,
-                rusttype::Point {
-                    x: 0f32,
-                    y: y + ascent,
-                },
-            ),
-        ) {
-            if let Some(bounds) = glyph.pixel_bounding_box() {
-                glyph.draw(|dx, dy, v| {
-                    let a = (v * u8::MAX as f32).trunc() as u8;
-                    let Rgb([r, g, b]) = color.rgb;
-                    let color = Rgba([r, g, b, a]);
-
-                    let x = bounds.min.x as u32 + dx;
-                    let y = bounds.min.y as u32 + dy;
-                    let mut pixel = safe_area.get_pixel(x, y);
-                    pixel.blend(&color);
-                    safe_area.put_pixel(x, y, pixel);
-                });
-            }
-        }
-        y += SCALE.y;
-    }
-    Ok(image)
-}
-
-mod border {
-    use super::*;
-
-    const R: u32 = 10;
-    lazy_static! {
-        static ref BORDER: RgbaImage = {
-            let bytes = include_bytes!("../border.png").as_ref();
-            let png = PngDecoder::new(bytes).unwrap();
-            let width = {
-                let (x, y) = png.dimensions();
-                assert_eq!(x, y);
-                x
-            };
-            assert_eq!(R * 2 + 1, width);
-            assert_eq!(png.color_type(), ColorType::Rgba8);
-            let mut image = RgbaImage::new(width, width);
-            png.read_image(&mut image).unwrap();
-            image
+    let mut files = Vec::new();
+    for attachment in &message.attachments {
+        let bytes = attachment
+            .download()
+            .await
+            .map_err(CommandError::discord("discord-download-render"))?;
+        let name = attachment.filename.trim_start_matches(SPOILER_PREFIX);
+        let name = if spoiler {
+            format!("{SPOILER_PREFIX}{name}")
+        } else {
+            name.to_owned()
         };
-        static ref TOP_LEFT: SubImage<&'static RgbaImage> = BORDER.view(0, 0, R, R);
-        static ref TOP_RIGHT: SubImage<&'static RgbaImage> = BORDER.view(R + 1, 0, R, R);
-        static ref BOTTOM_LEFT: SubImage<&'static RgbaImage> = BORDER.view(0, R + 1, R, R);
-        static ref BOTTOM_RIGHT: SubImage<&'static RgbaImage> = BORDER.view(R + 1, R + 1, R, R);
-        static ref TOP: SubImage<&'static RgbaImage> = BORDER.view(R, 0, 1, R);
-        static ref LEFT: SubImage<&'static RgbaImage> = BORDER.view(0, R, R, 1);
-        static ref BOTTOM: SubImage<&'static RgbaImage> = BORDER.view(R, R + 1, 1, R);
-        static ref RIGHT: SubImage<&'static RgbaImage> = BORDER.view(R + 1, R, R, 1);
-        static ref CENTER: Rgba<u8> = *BORDER.get_pixel(R, R);
+        files.push((attachment.id, bytes, name));
     }
-
-    pub fn make_image<'a>(
-        image: &'a mut RgbaImage,
-        width: u32,
-        height: u32,
-    ) -> SubImage<&'a mut RgbaImage> {
-        let real_width = width + R * 2;
-        let real_height = height + R * 2;
-        *image = RgbaImage::from_pixel(real_width, real_height, *CENTER);
-        // tokio::task::yield_now().await;
-        put(&mut image.sub_image(0, 0, R, R), *TOP_LEFT);
-        put(&mut image.sub_image(R + width, 0, R, R), *TOP_RIGHT);
-        put(&mut image.sub_image(0, R + height, R, R), *BOTTOM_LEFT);
-        put(
-            &mut image.sub_image(R + width, R + height, R, R),
-            *BOTTOM_RIGHT,
-        );
-        for x in 0..width {
-            put(&mut image.sub_image(R + x, 0, 1, R), *TOP);
-            put(&mut image.sub_image(R + x, R + height, 1, R), *BOTTOM);
-        }
-        for y in 0..height {
-            put(&mut image.sub_image(0, R + y, R, 1), *LEFT);
-            put(&mut image.sub_image(R + width, R + y, R, 1), *RIGHT);
-        }
-        image.sub_image(R, R, width, height)
-    }
-
-    fn put(destination: &mut SubImage<&mut RgbaImage>, source: SubImage<&RgbaImage>) {
-        assert_eq!(destination.dimensions(), source.dimensions());
-        for y in 0..source.height() {
-            for x in 0..source.width() {
-                destination.put_pixel(x, y, source.get_pixel(x, y));
+    message
+        .clone()
+        .edit(ctx, |msg| {
+            for (id, bytes, name) in &files {
+                msg.remove_existing_attachment(*id)
+                    .attachment((&bytes[..], name.as_str()));
             }
-        }
-    }
+            msg
+        })
+        .await
+        .map_err(CommandError::discord("discord-edit-render"))
+}
+
+lazy_static! {
+    // discord has an upload limit of 8MB. Is that actually MiB? I don't know, and i'd rather be on the safe side of that margin
+    // boosted servers can upload more, so UPLOAD_LIMIT can go up for a bot that's only in those
+    pub static ref UPLOAD_LIMIT: usize = config::parse("UPLOAD_LIMIT").unwrap_or(8_000_000);
 }
@@ -0,0 +1,161 @@
+use super::*;
+use lru::LruCache;
+use tree_sitter::{InputEdit, Point};
+
+// When a codeblock that was +parse'd gets edited, the replies get edited to match, so there's no need to ask again.
+// The old tree is kept around so tree-sitter only has to look at whatever changed.
+// Only the most recent ones are remembered, and forgetting one just means the replies stay as they were
+const TRACKED_PARSES: usize = 64;
+
+pub struct Tracked {
+    // already for_guild
+    pub config: Arc<LanguageConfig>,
+    pub tree: Tree,
+    pub code: String,
+    pub plain: bool,
+    // whoever asked for it, options.theme still wins over this
+    pub theme: &'static Theme,
+    pub options: Options,
+    pub replies: Vec<Message>,
+}
+
+lazy_static! {
+    static ref TRACKED: std::sync::Mutex<LruCache<MessageId, Tracked>> =
+        std::sync::Mutex::new(LruCache::new(TRACKED_PARSES));
+}
+
+pub fn track(source: MessageId, tracked: Tracked) {
+    TRACKED.lock().unwrap().put(source, tracked);
+}
+
+// the replies to it, if it was tracked
+pub fn forget(source: MessageId) -> Option<Vec<Message>> {
+    TRACKED.lock().unwrap().pop(&source).map(|tracked| tracked.replies)
+}
+
+pub async fn update(ctx: &Context, event: &MessageUpdateEvent) {
+    // embeds loading in also counts as an edit, but it doesn't change the content
+    let content = match event.content {
+        Some(ref content) => normalize_newlines(content),
+        None => return,
+    };
+    // it's taken out while the replies are edited, and only goes back if it's still a +parse
+    let tracked = match TRACKED.lock().unwrap().pop(&event.id) {
+        Some(tracked) => tracked,
+        None => return,
+    };
+    if let Some(tracked) = refresh(ctx, event, &content, tracked).await {
+        track(event.id, tracked);
+    }
+}
+
+async fn refresh(
+    ctx: &Context,
+    event: &MessageUpdateEvent,
+    content: &str,
+    mut tracked: Tracked,
+) -> Option<Tracked> {
+    let (before, lang, code, after) = codeblock(content)?;
+    if !after.trim().is_empty() {
+        return None;
+    }
+    // a different language can't reuse the old tree
+    if get_guild_language(event.guild_id, lang)?.name != tracked.config.name {
+        return None;
+    }
+    match parse_command(before) {
+        Some(Ok((command @ (Command::PrettyParse | Command::PlainParse), options))) => {
+            tracked.plain = command == Command::PlainParse;
+            tracked.options = options;
+        }
+        // it was parsed from the context menu, so there's no command to read
+        None => {}
+        _ => return None,
+    }
+    tracked.tree.edit(&input_edit(&tracked.code, code));
+    let theme = tracked.options.theme.unwrap_or(tracked.theme);
+    let theme = if tracked.plain { None } else { Some(theme) };
+    let (config, old, source, options) = (
+        tracked.config.clone(),
+        tracked.tree.clone(),
+        code.to_owned(),
+        tracked.options.clone(),
+    );
+    let (tree, (chunks, depth, collapsed)) = workers::run(Task::Parse, move || {
+        let tree = workers::parse(&config, &source, Some(&old))?;
+        let chunks = pretty_parse_chunks(&tree, &source, theme, &[], &options)?;
+        Ok::<_, CommandError>((tree, chunks))
+    })
+    .await
+    .ok()?;
+    let components = parse_components(
+        event.id,
+        tracked.plain,
+        tracked.options.verbose,
+        depth,
+        &collapsed,
+        true,
+    );
+
+    // the reply goes back to the first page, since the others might not be there anymore
+    let shown = if chunks.len() > 1 {
+        pages::components(chunks.len(), 0, components.as_ref())
+    } else {
+        components.clone().unwrap_or_default()
+    };
+    let mut old = tracked.replies.into_iter();
+    let mut reply = old.next()?;
+    let result = reply
+        .edit(&ctx, |msg| msg.content(&chunks[0]).set_components(shown))
+        .await;
+    // probably someone deleted it, in which case they don't want it anymore
+    if let Err(why) = result {
+        error!("{why}");
+        return None;
+    }
+    if chunks.len() > 1 {
+        let pages = chunks.into_iter().map(Page::Content).collect();
+        pages::remember(reply.id, pages, components);
+    }
+    // from before there were pages, when long output took a few messages
+    for reply in old {
+        if let Err(why) = reply.delete(&ctx).await {
+            error!("{why}");
+        }
+    }
+    Some(Tracked {
+        tree,
+        code: code.to_owned(),
+        replies: vec![reply],
+        ..tracked
+    })
+}
+
+// Everything between the longest common start and end is what changed
+fn input_edit(old: &str, new: &str) -> InputEdit {
+    let (old, new) = (old.as_bytes(), new.as_bytes());
+    let start = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let end = old[start..]
+        .iter()
+        .rev()
+        .zip(new[start..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_end, new_end) = (old.len() - end, new.len() - end);
+    InputEdit {
+        start_byte: start,
+        old_end_byte: old_end,
+        new_end_byte: new_end,
+        start_position: point(old, start),
+        old_end_position: point(old, old_end),
+        new_end_position: point(new, new_end),
+    }
+}
+
+// tree-sitter's columns are in bytes too
+fn point(text: &[u8], byte: usize) -> Point {
+    let before = &text[..byte];
+    let row = before.iter().filter(|&&b| b == b'\n').count();
+    let column = byte - before.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    Point { row, column }
+}
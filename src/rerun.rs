@@ -0,0 +1,115 @@
+use super::*;
+use lru::LruCache;
+
+// When a codeblock that was highlighted or rendered gets edited, the old output is deleted and the command runs again,
+// so there's never a stale highlight under it. +parse has its own thing that edits the replies, see reparse.rs.
+// Only the most recent ones are remembered, and forgetting one just means the replies stay as they were
+const TRACKED_RUNS: usize = 64;
+
+pub struct Ran {
+    pub command: Command,
+    pub options: Options,
+    pub invoker: UserId,
+    pub add_components: bool,
+    pub replies: Vec<Message>,
+}
+
+lazy_static! {
+    static ref TRACKED: std::sync::Mutex<LruCache<MessageId, Ran>> =
+        std::sync::Mutex::new(LruCache::new(TRACKED_RUNS));
+}
+
+pub fn track(source: MessageId, ran: Ran) {
+    storage::track_replies(source, &ran.replies);
+    TRACKED.lock().unwrap().put(source, ran);
+}
+
+// Replies don't make much sense without what they're replying to, so they go away with it.
+// The ones from before the bot last started are only in the database, see storage::take_replies
+pub async fn deleted(ctx: &Context, source: MessageId) {
+    let ran = TRACKED.lock().unwrap().pop(&source).map(|ran| ran.replies);
+    let mut replies = ran
+        .into_iter()
+        .chain(reparse::forget(source))
+        .flatten()
+        .map(|reply| (reply.channel_id, reply.id, reply.webhook_id))
+        .chain(storage::take_replies(source))
+        .collect::<Vec<_>>();
+    // most of them are in both
+    replies.sort_by_key(|&(_, id, _)| id);
+    replies.dedup_by_key(|&mut (_, id, _)| id);
+    for (channel, reply, webhook_id) in replies {
+        if let Err(why) = webhook::delete(ctx, channel, reply, webhook_id).await {
+            error!("{why}");
+        }
+    }
+}
+
+pub async fn update(ctx: &Context, event: &MessageUpdateEvent) {
+    // embeds loading in also counts as an edit, but it doesn't change the content
+    let content = match event.content {
+        Some(ref content) => normalize_newlines(content),
+        None => return,
+    };
+    // running it again tracks it again, if it's still a codeblock that gets a command
+    let ran = match TRACKED.lock().unwrap().pop(&event.id) {
+        Some(ran) => ran,
+        None => return,
+    };
+    // they're deleted below, or they stay but aren't worth keeping track of anymore
+    storage::take_replies(event.id);
+    let (before, lang, code, after) = match codeblock(&content) {
+        Some(codeblock) => codeblock,
+        None => return,
+    };
+    let (command, options) = match parse_command(before) {
+        Some(Ok((command, options))) if command == ran.command => (command, options),
+        // it was from a button or the context menu, or it was an automatic render, so there's no command to read
+        None => (ran.command, ran.options),
+        // a different command is a different thing, not an edit of this one
+        _ => return,
+    };
+    if !after.trim().is_empty() {
+        return;
+    }
+    let config = match get_guild_language(event.guild_id, lang) {
+        Some(config) => config,
+        None => return,
+    };
+    let source = match event.channel_id.message(&ctx, event.id).await {
+        Ok(source) => source,
+        Err(why) => {
+            error!("{why}");
+            return;
+        }
+    };
+    let channel = event.channel_id.to_channel(&ctx).await;
+    let channel = match channel.or_log("get the channel") {
+        Some(channel) => channel,
+        None => return,
+    };
+    for reply in ran.replies {
+        if let Err(why) = webhook::delete(ctx, reply.channel_id, reply.id, reply.webhook_id).await {
+            error!("{why}");
+        }
+    }
+    let result = run_command(
+        ctx,
+        &channel,
+        command,
+        options,
+        config,
+        code,
+        ReplyMethod::PublicReference(&source),
+        ran.invoker,
+        &source.author,
+        ran.add_components,
+    )
+    .await;
+    if let Err(why) = result {
+        source
+            .reply(&ctx, why.report())
+            .await
+            .or_log("reply with the error");
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use super::*;
+use serenity::client::bridge::gateway::ShardManager;
+use tokio::sync::Notify;
+
+// On SIGINT or SIGTERM the bot stops picking up new events, waits for what it's already doing,
+// and only then closes the gateway. That wait is SHUTDOWN_DEADLINE seconds at most (30 by default).
+// Renders that are still going after that are given up on, which takes their "Rendering..." message
+// with them and tells whoever asked for it to try again, instead of leaving it there forever
+const DEFAULT_DEADLINE: u64 = 30;
+// how long the renders that were given up on get to clean up after themselves
+const GRACE: Duration = Duration::from_secs(5);
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+static GAVE_UP: AtomicBool = AtomicBool::new(false);
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    static ref DEADLINE: u64 = config::parse("SHUTDOWN_DEADLINE").unwrap_or(DEFAULT_DEADLINE);
+    // woken up whenever IN_FLIGHT drops to 0 or GAVE_UP is set
+    static ref CHANGED: Notify = Notify::new();
+}
+
+// Something the bot is in the middle of, which shutting down waits for until it's dropped
+pub struct Job;
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        if IN_FLIGHT.fetch_sub(1, Ordering::SeqCst) == 1 {
+            CHANGED.notify_waiters();
+        }
+    }
+}
+
+// None once it's shutting down, so nothing new gets started
+pub fn job() -> Option<Job> {
+    IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+    // counted first, so shutting down can't miss one that starts right as it checks
+    let job = Job;
+    (!SHUTTING_DOWN.load(Ordering::SeqCst)).then_some(job)
+}
+
+// Resolves once the deadline has passed, for anything that would rather give up than be cut off
+pub async fn given_up() {
+    until(|| GAVE_UP.load(Ordering::SeqCst)).await
+}
+
+async fn idle() {
+    until(|| IN_FLIGHT.load(Ordering::SeqCst) == 0).await
+}
+
+async fn until(done: impl Fn() -> bool) {
+    loop {
+        // made before checking, so a notification right in between isn't missed
+        let changed = CHANGED.notified();
+        if done() {
+            return;
+        }
+        changed.await;
+    }
+}
+
+#[cfg(unix)]
+async fn signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut terminate = signal(SignalKind::terminate()).expect("Error listening for SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => (),
+        _ = terminate.recv() => (),
+    }
+}
+
+#[cfg(not(unix))]
+async fn signal() {
+    tokio::signal::ctrl_c()
+        .await
+        .expect("Error listening for ctrl-c");
+}
+
+pub fn start(manager: Arc<Mutex<ShardManager>>) {
+    tokio::spawn(async move {
+        signal().await;
+        SHUTTING_DOWN.store(true, Ordering::SeqCst);
+        info!(
+            "shutting down, waiting up to {}s for {} jobs",
+            *DEADLINE,
+            IN_FLIGHT.load(Ordering::SeqCst)
+        );
+        if tokio::time::timeout(Duration::from_secs(*DEADLINE), idle())
+            .await
+            .is_err()
+        {
+            error!(
+                "giving up on {} jobs that didn't finish in time",
+                IN_FLIGHT.load(Ordering::SeqCst)
+            );
+            GAVE_UP.store(true, Ordering::SeqCst);
+            CHANGED.notify_waiters();
+            let _ = tokio::time::timeout(GRACE, idle()).await;
+        }
+        // this makes client.start() return, and main with it
+        manager.lock().await.shutdown_all().await;
+    });
+}
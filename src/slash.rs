@@ -0,0 +1,1330 @@
+use super::*;
+use serenity::{
+    builder::{
+        CreateApplicationCommand, CreateApplicationCommandOption, CreateApplicationCommands,
+    },
+    model::{
+        application::{
+            command::CommandOptionType,
+            interaction::application_command::{
+                ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue,
+            },
+        },
+        id::{ChannelId, RoleId},
+    },
+};
+use storage::{AutoRespond, ChannelMode, Tone};
+
+// nobody needs a highlights.scm bigger than this
+const MAX_QUERY_SIZE: u64 = 256 * 1024;
+// a day, anything longer than that might as well be forever
+const MAX_PROMPT_TIMEOUT: u64 = 24 * 60;
+
+// Chat input (slash) commands. The message commands and buttons live in main.rs, these are separate
+// because they don't operate on a codeblock someone else posted.
+
+// The descriptions are in locales/en-US.ftl like everything else the bot says, with every translation
+// of them next to it so discord can show whichever one matches whoever is typing the command
+trait Describe {
+    fn describe(&mut self, id: &str) -> &mut Self;
+}
+
+impl Describe for CreateApplicationCommand {
+    fn describe(&mut self, id: &str) -> &mut Self {
+        for (locale, description) in locale::translations(id) {
+            self.description_localized(locale, description);
+        }
+        self.description(locale::translate(locale::FALLBACK, id, &[]))
+    }
+}
+
+impl Describe for CreateApplicationCommandOption {
+    fn describe(&mut self, id: &str) -> &mut Self {
+        for (locale, description) in locale::translations(id) {
+            self.description_localized(locale, description);
+        }
+        self.description(locale::translate(locale::FALLBACK, id, &[]))
+    }
+}
+
+pub fn register(commands: &mut CreateApplicationCommands) -> &mut CreateApplicationCommands {
+    commands.create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("highlight")
+            .describe("slash-highlight")
+            .create_option(|option| {
+                option
+                    .name("language")
+                    .describe("slash-highlight-language")
+                    .kind(CommandOptionType::String)
+                    .set_autocomplete(true)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("render")
+                    .describe("slash-highlight-render")
+                    .kind(CommandOptionType::Boolean)
+                    .required(false)
+            })
+    })
+    .create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("scratchpad")
+            .describe("slash-scratchpad")
+            .create_option(|option| {
+                option
+                    .name("render")
+                    .describe("slash-scratchpad-render")
+                    .kind(CommandOptionType::Boolean)
+                    .required(false)
+            })
+    })
+    .create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("optout")
+            .describe("slash-optout")
+    })
+    .create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("languages")
+            .describe("slash-languages")
+            .create_option(|option| {
+                option
+                    .name("language")
+                    .describe("slash-languages-language")
+                    .kind(CommandOptionType::String)
+                    .required(false)
+            })
+    })
+    .create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("query")
+            .describe("slash-query")
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("set")
+                    .describe("slash-query-set")
+                    .create_sub_option(|option| {
+                        option
+                            .name("language")
+                            .describe("slash-query-set-language")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                    })
+                    .create_sub_option(|option| {
+                        option
+                            .name("query")
+                            .describe("slash-query-set-query")
+                            .kind(CommandOptionType::Attachment)
+                            .required(true)
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("reset")
+                    .describe("slash-query-reset")
+                    .create_sub_option(|option| {
+                        option
+                            .name("language")
+                            .describe("slash-query-reset-language")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                    })
+            })
+    })
+    .create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("help")
+            .describe("slash-help")
+            .create_option(|option| {
+                option
+                    .name("language")
+                    .describe("slash-help-language")
+                    .kind(CommandOptionType::String)
+                    .set_autocomplete(true)
+                    .required(false)
+            })
+    })
+    .create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("about")
+            .describe("slash-about")
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("grammars")
+                    .describe("slash-about-grammars")
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("bot")
+                    .describe("slash-about-bot")
+            })
+    })
+    .create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("reload-languages")
+            .describe("slash-reload-languages")
+            .default_member_permissions(Permissions::ADMINISTRATOR)
+    })
+    .create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("grammar")
+            .describe("slash-grammar")
+            .default_member_permissions(Permissions::ADMINISTRATOR)
+            .dm_permission(false)
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("install")
+                    .describe("slash-grammar-install")
+                    .create_sub_option(|option| {
+                        option
+                            .name("language")
+                            .describe("slash-grammar-install-language")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                    })
+                    .create_sub_option(|option| {
+                        option
+                            .name("url")
+                            .describe("slash-grammar-install-url")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("uninstall")
+                    .describe("slash-grammar-uninstall")
+                    .create_sub_option(|option| {
+                        option
+                            .name("language")
+                            .describe("slash-grammar-uninstall-language")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                    })
+            })
+    })
+    .create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("theme")
+            .describe("slash-theme")
+            // it's owner only anyways, but this at least hides it from most people
+            .default_member_permissions(Permissions::ADMINISTRATOR)
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("set-capture")
+                    .describe("slash-theme-set-capture")
+                    .create_sub_option(|option| {
+                        option
+                            .name("language")
+                            .describe("slash-theme-set-capture-language")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                    })
+                    .create_sub_option(|option| {
+                        option
+                            .name("capture")
+                            .describe("slash-theme-set-capture-capture")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                    })
+                    .create_sub_option(|option| {
+                        option
+                            .name("color")
+                            .describe("slash-theme-set-capture-color")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                    })
+            })
+    })
+    .create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("node-at")
+            .describe("slash-node-at")
+            .create_option(|option| {
+                option
+                    .name("message")
+                    .describe("slash-node-at-message")
+                    .kind(CommandOptionType::String)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("line")
+                    .describe("slash-node-at-line")
+                    .kind(CommandOptionType::Integer)
+                    .min_int_value(1)
+                    .required(true)
+            })
+            .create_option(|option| {
+                option
+                    .name("col")
+                    .describe("slash-node-at-col")
+                    .kind(CommandOptionType::Integer)
+                    .min_int_value(1)
+                    .required(true)
+            })
+    })
+    .create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("preferences")
+            .describe("slash-preferences")
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("theme")
+                    .describe("slash-preferences-theme")
+                    .create_sub_option(|option| {
+                        option
+                            .name("theme")
+                            .describe("slash-preferences-theme-theme")
+                            .kind(CommandOptionType::String)
+                            .required(true);
+                        for theme in theme::THEMES {
+                            option.add_string_choice(theme.name, theme.name);
+                        }
+                        option.add_string_choice("default", "default")
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("size")
+                    .describe("slash-preferences-size")
+                    .create_sub_option(|option| {
+                        option
+                            .name("size")
+                            .describe("slash-preferences-size-size")
+                            .kind(CommandOptionType::Integer)
+                            .min_int_value(render::MIN_TEXT_SIZE)
+                            .max_int_value(render::MAX_TEXT_SIZE)
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("visibility")
+                    .describe("slash-preferences-visibility")
+                    .create_sub_option(|option| {
+                        option
+                            .name("visibility")
+                            .describe("slash-preferences-visibility-visibility")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                            .add_string_choice("public", "public")
+                            .add_string_choice("just me", "just me")
+                    })
+            })
+    })
+    .create_application_command(|cmd| {
+        cmd.kind(ApplicationCommandType::ChatInput)
+            .name("server")
+            .describe("slash-server")
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("theme")
+                    .describe("slash-server-theme")
+                    .create_sub_option(|option| {
+                        option
+                            .name("theme")
+                            .describe("slash-server-theme-theme")
+                            .kind(CommandOptionType::String)
+                            .required(true);
+                        for theme in theme::THEMES {
+                            option.add_string_choice(theme.name, theme.name);
+                        }
+                        option.add_string_choice("default", "default")
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("line-numbers")
+                    .describe("slash-server-line-numbers")
+                    .create_sub_option(|option| {
+                        option
+                            .name("enabled")
+                            .describe("slash-server-line-numbers-enabled")
+                            .kind(CommandOptionType::Boolean)
+                            .required(true)
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("threads")
+                    .describe("slash-server-threads")
+                    .create_sub_option(|option| {
+                        option
+                            .name("enabled")
+                            .describe("slash-server-threads-enabled")
+                            .kind(CommandOptionType::Boolean)
+                            .required(true)
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("webhooks")
+                    .describe("slash-server-webhooks")
+                    .create_sub_option(|option| {
+                        option
+                            .name("enabled")
+                            .describe("slash-server-webhooks-enabled")
+                            .kind(CommandOptionType::Boolean)
+                            .required(true)
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("reactions")
+                    .describe("slash-server-reactions")
+                    .create_sub_option(|option| {
+                        option
+                            .name("enabled")
+                            .describe("slash-server-reactions-enabled")
+                            .kind(CommandOptionType::Boolean)
+                            .required(true)
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("prompt-timeout")
+                    .describe("slash-server-prompt-timeout")
+                    .create_sub_option(|option| {
+                        option
+                            .name("minutes")
+                            .describe("slash-server-prompt-timeout-minutes")
+                            .kind(CommandOptionType::Integer)
+                            .min_int_value(0)
+                            .max_int_value(MAX_PROMPT_TIMEOUT)
+                            .required(true)
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("tone")
+                    .describe("slash-server-tone")
+                    .create_sub_option(|option| {
+                        option
+                            .name("mode")
+                            .describe("slash-server-tone-mode")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                            .add_string_choice("owo", "owo")
+                            .add_string_choice("plain", "plain")
+                            .add_string_choice("custom", "custom")
+                    })
+                    .create_sub_option(|option| {
+                        option
+                            .name("template")
+                            .describe("slash-server-tone-template")
+                            .kind(CommandOptionType::String)
+                            .required(false)
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("font")
+                    .describe("slash-server-font")
+                    .create_sub_option(|option| {
+                        option
+                            .name("font")
+                            .describe("slash-server-font-font")
+                            .kind(CommandOptionType::Attachment)
+                            .required(false)
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("autorespond")
+                    .describe("slash-server-autorespond")
+                    .create_sub_option(|option| {
+                        option
+                            .name("mode")
+                            .describe("slash-server-autorespond-mode")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                            .add_string_choice("on", "on")
+                            .add_string_choice("off", "off")
+                            .add_string_choice("allowlist", "allowlist")
+                    })
+                    .create_sub_option(|option| {
+                        option
+                            .name("channel")
+                            .describe("slash-server-autorespond-channel")
+                            .kind(CommandOptionType::Channel)
+                            .required(false)
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("channels")
+                    .describe("slash-server-channels")
+                    .create_sub_option(|option| {
+                        option
+                            .name("mode")
+                            .describe("slash-server-channels-mode")
+                            .kind(CommandOptionType::String)
+                            .required(true)
+                            .add_string_choice("everywhere", "everywhere")
+                            .add_string_choice("allowlist", "allowlist")
+                            .add_string_choice("blocklist", "blocklist")
+                    })
+                    .create_sub_option(|option| {
+                        option
+                            .name("channel")
+                            .describe("slash-server-channels-channel")
+                            .kind(CommandOptionType::Channel)
+                            .required(false)
+                    })
+            })
+            .create_option(|sub| {
+                sub.kind(CommandOptionType::SubCommand)
+                    .name("permissions")
+                    .describe("slash-server-permissions")
+                    .create_sub_option(|option| {
+                        option
+                            .name("command")
+                            .describe("slash-server-permissions-command")
+                            .kind(CommandOptionType::String)
+                            .required(true);
+                        for spec in commands::menus() {
+                            option.add_string_choice(spec.id, spec.id);
+                        }
+                        option
+                    })
+                    .create_sub_option(|option| {
+                        option
+                            .name("role")
+                            .describe("slash-server-permissions-role")
+                            .kind(CommandOptionType::Role)
+                            .required(false)
+                    })
+            })
+    })
+}
+
+pub async fn handle(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+    // this one answers with a modal instead of a message, see modal.rs
+    if interaction.data.name == "highlight" {
+        modal::open(ctx, interaction).await;
+        return;
+    }
+    if interaction.data.name == "scratchpad" {
+        modal::scratchpad(ctx, interaction).await;
+        return;
+    }
+    // and this one has buttons, see help.rs
+    if interaction.data.name == "help" {
+        let lang = string_option(&interaction.data.options, "language");
+        help::show(ctx, interaction, lang).await;
+        return;
+    }
+    // these can take way longer than the 3 seconds discord gives us to respond
+    let slow = matches!(
+        interaction.data.name.as_str(),
+        "query" | "reload-languages" | "grammar" | "server"
+    );
+    if slow {
+        interaction
+            .create_interaction_response(ctx, |response| {
+                response
+                    .kind(InteractionResponseType::DeferredChannelMessageWithSource)
+                    .interaction_response_data(|data| data.ephemeral(true))
+            })
+            .await
+            .or_log("defer the command");
+    }
+    let content = match interaction.data.name.as_str() {
+        "languages" => languages(interaction),
+        "query" => query(interaction).await,
+        "theme" => theme(ctx, interaction).await,
+        "reload-languages" => reload_languages(ctx, interaction).await,
+        "about" => about(interaction),
+        "grammar" => grammar(ctx, interaction).await,
+        "preferences" => preferences(interaction),
+        "optout" => optout(interaction),
+        "node-at" => node_at(ctx, interaction).await,
+        "server" => server(interaction).await,
+        name => owo!("unknown-command", name = name),
+    };
+    if slow {
+        interaction
+            .edit_original_interaction_response(ctx, |response| response.content(content))
+            .await
+            .or_log("answer the command");
+    } else {
+        interaction
+            .create_interaction_response(ctx, |response| {
+                response.interaction_response_data(|msg| msg.ephemeral(true).content(content))
+            })
+            .await
+            .or_log("answer the command");
+    }
+}
+
+fn string_option<'a>(options: &'a [CommandDataOption], name: &str) -> Option<&'a str> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_ref())
+        .and_then(|value| value.as_str())
+}
+
+async fn is_owner(ctx: &Context, user: UserId) -> bool {
+    match ctx.http.get_current_application_info().await {
+        Ok(info) => {
+            info.owner.id == user
+                || info.team.map_or(false, |team| {
+                    team.members.iter().any(|member| member.user.id == user)
+                })
+        }
+        Err(e) => {
+            error!("{e:?}");
+            false
+        }
+    }
+}
+
+fn capabilities(config: &LanguageConfig) -> &'static str {
+    if config.language.is_some() {
+        "highlight, render, parse"
+    } else {
+        "highlight, render"
+    }
+}
+
+fn languages(interaction: &ApplicationCommandInteraction) -> String {
+    let lang = match string_option(&interaction.data.options, "language") {
+        Some(lang) => lang,
+        None => {
+            let languages = all_languages();
+            let mut list = tr!("languages-header") + "\n";
+            for config in &languages {
+                list += &format!("- `{}`", config.name);
+                if !config.aliases.is_empty() {
+                    list += &format!(
+                        " {}",
+                        tr!("languages-aka", aliases = config.aliases.join("`, `"))
+                    );
+                }
+                list += &format!(" — {}\n", capabilities(config));
+            }
+            // then as many examples as fit, the rest are only in /languages <language>
+            let mut examples = String::new();
+            let mut left_out = false;
+            for config in &languages {
+                match highlighted_example(config) {
+                    Some(example) if list.len() + examples.len() + example.len() <= LIST_LIMIT => {
+                        examples += &format!("`{}`:{example}", config.name);
+                    }
+                    Some(_) => left_out = true,
+                    None => {}
+                }
+            }
+            if left_out {
+                examples += &tr!("languages-more-examples");
+            }
+            return list + &examples;
+        }
+    };
+    let config = match get_language(lang) {
+        Some(config) if !config.name.is_empty() => config,
+        _ => return owo!("no-such-language", language = lang),
+    };
+    let mut content = format!("**{}**\n", config.name);
+    if !config.aliases.is_empty() {
+        content += &format!(
+            "{}\n",
+            tr!("languages-aliases", aliases = config.aliases.join("`, `"))
+        );
+    }
+    content += &format!(
+        "{}\n",
+        tr!("languages-supports", commands = capabilities(&config))
+    );
+    content += &if config.auto_respond {
+        tr!("languages-auto-respond")
+    } else {
+        tr!("languages-when-asked")
+    };
+    content.push('\n');
+    if let Some(example) = highlighted_example(&config) {
+        // the example is just a nice extra, don't fail the whole thing if it's too long
+        if content.len() + example.len() <= 2000 {
+            content += &example;
+        }
+    }
+    content
+}
+
+// room for the note about the examples that didn't fit in the list
+const LIST_LIMIT: usize = 1900;
+
+fn highlighted_example(config: &LanguageConfig) -> Option<String> {
+    let example = config.example.as_ref()?;
+    let highlighted = syntax_highlight(config, example, theme::DEFAULT, DEFAULT_TAB_WIDTH).ok()?;
+    Some(format!("```ansi\n{highlighted}\n```"))
+}
+
+async fn query(interaction: &ApplicationCommandInteraction) -> String {
+    let guild_id = match interaction.guild_id {
+        Some(guild_id) => guild_id,
+        None => return owo!("query-server-only"),
+    };
+    let subcommand = match interaction.data.options.first() {
+        Some(subcommand) => subcommand,
+        None => return owo!("pick-a-subcommand"),
+    };
+    let lang = string_option(&subcommand.options, "language").unwrap_or_default();
+    let config = match get_language(lang) {
+        Some(config) if config.language.is_some() => config,
+        _ => return owo!("query-no-such-language", language = lang),
+    };
+    match subcommand.name.as_str() {
+        "set" => {
+            let attachment = match subcommand
+                .options
+                .iter()
+                .find(|option| option.name == "query")
+                .and_then(|option| option.resolved.as_ref())
+            {
+                Some(CommandDataOptionValue::Attachment(attachment)) => attachment,
+                _ => return owo!("query-attach"),
+            };
+            if attachment.size > MAX_QUERY_SIZE {
+                return owo!("query-too-big", max = MAX_QUERY_SIZE);
+            }
+            let query = match attachment.download().await {
+                Ok(bytes) => match String::from_utf8(bytes) {
+                    Ok(query) => query,
+                    Err(_) => return owo!("query-not-utf8"),
+                },
+                Err(e) => {
+                    error!("{e:?}");
+                    return owo!("download-failed");
+                }
+            };
+            match languages::set_guild_query(guild_id, &config, Some(query)) {
+                Ok(()) => tr!(
+                    "query-set",
+                    language = config.name.as_str(),
+                    file = attachment.filename.as_str()
+                ),
+                // not owoified, the person fixing their query needs to be able to read this
+                Err(why) => format!("{}\n```\n{why}\n```", tr!("query-not-compiling")),
+            }
+        }
+        "reset" => match languages::set_guild_query(guild_id, &config, None) {
+            Ok(()) => tr!("query-reset", language = config.name.as_str()),
+            Err(why) => why,
+        },
+        name => owo!("unknown-subcommand", name = name),
+    }
+}
+
+async fn theme(ctx: &Context, interaction: &ApplicationCommandInteraction) -> String {
+    // this changes the colors for everyone, not just one server
+    if !is_owner(ctx, interaction.user.id).await {
+        return owo!("theme-owner-only");
+    }
+    let subcommand = match interaction.data.options.first() {
+        Some(subcommand) if subcommand.name == "set-capture" => subcommand,
+        _ => return owo!("pick-a-subcommand"),
+    };
+    let lang = string_option(&subcommand.options, "language").unwrap_or_default();
+    let capture = string_option(&subcommand.options, "capture").unwrap_or_default();
+    let color = string_option(&subcommand.options, "color").unwrap_or_default();
+    let config = match get_language(lang) {
+        Some(config) if !config.name.is_empty() => config,
+        _ => return owo!("no-such-language", language = lang),
+    };
+    let parsed = match Format::parse(color) {
+        Some(color) => color,
+        None => return owo!("theme-bad-color", color = color),
+    };
+    match languages::set_capture_color(&config, capture, parsed) {
+        Ok(()) => tr!(
+            "theme-set",
+            capture = capture,
+            language = config.name.as_str(),
+            color = color
+        ),
+        // the only way it fails, and core's message isn't translated
+        Err(_) => owo!(
+            "theme-no-capture",
+            language = config.name.as_str(),
+            capture = capture
+        ),
+    }
+}
+
+async fn reload_languages(ctx: &Context, interaction: &ApplicationCommandInteraction) -> String {
+    if !is_owner(ctx, interaction.user.id).await {
+        return owo!("reload-owner-only");
+    }
+    // compiling every query takes a moment, don't do that on the gateway task
+    match tokio::task::spawn_blocking(languages::reload).await {
+        Ok(Ok(count)) => tr!("reload-done", count = count),
+        // not owoified, whoever broke languages.toml needs to read this
+        Ok(Err(why)) => format!("{}\n```\n{why}\n```", tr!("reload-broken")),
+        Err(e) => {
+            error!("{e:?}");
+            owo!("reload-join")
+        }
+    }
+}
+
+fn about(interaction: &ApplicationCommandInteraction) -> String {
+    match interaction.data.options.first().map(|sub| sub.name.as_str()) {
+        Some("grammars") => about_grammars(interaction.guild_id),
+        Some("bot") => usage::report(),
+        _ => owo!("pick-a-subcommand"),
+    }
+}
+
+// mostly for figuring out why a query stopped compiling after a grammar update
+// The grammars installed in this server (see install.rs) are listed after the built-in ones
+fn about_grammars(guild_id: Option<GuildId>) -> String {
+    let mut table = format!(
+        "{:<10} {:<10} {:>3} {:>6} {:>6}  {}\n",
+        "language", "grammar", "ABI", "kinds", "fields", "source"
+    );
+    let installed = guild_id.map(install::guild_languages).unwrap_or_default();
+    for config in all_languages().into_iter().chain(installed) {
+        if let Some(language) = config.language {
+            table += &format!(
+                "{:<10} {:<10} {:>3} {:>6} {:>6}  {}\n",
+                config.name,
+                config.grammar,
+                language.version(),
+                language.node_kind_count(),
+                language.field_count(),
+                config.source
+            );
+        }
+    }
+    let versions = tr!(
+        "about-abi-versions",
+        min = tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+        max = tree_sitter::LANGUAGE_VERSION
+    );
+    format!("{versions}\n```\n{table}```")
+}
+
+async fn grammar(ctx: &Context, interaction: &ApplicationCommandInteraction) -> String {
+    // this compiles and loads native code, see install.rs
+    if !is_owner(ctx, interaction.user.id).await {
+        return owo!("grammar-owner-only");
+    }
+    let guild_id = match interaction.guild_id {
+        Some(guild_id) => guild_id,
+        None => return owo!("grammar-server-only"),
+    };
+    let subcommand = match interaction.data.options.first() {
+        Some(subcommand) => subcommand,
+        None => return owo!("pick-a-subcommand"),
+    };
+    let lang = string_option(&subcommand.options, "language").unwrap_or_default();
+    match subcommand.name.as_str() {
+        "install" => {
+            let url = string_option(&subcommand.options, "url").unwrap_or_default();
+            info!("installing {lang} from {url} in guild {guild_id}");
+            match install::install(guild_id, lang, url).await {
+                Ok(()) => tr!("grammar-installed", language = lang),
+                // not owoified, these are build errors
+                Err(why) => format!(
+                    "{}\n```\n{why}\n```",
+                    tr!("grammar-failed", language = lang)
+                ),
+            }
+        }
+        "uninstall" => match install::uninstall(guild_id, lang).await {
+            Ok(()) => tr!("grammar-uninstalled", language = lang),
+            Err(why) => why,
+        },
+        name => owo!("unknown-subcommand", name = name),
+    }
+}
+
+fn int_option(options: &[CommandDataOption], name: &str) -> Option<u64> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_ref())
+        .and_then(|value| value.as_u64())
+}
+
+// https://discord.com/channels/<guild or @me>/<channel>/<message>, or just the message ID for this channel
+fn message_link(link: &str, here: ChannelId) -> Option<(ChannelId, MessageId)> {
+    let mut parts = link.trim().trim_end_matches('/').rsplit('/');
+    let message = parts.next()?.parse::<u64>().ok()?;
+    let channel = match parts.next() {
+        Some(channel) => ChannelId(channel.parse().ok()?),
+        None => here,
+    };
+    Some((channel, MessageId(message)))
+}
+
+// how much of the node's text is shown
+const MAX_NODE_TEXT: usize = 40;
+
+async fn node_at(ctx: &Context, interaction: &ApplicationCommandInteraction) -> String {
+    let options = &interaction.data.options;
+    let link = string_option(options, "message").unwrap_or_default();
+    let (line, col) = match (int_option(options, "line"), int_option(options, "col")) {
+        (Some(line), Some(col)) if line > 0 && col > 0 => (line as usize, col as usize),
+        _ => return owo!("node-at-start"),
+    };
+    let (channel_id, message_id) = match message_link(link, interaction.channel_id) {
+        Some(ids) => ids,
+        None => return owo!("node-at-not-a-link"),
+    };
+    // the bot can see a lot of channels, so this can't be a way to read the ones you can't
+    let allowed = match channel_id.to_channel(ctx).await {
+        Ok(Channel::Guild(channel)) => {
+            let needed = Permissions::VIEW_CHANNEL | Permissions::READ_MESSAGE_HISTORY;
+            channel
+                .permissions_for_user(ctx, interaction.user.id)
+                .map_or(false, |p| p.contains(needed))
+        }
+        Ok(_) => channel_id == interaction.channel_id,
+        Err(_) => false,
+    };
+    if !allowed {
+        return owo!("node-at-not-allowed");
+    }
+    let message = match channel_id.message(ctx, message_id).await {
+        Ok(message) => message,
+        Err(_) => return owo!("node-at-not-found"),
+    };
+    let (_, lang, code, _) = match codeblock(&message.content) {
+        Some(codeblock) => codeblock,
+        None => return owo!("not-a-codeblock"),
+    };
+    let config = match get_guild_language(message.guild_id, lang) {
+        Some(config) => config,
+        None => return owo!("unknown-language", language = lang),
+    };
+    let tree = {
+        let (config, code) = (config.clone(), code.to_owned());
+        workers::run(Task::Parse, move || workers::parse(&config, &code, None)).await
+    };
+    let tree = match tree {
+        Ok(tree) => tree,
+        Err(why) => return why.report(),
+    };
+
+    // tree-sitter's columns are in bytes, but people count characters
+    let line_start = code.split('\n').take(line - 1).map(|line| line.len() + 1).sum::<usize>();
+    let text = match code.split('\n').nth(line - 1) {
+        Some(text) => text,
+        None => return tr!("node-at-lines", count = code.split('\n').count()),
+    };
+    let byte = match text.char_indices().nth(col - 1) {
+        Some((i, _)) => line_start + i,
+        None => return tr!("node-at-columns", line = line, count = text.chars().count()),
+    };
+
+    // all the way down to the smallest node there, with the field it's in on the way
+    let mut cursor = tree.walk();
+    let mut chain = vec![(None, cursor.node())];
+    while cursor.goto_first_child_for_byte(byte).is_some() {
+        if cursor.node().start_byte() > byte {
+            break;
+        }
+        chain.push((cursor.field_name(), cursor.node()));
+    }
+    let mut content = tr!(
+        "node-at-header",
+        line = line,
+        col = col,
+        language = config.name.as_str()
+    );
+    content += "\n```\n";
+    for (depth, (field, node)) in chain.iter().enumerate() {
+        content.extend(iter::repeat("  ").take(depth));
+        if let Some(field) = field {
+            content += &format!("{field}: ");
+        }
+        content += node.kind();
+        content.push('\n');
+    }
+    let node = chain.last().unwrap().1;
+    let node_text = code[node.byte_range()].lines().next().unwrap_or_default();
+    let mut shown = node_text.chars().take(MAX_NODE_TEXT).collect::<String>();
+    if shown.len() < code[node.byte_range()].len() {
+        shown.push('…');
+    }
+    // backticks would close the codeblock
+    content += "```\n";
+    content += &tr!("node-at-text", text = shown.replace('`', "ˋ"));
+    content
+}
+
+fn preferences(interaction: &ApplicationCommandInteraction) -> String {
+    let subcommand = match interaction.data.options.first() {
+        Some(subcommand) => subcommand,
+        None => return owo!("pick-a-subcommand"),
+    };
+    match subcommand.name.as_str() {
+        "theme" => preferred_theme(interaction, subcommand),
+        "size" => {
+            match int_option(&subcommand.options, "size") {
+                Some(size) => {
+                    // discord already checks the bounds, but it doesn't hurt
+                    let size = size.clamp(
+                        render::MIN_TEXT_SIZE as u64,
+                        render::MAX_TEXT_SIZE as u64,
+                    ) as u32;
+                    render::set_preferred_size(interaction.user.id, Some(size));
+                    tr!("preferences-size", size = size)
+                }
+                None => {
+                    render::set_preferred_size(interaction.user.id, None);
+                    tr!("preferences-size-reset", size = render::DEFAULT_TEXT_SIZE)
+                }
+            }
+        }
+        "visibility" => {
+            let public = string_option(&subcommand.options, "visibility") == Some("public");
+            storage::write(|data| {
+                data.users.entry(interaction.user.id.0).or_default().public = Some(public)
+            });
+            if public {
+                tr!("preferences-public")
+            } else {
+                tr!("preferences-private")
+            }
+        }
+        name => owo!("unknown-subcommand", name = name),
+    }
+}
+
+fn optout(interaction: &ApplicationCommandInteraction) -> String {
+    let opted_out = storage::write(|data| {
+        let user = data.users.entry(interaction.user.id.0).or_default();
+        user.opted_out = !user.opted_out;
+        user.opted_out
+    });
+    if opted_out {
+        tr!(&interaction.locale, "optout-on")
+    } else {
+        tr!(&interaction.locale, "optout-off")
+    }
+}
+
+fn tone(guild_id: GuildId, subcommand: &CommandDataOption) -> String {
+    let tone = match string_option(&subcommand.options, "mode") {
+        Some("owo") => Tone::Owo,
+        Some("plain") => Tone::Plain,
+        Some("custom") => match string_option(&subcommand.options, "template") {
+            Some(template) if template.contains("{message}") => Tone::Custom(template.to_owned()),
+            _ => return owo!("tone-no-message"),
+        },
+        mode => return owo!("no-such-mode", mode = mode.unwrap_or_default()),
+    };
+    let reply = match tone {
+        Tone::Owo => tr!("tone-owo"),
+        Tone::Plain => tr!("tone-plain"),
+        Tone::Custom(_) => tr!("tone-custom"),
+    };
+    storage::write(|data| data.guilds.entry(guild_id.0).or_default().tone = Some(tone));
+    reply
+}
+
+fn autorespond(guild_id: GuildId, subcommand: &CommandDataOption) -> String {
+    let mode = match string_option(&subcommand.options, "mode") {
+        Some("on") => AutoRespond::On,
+        Some("off") => AutoRespond::Off,
+        Some("allowlist") => AutoRespond::Allowlist,
+        mode => return owo!("no-such-mode", mode = mode.unwrap_or_default()),
+    };
+    let channel = channel_option(&subcommand.options, "channel");
+    let channels = storage::write(|data| {
+        let guild = data.guilds.entry(guild_id.0).or_default();
+        guild.autorespond = Some(mode);
+        toggle_channel(&mut guild.autorespond_channels, channel);
+        guild.autorespond_channels.clone()
+    });
+    match mode {
+        AutoRespond::On => tr!("autorespond-on"),
+        AutoRespond::Off => tr!("autorespond-off"),
+        AutoRespond::Allowlist if channels.is_empty() => tr!("autorespond-allowlist-empty"),
+        AutoRespond::Allowlist => {
+            tr!(
+                "autorespond-allowlist",
+                channels = mention_channels(&channels)
+            )
+        }
+    }
+}
+
+fn channels(guild_id: GuildId, subcommand: &CommandDataOption) -> String {
+    let mode = match string_option(&subcommand.options, "mode") {
+        Some("everywhere") => ChannelMode::Everywhere,
+        Some("allowlist") => ChannelMode::Allowlist,
+        Some("blocklist") => ChannelMode::Blocklist,
+        mode => return owo!("no-such-mode", mode = mode.unwrap_or_default()),
+    };
+    let channel = channel_option(&subcommand.options, "channel");
+    let channels = storage::write(|data| {
+        let guild = data.guilds.entry(guild_id.0).or_default();
+        guild.channel_mode = Some(mode);
+        toggle_channel(&mut guild.listed_channels, channel);
+        guild.listed_channels.clone()
+    });
+    match mode {
+        ChannelMode::Everywhere => tr!("channels-everywhere"),
+        ChannelMode::Allowlist if channels.is_empty() => tr!("channels-allowlist-empty"),
+        ChannelMode::Allowlist => tr!("channels-allowlist", channels = mention_channels(&channels)),
+        ChannelMode::Blocklist if channels.is_empty() => tr!("channels-blocklist-empty"),
+        ChannelMode::Blocklist => tr!("channels-blocklist", channels = mention_channels(&channels)),
+    }
+}
+
+fn permissions(guild_id: GuildId, subcommand: &CommandDataOption) -> String {
+    let name = match string_option(&subcommand.options, "command") {
+        Some(name) if commands::menus().any(|spec| spec.id == name) => name,
+        name => return owo!("no-such-command", command = name.unwrap_or_default()),
+    };
+    let role = subcommand
+        .options
+        .iter()
+        .find(|option| option.name == "role")
+        .and_then(|option| option.resolved.as_ref())
+        .and_then(|value| match value {
+            CommandDataOptionValue::Role(role) => Some(role.id),
+            _ => None,
+        });
+    let roles = storage::write(|data| {
+        let guild = data.guilds.entry(guild_id.0).or_default();
+        match role {
+            Some(RoleId(role)) => {
+                let roles = guild.command_roles.entry(name.to_owned()).or_default();
+                match roles.iter().position(|&id| id == role) {
+                    Some(i) => {
+                        roles.remove(i);
+                    }
+                    None => roles.push(role),
+                }
+                if roles.is_empty() {
+                    guild.command_roles.remove(name);
+                }
+            }
+            None => {
+                guild.command_roles.remove(name);
+            }
+        }
+        guild.command_roles.get(name).cloned().unwrap_or_default()
+    });
+    if roles.is_empty() {
+        tr!("permissions-everyone", command = name)
+    } else {
+        let roles = roles
+            .iter()
+            .map(|id| format!("<@&{id}>"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        tr!("permissions-roles", roles = roles, command = name)
+    }
+}
+
+fn channel_option(options: &[CommandDataOption], name: &str) -> Option<ChannelId> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.resolved.as_ref())
+        .and_then(|value| match value {
+            CommandDataOptionValue::Channel(channel) => Some(channel.id),
+            _ => None,
+        })
+}
+
+// adds it, or takes it off if it's already there
+fn toggle_channel(channels: &mut Vec<u64>, channel: Option<ChannelId>) {
+    if let Some(channel) = channel {
+        match channels.iter().position(|&id| id == channel.0) {
+            Some(i) => {
+                channels.remove(i);
+            }
+            None => channels.push(channel.0),
+        }
+    }
+}
+
+fn mention_channels(channels: &[u64]) -> String {
+    channels
+        .iter()
+        .map(|id| format!("<#{id}>"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn preferred_theme(
+    interaction: &ApplicationCommandInteraction,
+    subcommand: &CommandDataOption,
+) -> String {
+    let name = string_option(&subcommand.options, "theme").unwrap_or_default();
+    if name == "default" {
+        theme::set_preferred(interaction.user.id, None);
+        // without their own, it's whatever /server theme picked, see theme::preferred
+        let fallback = theme::preferred(interaction.user.id, interaction.guild_id);
+        return if fallback.name != theme::DEFAULT.name {
+            tr!("preferences-theme-server", theme = fallback.name)
+        } else {
+            tr!("preferences-theme-default", theme = theme::DEFAULT.name)
+        };
+    }
+    match theme::by_name(name) {
+        Some(theme) => {
+            theme::set_preferred(interaction.user.id, Some(theme));
+            tr!("preferences-theme", theme = theme.name)
+        }
+        None => owo!("option-theme", theme = name, themes = theme::names()),
+    }
+}
+
+async fn server(interaction: &ApplicationCommandInteraction) -> String {
+    let guild_id = match interaction.guild_id {
+        Some(guild_id) => guild_id,
+        None => return owo!("server-only"),
+    };
+    let subcommand = match interaction.data.options.first() {
+        Some(subcommand) => subcommand,
+        None => return owo!("pick-a-subcommand"),
+    };
+    let locale = &interaction.locale;
+    match subcommand.name.as_str() {
+        "theme" => {
+            let name = string_option(&subcommand.options, "theme").unwrap_or_default();
+            if name == "default" {
+                theme::set_guild_default(guild_id, None);
+                return tr!(locale, "server-theme-reset", theme = theme::DEFAULT.name);
+            }
+            match theme::by_name(name) {
+                Some(theme) => {
+                    theme::set_guild_default(guild_id, Some(theme));
+                    tr!(locale, "server-theme", theme = theme.name)
+                }
+                None => owo!("option-theme", theme = name, themes = theme::names()),
+            }
+        }
+        "line-numbers" => {
+            let enabled = subcommand
+                .options
+                .iter()
+                .find(|option| option.name == "enabled")
+                .and_then(|option| option.value.as_ref())
+                .and_then(|value| value.as_bool())
+                .unwrap_or_default();
+            storage::write(|data| {
+                data.guilds.entry(guild_id.0).or_default().line_numbers = Some(enabled)
+            });
+            if enabled {
+                tr!(locale, "server-line-numbers-on")
+            } else {
+                tr!(locale, "server-line-numbers-off")
+            }
+        }
+        "threads" => {
+            let enabled = subcommand
+                .options
+                .iter()
+                .find(|option| option.name == "enabled")
+                .and_then(|option| option.value.as_ref())
+                .and_then(|value| value.as_bool())
+                .unwrap_or_default();
+            storage::write(|data| {
+                data.guilds.entry(guild_id.0).or_default().threads = Some(enabled)
+            });
+            if enabled {
+                tr!(locale, "server-threads-on")
+            } else {
+                tr!(locale, "server-threads-off")
+            }
+        }
+        "webhooks" => {
+            let enabled = subcommand
+                .options
+                .iter()
+                .find(|option| option.name == "enabled")
+                .and_then(|option| option.value.as_ref())
+                .and_then(|value| value.as_bool())
+                .unwrap_or_default();
+            storage::write(|data| {
+                data.guilds.entry(guild_id.0).or_default().webhooks = Some(enabled)
+            });
+            if enabled {
+                tr!(locale, "server-webhooks-on")
+            } else {
+                tr!(locale, "server-webhooks-off")
+            }
+        }
+        "reactions" => {
+            let enabled = subcommand
+                .options
+                .iter()
+                .find(|option| option.name == "enabled")
+                .and_then(|option| option.value.as_ref())
+                .and_then(|value| value.as_bool())
+                .unwrap_or_default();
+            storage::write(|data| {
+                data.guilds.entry(guild_id.0).or_default().reactions = Some(enabled)
+            });
+            if enabled {
+                tr!(locale, "server-reactions-on")
+            } else {
+                tr!(locale, "server-reactions-off")
+            }
+        }
+        "prompt-timeout" => {
+            let minutes = int_option(&subcommand.options, "minutes")
+                .unwrap_or_default()
+                .min(MAX_PROMPT_TIMEOUT);
+            storage::write(|data| {
+                data.guilds.entry(guild_id.0).or_default().prompt_timeout = Some(minutes)
+            });
+            if minutes == 0 {
+                tr!(locale, "server-prompt-timeout-never")
+            } else {
+                tr!(locale, "server-prompt-timeout", minutes = minutes)
+            }
+        }
+        "font" => {
+            let attachment = match subcommand
+                .options
+                .iter()
+                .find(|option| option.name == "font")
+                .and_then(|option| option.resolved.as_ref())
+            {
+                Some(CommandDataOptionValue::Attachment(attachment)) => attachment,
+                _ => {
+                    return match render::set_guild_font(guild_id, None) {
+                        Ok(()) => tr!(locale, "server-font-reset"),
+                        Err(why) => why,
+                    }
+                }
+            };
+            if attachment.size > render::MAX_FONT_SIZE {
+                return owo!("server-font-too-big", max = render::MAX_FONT_SIZE);
+            }
+            let bytes = match attachment.download().await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    error!("{e:?}");
+                    return owo!("download-failed");
+                }
+            };
+            match render::set_guild_font(guild_id, Some((&attachment.filename, bytes))) {
+                Ok(()) => tr!(locale, "server-font", file = attachment.filename.as_str()),
+                Err(why) => why,
+            }
+        }
+        "tone" => tone(guild_id, subcommand),
+        "autorespond" => autorespond(guild_id, subcommand),
+        "channels" => channels(guild_id, subcommand),
+        "permissions" => permissions(guild_id, subcommand),
+        name => owo!("unknown-subcommand", name = name),
+    }
+}
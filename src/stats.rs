@@ -0,0 +1,106 @@
+use std::time::Instant;
+
+use super::*;
+
+// +stats, for grammar authors looking for weird tree shapes, like one node kind everywhere
+// or a tree that's a thousand levels deep when it really shouldn't be
+
+// only the most common ones are listed
+const MAX_LISTED_KINDS: usize = 10;
+
+#[derive(Default)]
+struct Counts {
+    nodes: usize,
+    named: usize,
+    errors: usize,
+    missing: usize,
+    max_depth: usize,
+    kinds: HashMap<&'static str, usize>,
+}
+
+pub fn stats(
+    config: &LanguageConfig,
+    code: &str,
+    theme: &Theme,
+) -> Result<String, HighlightError> {
+    let mut parser = parser(config)?;
+    let start = Instant::now();
+    let tree = parser
+        .parse(code, None)
+        .ok_or_else(|| HighlightError::TreeSitter {
+            language: config.name.clone(),
+            offset: None,
+            reason: "the parser gave up".to_owned(),
+        })?;
+    let elapsed = start.elapsed();
+
+    let mut counts = Counts::default();
+    let mut cursor = tree.walk();
+    let mut depth = 0;
+    'walk: loop {
+        let node = cursor.node();
+        counts.nodes += 1;
+        counts.max_depth = counts.max_depth.max(depth);
+        if node.is_error() {
+            counts.errors += 1;
+        } else if node.is_missing() {
+            counts.missing += 1;
+        }
+        if node.is_named() {
+            counts.named += 1;
+            *counts.kinds.entry(node.kind()).or_default() += 1;
+        }
+        if cursor.goto_first_child() {
+            depth += 1;
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+            depth -= 1;
+        }
+    }
+
+    let ansi = |name| theme.get(name).ansi;
+    let line =
+        |label: String, value: String| format!("{label}: {}{value}{}\n", ansi(BLUE), ansi(RESET));
+    let mut out = String::new();
+    let nodes = tr!(
+        "stats-nodes-value",
+        nodes = counts.nodes,
+        named = counts.named
+    );
+    out.push_str(&line(tr!("stats-nodes"), nodes));
+    out.push_str(&line(tr!("stats-depth"), counts.max_depth.to_string()));
+    let problems = counts.errors + counts.missing;
+    out.push_str(&format!(
+        "{}: {}{} {}{}\n",
+        tr!("stats-errors"),
+        ansi(if problems == 0 { LIGHT_GREEN } else { RED }),
+        problems,
+        tr!(
+            "stats-errors-value",
+            errors = counts.errors,
+            missing = counts.missing
+        ),
+        ansi(RESET)
+    ));
+    out.push_str(&line(tr!("stats-parse-time"), format!("{elapsed:?}")));
+
+    let mut kinds = counts.kinds.into_iter().collect::<Vec<_>>();
+    // ties go alphabetically, so it's the same every time
+    kinds.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    if !kinds.is_empty() {
+        out.push_str(&tr!("stats-kinds"));
+        out.push('\n');
+    }
+    for (kind, count) in kinds.iter().take(MAX_LISTED_KINDS) {
+        out.push_str(&format!("    {}{kind}{} {count}\n", ansi(YELLOW), ansi(RESET)));
+    }
+    if kinds.len() > MAX_LISTED_KINDS {
+        let more = tr!("stats-more-kinds", count = kinds.len() - MAX_LISTED_KINDS);
+        out.push_str(&format!("    {more}\n"));
+    }
+    Ok(out)
+}
@@ -0,0 +1,394 @@
+use std::{error::Error, fs};
+
+use super::*;
+use rusqlite::{params, Connection};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serenity::model::id::WebhookId;
+
+// Everything the bot remembers between restarts, in an SQLite database (data.db, or DATABASE_PATH).
+// The settings are small enough that they're all kept in memory too, and every guild and user is
+// one row with its settings as JSON, so only the ones that changed get written back.
+// Besides those it has the replies to codeblocks, so they can still be cleaned up after a restart,
+// and how many times things have happened for /about bot, see usage.rs
+const DATABASE_PATH: &str = "data.db";
+// where everything was before the database, it's moved in the first time the bot starts with one
+const LEGACY_PATH: &str = "data.json";
+// replies are only kept for this many of the most recent ones, the rest just stay where they are
+const TRACKED_REPLIES: i64 = 10_000;
+
+// Every change to the tables is a new one at the end, and never an edit to one that's already here.
+// PRAGMA user_version is how many of them the database has had
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE guilds (id INTEGER PRIMARY KEY, settings TEXT NOT NULL);
+    CREATE TABLE users (id INTEGER PRIMARY KEY, settings TEXT NOT NULL);
+    CREATE TABLE replies (
+        source INTEGER NOT NULL,
+        channel INTEGER NOT NULL,
+        reply INTEGER NOT NULL,
+        webhook INTEGER
+    );
+    CREATE INDEX replies_by_source ON replies (source);
+    CREATE TABLE usage (name TEXT PRIMARY KEY, count INTEGER NOT NULL);
+"];
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Data {
+    #[serde(default)]
+    pub guilds: HashMap<u64, GuildSettings>,
+    #[serde(default)]
+    pub users: HashMap<u64, UserSettings>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct GuildSettings {
+    // language name => replacement highlights.scm
+    #[serde(default)]
+    pub queries: HashMap<String, String>,
+    // language name => grammar installed with /grammar install
+    #[serde(default)]
+    pub grammars: HashMap<String, InstalledGrammar>,
+    // set with /server theme, members can still pick their own
+    #[serde(default)]
+    pub theme: Option<String>,
+    // set with /server line-numbers
+    #[serde(default)]
+    pub line_numbers: Option<bool>,
+    // set with /server threads
+    #[serde(default)]
+    pub threads: Option<bool>,
+    // set with /server webhooks
+    #[serde(default)]
+    pub webhooks: Option<bool>,
+    // set with /server reactions
+    #[serde(default)]
+    pub reactions: Option<bool>,
+    // in minutes, set with /server prompt-timeout, see expire.rs
+    #[serde(default)]
+    pub prompt_timeout: Option<u64>,
+    // set with /server tone, None is the same as owo
+    #[serde(default)]
+    pub tone: Option<Tone>,
+    // the file name of the font uploaded with /server font, the font itself is in fonts/, see render::guild_font
+    #[serde(default)]
+    pub font: Option<String>,
+    // set with /server autorespond, None is the same as on
+    #[serde(default)]
+    pub autorespond: Option<AutoRespond>,
+    // the only channels it auto-responds in when autorespond is allowlist
+    #[serde(default)]
+    pub autorespond_channels: Vec<u64>,
+    // set with /server channels, None is the same as everywhere
+    #[serde(default)]
+    pub channel_mode: Option<ChannelMode>,
+    #[serde(default)]
+    pub listed_channels: Vec<u64>,
+    // set with /server permissions, the name of a command (see commands::menus) => the roles that can use it.
+    // a command that isn't in here can be used by everyone
+    #[serde(default)]
+    pub command_roles: HashMap<String, Vec<u64>>,
+}
+
+// Whether the bot replies to codeblocks nobody asked it about,
+// which is the automatic renders and asking what language a bare codeblock is
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoRespond {
+    On,
+    Off,
+    Allowlist,
+}
+
+// How jokey error messages sound, see tone.rs. A custom one has {message} where the message goes
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tone {
+    Owo,
+    Plain,
+    Custom(String),
+}
+
+// Where the bot is allowed to post anything publicly at all. Elsewhere it ignores messages,
+// and the context menu commands and buttons only reply to whoever used them
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelMode {
+    Everywhere,
+    // only in listed_channels
+    Allowlist,
+    // anywhere but listed_channels
+    Blocklist,
+}
+
+// Set with /preferences
+#[derive(Default, Serialize, Deserialize)]
+pub struct UserSettings {
+    #[serde(default)]
+    pub theme: Option<String>,
+    // text size for renders, in pixels
+    #[serde(default)]
+    pub size: Option<u32>,
+    // whether the context menu commands reply publicly instead of just to them, see public_output
+    #[serde(default)]
+    pub public: Option<bool>,
+    // set with /optout, the bot never replies to them unless they ask
+    #[serde(default)]
+    pub opted_out: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InstalledGrammar {
+    pub url: String,
+    // the name the grammar calls itself, which decides the tree_sitter_* symbol
+    pub grammar: String,
+    // the hash of the compiled library, which is also in its file name, see install.rs
+    #[serde(default)]
+    pub library: u64,
+}
+
+lazy_static! {
+    static ref DB: std::sync::Mutex<Connection> = std::sync::Mutex::new(open());
+    static ref DATA: std::sync::Mutex<Data> = std::sync::Mutex::new(load());
+    // the JSON of every row as it was last written, to know which ones changed
+    static ref SAVED: std::sync::Mutex<HashMap<(&'static str, u64), String>> = Default::default();
+}
+
+// called at startup, so a database that can't be opened or migrated fails then and not on the first message
+pub fn start() {
+    lazy_static::initialize(&DATA);
+}
+
+fn open() -> Connection {
+    let path = config::var("DATABASE_PATH").unwrap_or_else(|| DATABASE_PATH.to_owned());
+    let mut db = Connection::open(&path).unwrap_or_else(|e| panic!("couldn't open {path}: {e}"));
+    migrate(&mut db).unwrap_or_else(|e| panic!("couldn't migrate {path}: {e}"));
+    db
+}
+
+fn migrate(db: &mut Connection) -> rusqlite::Result<()> {
+    let version: i64 = db.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let tx = db.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i as i64 + 1)?;
+        tx.commit()?;
+        info!("migrated the database to version {}", i + 1);
+    }
+    Ok(())
+}
+
+fn load() -> Data {
+    let data = {
+        let db = DB.lock().unwrap();
+        Data {
+            guilds: rows(&db, "guilds").unwrap_or_else(|e| panic!("couldn't load guilds: {e}")),
+            users: rows(&db, "users").unwrap_or_else(|e| panic!("couldn't load users: {e}")),
+        }
+    };
+    if !data.guilds.is_empty() || !data.users.is_empty() {
+        return data;
+    }
+    let json = match fs::read_to_string(LEGACY_PATH) {
+        Ok(json) => json,
+        Err(_) => return data,
+    };
+    let data: Data =
+        serde_json::from_str(&json).unwrap_or_else(|e| panic!("couldn't parse {LEGACY_PATH}: {e}"));
+    save(&data).unwrap_or_else(|e| panic!("couldn't move {LEGACY_PATH} into the database: {e}"));
+    // kept around, just in case
+    let moved = format!("{LEGACY_PATH}.imported");
+    if let Err(e) = fs::rename(LEGACY_PATH, &moved) {
+        error!("couldn't rename {LEGACY_PATH} to {moved}: {e}");
+    }
+    info!("moved {LEGACY_PATH} into the database");
+    data
+}
+
+fn rows<T: DeserializeOwned>(
+    db: &Connection,
+    table: &'static str,
+) -> Result<HashMap<u64, T>, Box<dyn Error>> {
+    let mut saved = SAVED.lock().unwrap();
+    let mut statement = db.prepare(&format!("SELECT id, settings FROM {table}"))?;
+    let mut rows = statement.query([])?;
+    let mut settings = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let id = row.get::<_, i64>(0)? as u64;
+        let json: String = row.get(1)?;
+        settings.insert(id, serde_json::from_str(&json)?);
+        saved.insert((table, id), json);
+    }
+    Ok(settings)
+}
+
+pub fn read<T>(f: impl FnOnce(&Data) -> T) -> T {
+    f(&DATA.lock().unwrap())
+}
+
+// Something a guild has set, if it's in a guild and has set it
+pub fn guild_setting<T>(
+    guild_id: Option<GuildId>,
+    f: impl FnOnce(&GuildSettings) -> Option<T>,
+) -> Option<T> {
+    read(|data| data.guilds.get(&guild_id?.0).and_then(f))
+}
+
+// Something a user has set with /preferences, if they have
+pub fn user_setting<T>(user: UserId, f: impl FnOnce(&UserSettings) -> Option<T>) -> Option<T> {
+    read(|data| data.users.get(&user.0).and_then(f))
+}
+
+// The context menu commands only show their output to whoever used them, unless they'd rather it be a public reply
+pub fn public_output(user: UserId) -> bool {
+    user_setting(user, |user| user.public).unwrap_or(false)
+}
+
+// DMs don't have settings, so it always can there
+pub fn posts_in(guild_id: Option<GuildId>, channel: ChannelId) -> bool {
+    let setting = guild_setting(guild_id, |guild| {
+        Some((guild.channel_mode?, guild.listed_channels.contains(&channel.0)))
+    });
+    match setting {
+        None | Some((ChannelMode::Everywhere, _)) => true,
+        Some((ChannelMode::Allowlist, listed)) => listed,
+        Some((ChannelMode::Blocklist, listed)) => !listed,
+    }
+}
+
+// DMs don't have settings, so it always does there
+pub fn auto_responds(guild_id: Option<GuildId>, channel: ChannelId) -> bool {
+    let setting = guild_setting(guild_id, |guild| {
+        Some((guild.autorespond?, guild.autorespond_channels.contains(&channel.0)))
+    });
+    match setting {
+        None | Some((AutoRespond::On, _)) => true,
+        Some((AutoRespond::Off, _)) => false,
+        Some((AutoRespond::Allowlist, allowed)) => allowed,
+    }
+}
+
+pub fn opted_out(user: UserId) -> bool {
+    user_setting(user, |user| Some(user.opted_out)).unwrap_or(false)
+}
+
+pub fn write<T>(f: impl FnOnce(&mut Data) -> T) -> T {
+    let mut data = DATA.lock().unwrap();
+    let result = f(&mut data);
+    if let Err(e) = save(&data) {
+        error!("couldn't save the settings: {e}");
+    }
+    result
+}
+
+// Writes whichever guilds and users changed since the last time, all at once
+fn save(data: &Data) -> Result<(), Box<dyn Error>> {
+    // the same order as rows locks them in
+    let mut db = DB.lock().unwrap();
+    let mut saved = SAVED.lock().unwrap();
+    let mut current = HashMap::new();
+    for (&id, guild) in &data.guilds {
+        current.insert(("guilds", id), serde_json::to_string(guild)?);
+    }
+    for (&id, user) in &data.users {
+        current.insert(("users", id), serde_json::to_string(user)?);
+    }
+    let tx = db.transaction()?;
+    for (&(table, id), json) in &current {
+        if saved.get(&(table, id)) != Some(json) {
+            tx.execute(
+                &format!(
+                    "INSERT INTO {table} (id, settings) VALUES (?1, ?2)
+                    ON CONFLICT (id) DO UPDATE SET settings = ?2"
+                ),
+                params![id as i64, json],
+            )?;
+        }
+    }
+    for &(table, id) in saved.keys().filter(|key| !current.contains_key(key)) {
+        tx.execute(&format!("DELETE FROM {table} WHERE id = ?1"), [id as i64])?;
+    }
+    tx.commit()?;
+    *saved = current;
+    Ok(())
+}
+
+// The replies to a codeblock, so they can go away with it even if the bot restarted in between
+pub fn track_replies(source: MessageId, replies: &[Message]) {
+    if let Err(e) = insert_replies(source, replies) {
+        error!("couldn't save the replies to {source}: {e}");
+    }
+}
+
+fn insert_replies(source: MessageId, replies: &[Message]) -> rusqlite::Result<()> {
+    let mut db = DB.lock().unwrap();
+    let tx = db.transaction()?;
+    for reply in replies {
+        tx.execute(
+            "INSERT INTO replies (source, channel, reply, webhook) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                source.0 as i64,
+                reply.channel_id.0 as i64,
+                reply.id.0 as i64,
+                reply.webhook_id.map(|id| id.0 as i64),
+            ],
+        )?;
+    }
+    tx.execute(
+        "DELETE FROM replies WHERE rowid <= (SELECT MAX(rowid) FROM replies) - ?1",
+        [TRACKED_REPLIES],
+    )?;
+    tx.commit()
+}
+
+// Takes them out, since they're either deleted or replaced by whoever asked for them
+pub fn take_replies(source: MessageId) -> Vec<(ChannelId, MessageId, Option<WebhookId>)> {
+    select_replies(source).unwrap_or_else(|e| {
+        error!("couldn't load the replies to {source}: {e}");
+        Vec::new()
+    })
+}
+
+fn select_replies(
+    source: MessageId,
+) -> rusqlite::Result<Vec<(ChannelId, MessageId, Option<WebhookId>)>> {
+    let db = DB.lock().unwrap();
+    let replies = db
+        .prepare("SELECT channel, reply, webhook FROM replies WHERE source = ?1")?
+        .query_map([source.0 as i64], |row| {
+            Ok((
+                ChannelId(row.get::<_, i64>(0)? as u64),
+                MessageId(row.get::<_, i64>(1)? as u64),
+                row.get::<_, Option<i64>>(2)?.map(|id| WebhookId(id as u64)),
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    db.execute("DELETE FROM replies WHERE source = ?1", [source.0 as i64])?;
+    Ok(replies)
+}
+
+// One more of something, for how many there have been ever in /about bot
+pub fn count(name: &str) {
+    let db = DB.lock().unwrap();
+    let result = db.execute(
+        "INSERT INTO usage (name, count) VALUES (?1, 1)
+        ON CONFLICT (name) DO UPDATE SET count = count + 1",
+        [name],
+    );
+    if let Err(e) = result {
+        error!("couldn't count {name}: {e}");
+    }
+}
+
+pub fn counted(name: &str) -> u64 {
+    let db = DB.lock().unwrap();
+    let result = db.query_row("SELECT count FROM usage WHERE name = ?1", [name], |row| {
+        row.get::<_, i64>(0)
+    });
+    match result {
+        Ok(count) => count as u64,
+        Err(rusqlite::Error::QueryReturnedNoRows) => 0,
+        Err(e) => {
+            error!("couldn't load how many {name} there have been: {e}");
+            0
+        }
+    }
+}
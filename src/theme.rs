@@ -0,0 +1,36 @@
+// The themes themselves live in custom-highlight-core, this is only who picked which one
+use super::*;
+pub use custom_highlight_core::theme::*;
+
+// What someone picked with /preferences theme, or what their server picked with /server theme,
+// or the default if neither did
+pub fn preferred(user: UserId, guild_id: Option<GuildId>) -> &'static Theme {
+    let (user, guild) = storage::read(|data| {
+        let user = data
+            .users
+            .get(&user.0)
+            .and_then(|settings| settings.theme.clone());
+        let guild = guild_id
+            .and_then(|guild_id| data.guilds.get(&guild_id.0))
+            .and_then(|settings| settings.theme.clone());
+        (user, guild)
+    });
+    // a theme that got removed since then is just ignored
+    [user, guild]
+        .into_iter()
+        .flatten()
+        .find_map(|name| by_name(&name))
+        .unwrap_or(DEFAULT)
+}
+
+pub fn set_preferred(user: UserId, theme: Option<&'static Theme>) {
+    storage::write(|data| {
+        data.users.entry(user.0).or_default().theme = theme.map(|theme| theme.name.to_owned())
+    });
+}
+
+pub fn set_guild_default(guild_id: GuildId, theme: Option<&'static Theme>) {
+    storage::write(|data| {
+        data.guilds.entry(guild_id.0).or_default().theme = theme.map(|theme| theme.name.to_owned())
+    });
+}
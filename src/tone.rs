@@ -0,0 +1,26 @@
+use super::*;
+
+// How owo! messages sound, set per server with /server tone. owo! doesn't know what server it's in,
+// so every event is handled inside scope() with its guild, and owo! looks it up from there.
+// Anything outside of that (like in a spawn_blocking) just gets the default, which is owo
+tokio::task_local! {
+    static GUILD: Option<GuildId>;
+}
+
+pub async fn scope<F: std::future::Future>(guild_id: Option<GuildId>, f: F) -> F::Output {
+    GUILD.scope(guild_id, f).await
+}
+
+// what owo! does with the message once it's formatted
+pub fn apply(message: String) -> String {
+    let guild_id = GUILD.try_with(|guild_id| *guild_id).ok().flatten();
+    match storage::guild_setting(guild_id, |guild| guild.tone.clone()) {
+        Some(storage::Tone::Plain) => message,
+        Some(storage::Tone::Custom(template)) => template.replace("{message}", &message),
+        Some(storage::Tone::Owo) | None => message
+            .owoify(OwoifyLevel::Uvu)
+            .owoify(OwoifyLevel::Uvu)
+            .owoify(OwoifyLevel::Uvu)
+            .owoify(OwoifyLevel::Uvu),
+    }
+}
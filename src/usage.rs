@@ -0,0 +1,60 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+use super::*;
+
+// What the bot has been up to since it started, for /about bot. These start over on a restart,
+// and the database counts them too, for how many there have been ever (see storage::count)
+static CACHED_RENDERS: AtomicU64 = AtomicU64::new(0);
+static DRAWN_RENDERS: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    static ref STARTED: Instant = Instant::now();
+}
+
+// called at startup, otherwise the uptime would start at the first /about bot
+pub fn start() {
+    lazy_static::initialize(&STARTED);
+}
+
+// a render that actually got sent, cached means it was already in the render cache (see cache.rs)
+pub fn rendered(cached: bool) {
+    let (counter, name) = if cached {
+        (&CACHED_RENDERS, "cached_renders")
+    } else {
+        (&DRAWN_RENDERS, "drawn_renders")
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+    storage::count(name);
+}
+
+pub fn report() -> String {
+    let uptime = STARTED.elapsed().as_secs();
+    let (days, hours, minutes) = (uptime / 86400, uptime / 3600 % 24, uptime / 60 % 60);
+    let cached = CACHED_RENDERS.load(Ordering::Relaxed);
+    let renders = cached + DRAWN_RENDERS.load(Ordering::Relaxed);
+    let hit_rate = match renders {
+        0 => tr!("about-nothing-yet"),
+        _ => format!("{}%", cached * 100 / renders),
+    };
+    let (running, waiting) = queue::depth();
+    let (connected, shards) = health::shards();
+    let ever = storage::counted("cached_renders") + storage::counted("drawn_renders");
+    tr!(
+        "about",
+        version = env!("CARGO_PKG_VERSION"),
+        days = days,
+        hours = hours,
+        minutes = minutes,
+        languages = all_languages().len(),
+        renders = renders,
+        ever = ever,
+        hit_rate = hit_rate,
+        running = running,
+        waiting = waiting,
+        connected = connected,
+        shards = shards,
+    )
+}
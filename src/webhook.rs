@@ -0,0 +1,111 @@
+use super::*;
+use serenity::model::{channel::ChannelType, id::WebhookId, webhook::Webhook};
+
+// With /server webhooks, +highlight posts through a webhook with the name and avatar of whoever wrote the code,
+// so it reads like they posted it highlighted themselves. There's one webhook per channel, made by the bot
+// the first time it's needed. Webhooks can't have buttons, so every page is its own message.
+// Threads and DMs don't get this, since there's no webhook to use there
+const WEBHOOK_NAME: &str = "Highlight";
+
+lazy_static! {
+    static ref WEBHOOKS: std::sync::Mutex<HashMap<ChannelId, Webhook>> = Default::default();
+}
+
+// None means it should just be sent normally, because it's not turned on or it didn't work out
+pub async fn send(
+    ctx: &Context,
+    channel: &Channel,
+    ansi: &str,
+    options: &Options,
+    reply_to: ReplyMethod<'_>,
+) -> Option<Vec<Message>> {
+    let (channel, referenced) = match (channel, reply_to) {
+        (Channel::Guild(channel), ReplyMethod::PublicReference(referenced))
+            if matches!(channel.kind, ChannelType::Text | ChannelType::News) =>
+        {
+            (channel, referenced)
+        }
+        _ => return None,
+    };
+    if !storage::guild_setting(Some(channel.guild_id), |guild| guild.webhooks).unwrap_or(false) {
+        return None;
+    }
+    let tab_width = options.tab_width.unwrap_or(DEFAULT_TAB_WIDTH);
+    let chunks = chunk_ansi(ansi, tab_width, options.spoiler, 2000).ok()?;
+    // anything that long goes in a file anyways
+    if chunks.len() > MAX_ANSI_PAGES {
+        return None;
+    }
+    let webhook = match webhook(ctx, channel.id).await {
+        Ok(webhook) => webhook,
+        // probably no permission to manage webhooks
+        Err(why) => {
+            error!("{why}");
+            return None;
+        }
+    };
+    let name = referenced
+        .author_nick(&ctx)
+        .await
+        .unwrap_or_else(|| referenced.author.name.clone());
+    let avatar = referenced.author.face();
+    let mut sent = Vec::with_capacity(chunks.len());
+    for chunk in chunks {
+        let result = webhook
+            .execute(&ctx, true, |msg| {
+                msg.content(chunk).username(&name).avatar_url(&avatar)
+            })
+            .await;
+        match result {
+            Ok(Some(message)) => sent.push(message),
+            Ok(None) => (),
+            // someone deleted the webhook, so the next one makes a new one
+            Err(why) => {
+                error!("{why}");
+                WEBHOOKS.lock().unwrap().remove(&channel.id);
+                return if sent.is_empty() { None } else { Some(sent) };
+            }
+        }
+    }
+    Some(sent)
+}
+
+// Deleting a message from a webhook needs the webhook, or permission to delete anyone's messages
+// The message itself isn't needed, since replies from before a restart are only ids, see storage.rs
+pub async fn delete(
+    ctx: &Context,
+    channel: ChannelId,
+    message: MessageId,
+    webhook_id: Option<WebhookId>,
+) -> serenity::Result<()> {
+    let webhook = match webhook_id {
+        Some(id) => WEBHOOKS
+            .lock()
+            .unwrap()
+            .get(&channel)
+            .filter(|webhook| webhook.id == id)
+            .cloned(),
+        None => None,
+    };
+    match webhook {
+        Some(webhook) => webhook.delete_message(&ctx, message).await,
+        None => channel.delete_message(&ctx, message).await,
+    }
+}
+
+async fn webhook(ctx: &Context, channel: ChannelId) -> serenity::Result<Webhook> {
+    if let Some(webhook) = WEBHOOKS.lock().unwrap().get(&channel) {
+        return Ok(webhook.clone());
+    }
+    // the bot made one before it last restarted
+    let own = ctx.cache.current_user_id();
+    let existing = channel.webhooks(&ctx).await?.into_iter().find(|webhook| {
+        webhook.token.is_some() && webhook.user.as_ref().map(|user| user.id) == Some(own)
+    });
+    let webhook = match existing {
+        Some(webhook) => webhook,
+        None => channel.create_webhook(&ctx, WEBHOOK_NAME).await?,
+    };
+    WEBHOOKS.lock().unwrap().insert(channel, webhook.clone());
+    Ok(webhook)
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use super::*;
+use tokio::sync::Semaphore;
+
+// Highlighting, parsing and rendering keep a CPU busy for as long as they take, so none of them run
+// on the async executor, where one huge codeblock would hold up the gateway and everyone else with it.
+// They go on the blocking threads instead, but only WORKERS of them at once (as many as there are CPUs
+// by default), and the rest wait for one to be free.
+//
+// Every kind of task has a budget, HIGHLIGHT_BUDGET, PARSE_BUDGET and RENDER_BUDGET in seconds, and
+// whoever asked stops waiting once it's over. A thread can't be stopped from the outside, so the work
+// still keeps its worker until it's done, except for parsing, which tree-sitter gives up on by itself
+const DEFAULT_HIGHLIGHT_BUDGET: u64 = 10;
+const DEFAULT_PARSE_BUDGET: u64 = 10;
+// it can draw it more than once to make it fit, see render_to_fit
+const DEFAULT_RENDER_BUDGET: u64 = 60;
+
+lazy_static! {
+    static ref WORKERS: usize = config::parse("WORKERS").unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(2, |workers| workers.get())
+    });
+    static ref SLOTS: Arc<Semaphore> = Arc::new(Semaphore::new(*WORKERS));
+    static ref HIGHLIGHT_BUDGET: u64 =
+        config::parse("HIGHLIGHT_BUDGET").unwrap_or(DEFAULT_HIGHLIGHT_BUDGET);
+    static ref PARSE_BUDGET: u64 = config::parse("PARSE_BUDGET").unwrap_or(DEFAULT_PARSE_BUDGET);
+    static ref RENDER_BUDGET: u64 = config::parse("RENDER_BUDGET").unwrap_or(DEFAULT_RENDER_BUDGET);
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Task {
+    Highlight,
+    Parse,
+    Render,
+}
+
+impl Task {
+    pub fn budget(self) -> Duration {
+        Duration::from_secs(match self {
+            Task::Highlight => *HIGHLIGHT_BUDGET,
+            Task::Parse => *PARSE_BUDGET,
+            Task::Render => *RENDER_BUDGET,
+        })
+    }
+}
+
+// Does the work on a worker once one is free. The budget only starts once it's running,
+// waiting for a worker is just like waiting in the render queue
+pub async fn run<T, E>(
+    task: Task,
+    work: impl FnOnce() -> Result<T, E> + Send + 'static,
+) -> Result<T, CommandError>
+where
+    T: Send + 'static,
+    E: Into<CommandError> + Send + 'static,
+{
+    let budget = task.budget();
+    let permit = SLOTS
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("the workers are never closed");
+    // the blocking thread isn't in the command's span, so whatever it logs wouldn't be either
+    let span = Span::current();
+    // and it's not in locale::scope either
+    let locale = locale::current();
+    let running = tokio::task::spawn_blocking(move || {
+        // the worker is only free again once the work is really done, even if nobody's waiting for it
+        let _permit = permit;
+        locale::sync_scope(locale, || span.in_scope(work))
+    });
+    match tokio::time::timeout(budget, running).await {
+        Ok(result) => result?.map_err(Into::into),
+        Err(_) => {
+            warn!(?task, "gave up after {}s", budget.as_secs());
+            Err(tr!("too-long").into())
+        }
+    }
+}
+
+// Like parse, but it stops by itself once it's over the budget, so it doesn't keep the worker either.
+// It's cached just the same, see core's cache.rs
+// old is the tree from before the code was edited, if there is one
+pub fn parse(
+    config: &LanguageConfig,
+    code: &str,
+    old: Option<&Tree>,
+) -> Result<Tree, CommandError> {
+    let parse = || -> Result<Tree, CommandError> {
+        let mut parser = parser(config)?;
+        parser.set_timeout_micros(Task::Parse.budget().as_micros() as u64);
+        parser
+            .parse(code, old)
+            .ok_or_else(|| tr!("too-long").into())
+    };
+    // an edited codeblock is new code anyway, see reparse.rs
+    match old {
+        Some(_) => parse(),
+        None => custom_highlight_core::cache::tree(config, code, parse),
+    }
+}
@@ -0,0 +1,116 @@
+// The custom_ids the bot makes, and a few it doesn't, see fuzz/ for the rest
+use custom_highlight::custom_ids;
+
+#[test]
+fn button() {
+    let button = custom_ids::button("highlight-991266068330975302");
+    assert_eq!(button.kind, "highlight");
+    assert_eq!(button.message, Some(991266068330975302));
+    assert!(!button.ephemeralish);
+
+    let button = custom_ids::button("pretty-parse");
+    assert_eq!(button.kind, "pretty-parse");
+    assert_eq!(button.message, None);
+
+    let button = custom_ids::button("delete-ephemeralish");
+    assert_eq!(button.kind, "delete");
+    assert!(button.ephemeralish);
+
+    let button = custom_ids::button("relang-render-991266068330975302");
+    assert_eq!(button.kind, "relang-render");
+    assert_eq!(button.message, Some(991266068330975302));
+}
+
+// the language used to lose anything after its last - if that looked like a message id
+#[test]
+fn detected_language() {
+    let button = custom_ids::button("render@ursl");
+    assert_eq!((button.kind, button.lang), ("render", Some("ursl")));
+
+    let button = custom_ids::button("highlight@urcl-2");
+    assert_eq!((button.kind, button.lang), ("highlight", Some("urcl-2")));
+    assert_eq!(button.message, None);
+}
+
+#[test]
+fn menus() {
+    assert_eq!(custom_ids::block("block-render-12"), Some(("render", 12)));
+    assert_eq!(custom_ids::block("block-render-"), None);
+    assert_eq!(
+        custom_ids::lang("lang-pretty-parse-12-0"),
+        Some(("pretty-parse", 12))
+    );
+    assert_eq!(custom_ids::lang("lang-12"), None);
+    assert_eq!(
+        custom_ids::expand("expand-plain.verbose-3-12"),
+        Some(("plain.verbose", 3, 12))
+    );
+    assert_eq!(custom_ids::expand("expand-plain--12"), None);
+    assert_eq!(custom_ids::page("page-next-2"), Some(2));
+    assert_eq!(custom_ids::page("page-next-ü"), None);
+}
+
+// whatever the bot makes, it has to be able to take apart again
+#[test]
+fn round_trip() {
+    let id = custom_ids::button_id("parse-json", Some(12));
+    let button = custom_ids::button(&id);
+    assert_eq!((button.kind, button.message), ("parse-json", Some(12)));
+    let id = custom_ids::detected_id("render", "objective-c");
+    let button = custom_ids::button(&id);
+    assert_eq!((button.kind, button.lang), ("render", Some("objective-c")));
+    let id = custom_ids::ephemeralish_id("delete");
+    let button = custom_ids::button(&id);
+    assert_eq!(button.kind, "delete");
+    assert!(button.ephemeralish);
+
+    let id = custom_ids::relang_id("pretty-parse", 12);
+    let button = custom_ids::button(&id);
+    assert_eq!(custom_ids::relang(button.kind), Some("pretty-parse"));
+    assert_eq!(button.message, Some(12));
+    let id = custom_ids::help_id("render", "urcl-2");
+    let button = custom_ids::button(&id);
+    assert_eq!(custom_ids::help(button.kind), Some("render"));
+    assert_eq!(button.lang, Some("urcl-2"));
+
+    let id = custom_ids::block_id("pretty-parse", 12);
+    assert_eq!(custom_ids::block(&id), Some(("pretty-parse", 12)));
+    let id = custom_ids::lang_id("pretty-parse", 12, 3);
+    assert_eq!(custom_ids::lang(&id), Some(("pretty-parse", 12)));
+    let id = custom_ids::expand_id("plain.verbose", 3, 12);
+    assert_eq!(custom_ids::expand(&id), Some(("plain.verbose", 3, 12)));
+    assert_eq!(custom_ids::page(&custom_ids::page_id("prev", 2)), Some(2));
+
+    let id = custom_ids::code_modal_id("render", "objective-c");
+    assert_eq!(custom_ids::modal(&id), Some((false, "render", "objective-c")));
+    let id = custom_ids::scratchpad_id("highlight");
+    assert_eq!(custom_ids::modal(&id), Some((true, "highlight", "")));
+}
+
+// nothing the bot would make, but all of these come from the fuzzer's corpus
+#[test]
+fn garbage() {
+    let button = custom_ids::button("");
+    assert_eq!((button.kind, button.lang, button.message), ("", None, None));
+
+    let button = custom_ids::button("-ephemeralish");
+    assert_eq!(button.kind, "");
+    assert!(button.ephemeralish);
+
+    let button = custom_ids::button("-1");
+    assert_eq!((button.kind, button.message), ("", Some(1)));
+
+    let button = custom_ids::button("@-18446744073709551616");
+    assert_eq!(button.lang, Some("-18446744073709551616"));
+    assert_eq!(button.message, None);
+
+    let button = custom_ids::button("ü-18446744073709551616");
+    assert_eq!((button.kind, button.message), ("ü-18446744073709551616", None));
+
+    assert_eq!(custom_ids::block("block--1"), Some(("", 1)));
+    assert_eq!(custom_ids::lang("lang---"), None);
+    assert_eq!(custom_ids::expand("expand-"), None);
+    assert_eq!(custom_ids::expand("expand-a-99999999999999999999999-1"), None);
+    assert_eq!(custom_ids::page("page-"), None);
+    assert_eq!(custom_ids::page("page-next-18446744073709551616"), None);
+}